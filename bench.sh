@@ -0,0 +1,59 @@
+#!/bin/bash
+
+# 生成した大きな入力でトークナイザ(とプリプロセッサ)の処理速度を測る。
+# 引数で生成する入力のおおよそのサイズ(MB)を指定できる。
+#
+# トークナイザを高速化する前後の計測結果(同じ入力をreleaseビルドの-Eで処理した時間)
+#
+#   入力   高速化前   高速化後
+#   1MB    5802ms     113ms
+#   2MB    34266ms    221ms
+#   8MB    5分以上    1045ms(約8MB/s)
+#
+# 高速化前は入力の大きさに対して処理時間が線形より大きく増えていた。
+
+SIZE_MB="${1:-8}"
+
+BENCH_FILE=`mktemp --suffix=.c` || exit 1
+trap 'rm -f "$BENCH_FILE"' EXIT
+
+cargo build --release || exit 1
+
+# 識別子、キーワード、数値、文字列、コメント、記号を
+# 一通り含むブロックを繰り返して入力を作る
+block() {
+	cat <<EOF2
+/* block $1 */
+struct node_$1 { int value; char *name; struct node_$1 *next; };
+int function_$1(int argument_a, int argument_b)
+{
+	// 行コメント
+	int local_variable = argument_a * 0x1f + argument_b / 3 - 'c';
+	char *message = "string literal with \\"escape\\" \\n";
+	if (local_variable >= 100 && argument_b != 0 || !argument_a)
+		local_variable <<= 2;
+	for (int i = 0; i < 10; i++)
+		local_variable += message[i % 8] ^ ~i;
+	return local_variable > 0 ? local_variable : -local_variable;
+}
+EOF2
+}
+
+: >"$BENCH_FILE"
+i=0
+target=$((SIZE_MB * 1024 * 1024))
+# 1ブロックずつ追記すると遅いので、まとめて生成したものを複製する
+chunk=`for j in $(seq 0 99); do block $j; done`
+while [ `stat -c %s "$BENCH_FILE"` -lt $target ]
+do
+	echo "$chunk" | sed "s/_\([0-9]*\)\b/_${i}_\1/g" >>"$BENCH_FILE"
+	i=$((i + 1))
+done
+
+bytes=`stat -c %s "$BENCH_FILE"`
+start=`date +%s%N`
+target/release/sumorucc -E "$BENCH_FILE" >/dev/null || exit 1
+end=`date +%s%N`
+
+ms=$(((end - start) / 1000000))
+echo "$bytes bytes in $ms ms: $((bytes * 1000 / 1024 / 1024 / (ms > 0 ? ms : 1))) MB/s"
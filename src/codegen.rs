@@ -1,8 +1,10 @@
 use std::fmt;
+use std::rc::Rc;
 
-use super::ctype::{CType, Integer};
+use super::ctype::{CType, Floating, Integer};
 use super::node::{Node, NodeKind};
 use super::parse_context::{GVar, ParseContext, Str};
+use super::tokenize::{EmbedFile, TokenKind};
 use super::util::align_to;
 
 macro_rules! code {
@@ -33,10 +35,7 @@ impl Debug {
     }
 
     fn find_file(&self, file: &str) -> Option<usize> {
-        match self.file.iter().find(|f| f.0 == file) {
-            Some(f) => Some(f.1),
-            None => None,
-        }
+        self.file.iter().find(|f| f.0 == file).map(|f| f.1)
     }
 
     fn add_file(&mut self, file: &str) -> Result<usize, ()> {
@@ -86,6 +85,22 @@ enum Register {
     R8,
     R9,
 
+    // 下位32bit
+    EDI,
+    ESI,
+    EDX,
+    ECX,
+    R8D,
+    R9D,
+
+    // 下位16bit
+    DI,
+    SI,
+    DX,
+    CX,
+    R8W,
+    R9W,
+
     // 下位8bit
     #[allow(dead_code)]
     AL,
@@ -110,6 +125,26 @@ static ARG_REG64: [Register; 6] = [
     Register::R9,
 ];
 
+// 関数呼び出しの際に引数をセットするレジスタ(32bit)
+static ARG_REG32: [Register; 6] = [
+    Register::EDI,
+    Register::ESI,
+    Register::EDX,
+    Register::ECX,
+    Register::R8D,
+    Register::R9D,
+];
+
+// 関数呼び出しの際に引数をセットするレジスタ(16bit)
+static ARG_REG16: [Register; 6] = [
+    Register::DI,
+    Register::SI,
+    Register::DX,
+    Register::CX,
+    Register::R8W,
+    Register::R9W,
+];
+
 // 関数呼び出しの際に引数をセットするレジスタ(8bit)
 static ARG_REG8: [Register; 6] = [
     Register::DIL,
@@ -132,6 +167,20 @@ impl fmt::Display for Register {
             Self::R8 => write!(f, "%r8"),
             Self::R9 => write!(f, "%r9"),
 
+            Self::EDI => write!(f, "%edi"),
+            Self::ESI => write!(f, "%esi"),
+            Self::EDX => write!(f, "%edx"),
+            Self::ECX => write!(f, "%ecx"),
+            Self::R8D => write!(f, "%r8d"),
+            Self::R9D => write!(f, "%r9d"),
+
+            Self::DI => write!(f, "%di"),
+            Self::SI => write!(f, "%si"),
+            Self::DX => write!(f, "%dx"),
+            Self::CX => write!(f, "%cx"),
+            Self::R8W => write!(f, "%r8w"),
+            Self::R9W => write!(f, "%r9w"),
+
             Self::AL => write!(f, "%al"),
             Self::DIL => write!(f, "%dil"),
             Self::BPL => write!(f, "%bpl"),
@@ -154,12 +203,73 @@ fn pop(reg: Register, ctx: &mut Context) {
     code!("pop {}", reg);
 }
 
-// 左辺の結果をraxに、右辺の結果をrdiにセットする
+// SSEレジスタはpushできないのでスタックを直接操作する
+fn push_xmm(n: usize, ctx: &mut Context) {
+    ctx.stack += 1;
+    code!("sub $8, %rsp");
+    code!("movsd %xmm{}, (%rsp)", n);
+}
+
+fn pop_xmm(n: usize, ctx: &mut Context) {
+    ctx.stack -= 1;
+    code!("movsd (%rsp), %xmm{}", n);
+    code!("add $8, %rsp");
+}
+
+// x87のスタックは8段しかないので、途中の値はメモリのスタックに退避する。
+// アライメントを崩さないように16バイト使う。
+fn push_x87(ctx: &mut Context) {
+    ctx.stack += 2;
+    code!("sub $16, %rsp");
+    code!("fstpt (%rsp)");
+}
+
+fn pop_x87(ctx: &mut Context) {
+    ctx.stack -= 2;
+    code!("fldt (%rsp)");
+    code!("add $16, %rsp");
+}
+
+// 左辺の結果をraxに、右辺の結果をrdiにセットする。
+// floatとdoubleなら左辺の結果をxmm0に、右辺の結果をxmm1にセットする。
+// long doubleなら左辺の結果をst(1)に、右辺の結果をst(0)にセットする。
 fn gen_binary_operator(lhs: &Node, rhs: &Node, ctx: &mut Context) {
     gen(rhs, ctx);
-    push(Register::RAX, ctx);
-    gen(lhs, ctx);
-    pop(Register::RDI, ctx);
+    if is_x87(&rhs.ctype) {
+        push_x87(ctx);
+        gen(lhs, ctx);
+        pop_x87(ctx);
+    } else if is_sse(&rhs.ctype) {
+        push_xmm(0, ctx);
+        gen(lhs, ctx);
+        pop_xmm(1, ctx);
+    } else {
+        push(Register::RAX, ctx);
+        gen(lhs, ctx);
+        pop(Register::RDI, ctx);
+    }
+}
+
+// long doubleはx87で計算し、値はst(0)に置く
+fn is_x87(ctype: &CType) -> bool {
+    matches!(ctype, CType::Floating(Floating::LongDouble))
+}
+
+// floatとdoubleはSSEで計算し、値はxmm0に置く
+fn is_sse(ctype: &CType) -> bool {
+    matches!(
+        ctype,
+        CType::Floating(Floating::Float) | CType::Floating(Floating::Double)
+    )
+}
+
+// 浮動小数点数の命令に付けるサフィックス
+fn float_suffix(ctype: &CType) -> &str {
+    match ctype {
+        CType::Floating(Floating::Float) => "ss",
+        CType::Floating(Floating::Double) => "sd",
+        _ => unreachable!(),
+    }
 }
 
 // 変数のアドレスをraxにセットする
@@ -169,7 +279,7 @@ fn gen_lval(node: &Node, ctx: &mut Context) {
             code!("lea -{}(%rbp), %rax", offset);
         }
         NodeKind::GVar(name, _) => {
-            code!("lea {}(%rip), %rax", name);
+            code!("lea {}(%rip), %rax", symbol(name));
         }
         NodeKind::Deref(operand) => {
             gen(operand, ctx);
@@ -184,36 +294,276 @@ fn gen_lval(node: &Node, ctx: &mut Context) {
     }
 }
 
-// raxが指すアドレスの値をraxにセットする
+// raxが指すアドレスの値をraxにセットする。
+// 8バイトより小さい整数は型に従って64bitに拡張する。
 fn gen_load(ctype: &CType) {
     match ctype {
-        CType::Integer(Integer::Char) => code!("movsbq (%rax), %rax"),
-        CType::Integer(Integer::Int) => code!("mov (%rax), %rax"),
-        CType::Pointer(_) => code!("mov (%rax), %rax"),
+        CType::Integer(Integer::Bool) => {
+            code!("movzbq (%rax), %rax");
+        }
+        CType::Integer(Integer::Char) => {
+            code!("movsbq (%rax), %rax");
+        }
+        CType::Integer(Integer::UChar) => {
+            code!("movzbq (%rax), %rax");
+        }
+        CType::Integer(Integer::Short) => {
+            code!("movswq (%rax), %rax");
+        }
+        CType::Integer(Integer::UShort) => {
+            code!("movzwq (%rax), %rax");
+        }
+        CType::Integer(Integer::Int) => {
+            code!("movslq (%rax), %rax");
+        }
+        // 32bitレジスタへの書き込みは上位32bitをゼロクリアする
+        CType::Integer(Integer::UInt) => {
+            code!("movl (%rax), %eax");
+        }
+        CType::Integer(_) | CType::Pointer(_) => {
+            code!("mov (%rax), %rax");
+        }
+        // 浮動小数点数はxmm0かst(0)にセットする
+        CType::Floating(Floating::LongDouble) => {
+            code!("fldt (%rax)");
+        }
+        CType::Floating(_) => {
+            code!("mov{} (%rax), %xmm0", float_suffix(ctype));
+        }
         // 値がraxに入りきる保障が無い型はなにもせず
         // gen_load呼び出し元で個別に対応する。
-        CType::Array(..) | CType::Struct(..) | CType::Union(..) => (),
+        CType::Array(..) | CType::Struct(..) | CType::Union(..) | CType::Function(..) => (),
         _ => unreachable!(),
     }
 }
 
+// raxの下位にあるctypeの値を、型に従って64bitに拡張する
+fn gen_extend(ctype: &CType) {
+    match ctype {
+        // 0以外の値は全て1にする
+        CType::Integer(Integer::Bool) => {
+            code!("cmp $0, %rax");
+            code!("setne %al");
+            code!("movzbq %al, %rax");
+        }
+        CType::Integer(Integer::Char) => {
+            code!("movsbq %al, %rax");
+        }
+        CType::Integer(Integer::UChar) => {
+            code!("movzbq %al, %rax");
+        }
+        CType::Integer(Integer::Short) => {
+            code!("movswq %ax, %rax");
+        }
+        CType::Integer(Integer::UShort) => {
+            code!("movzwq %ax, %rax");
+        }
+        CType::Integer(Integer::Int) => {
+            code!("movslq %eax, %rax");
+        }
+        CType::Integer(Integer::UInt) => {
+            code!("mov %eax, %eax");
+        }
+        _ => (),
+    }
+}
+
+// raxまたはxmm0にあるfromの値をtoに変換する
+fn gen_cast(from: &CType, to: &CType, ctx: &mut Context) {
+    match (from, to) {
+        (CType::Floating(Floating::LongDouble), CType::Floating(Floating::LongDouble)) => (),
+        // x87とSSEの間はメモリを経由して受け渡す
+        (CType::Floating(Floating::LongDouble), CType::Floating(Floating::Float)) => {
+            code!("sub $8, %rsp");
+            code!("fstps (%rsp)");
+            code!("movss (%rsp), %xmm0");
+            code!("add $8, %rsp");
+        }
+        (CType::Floating(Floating::LongDouble), CType::Floating(Floating::Double)) => {
+            code!("sub $8, %rsp");
+            code!("fstpl (%rsp)");
+            code!("movsd (%rsp), %xmm0");
+            code!("add $8, %rsp");
+        }
+        (CType::Floating(Floating::Float), CType::Floating(Floating::LongDouble)) => {
+            code!("sub $8, %rsp");
+            code!("movss %xmm0, (%rsp)");
+            code!("flds (%rsp)");
+            code!("add $8, %rsp");
+        }
+        (CType::Floating(Floating::Double), CType::Floating(Floating::LongDouble)) => {
+            code!("sub $8, %rsp");
+            code!("movsd %xmm0, (%rsp)");
+            code!("fldl (%rsp)");
+            code!("add $8, %rsp");
+        }
+        (CType::Floating(Floating::LongDouble), CType::Integer(Integer::Bool)) => {
+            code!("fldz");
+            code!("fucomip %st(1), %st");
+            code!("fstp %st(0)");
+            code!("setne %al");
+            code!("setp %dl");
+            code!("or %dl, %al");
+            code!("movzb %al, %rax");
+        }
+        // fisttpは丸めモードによらず小数部を切り捨てる。
+        // 2^63以上の値は2^63を引いてから変換し最上位ビットを立てる。
+        (CType::Floating(Floating::LongDouble), CType::Integer(Integer::ULong))
+        | (CType::Floating(Floating::LongDouble), CType::Integer(Integer::ULongLong)) => {
+            let label = ctx.label;
+            ctx.label += 1;
+
+            code!("sub $8, %rsp");
+            code!("movl $0x5f000000, (%rsp)");
+            code!("flds (%rsp)");
+            code!("fucomip %st(1), %st");
+            code!("jbe .Lf2u{}", label);
+            code!("fisttpq (%rsp)");
+            code!("mov (%rsp), %rax");
+            code!("jmp .Lend{}", label);
+            label!(".Lf2u{}", label);
+            code!("fsubs (%rsp)");
+            code!("fisttpq (%rsp)");
+            code!("mov (%rsp), %rax");
+            code!("btc $63, %rax");
+            label!(".Lend{}", label);
+            code!("add $8, %rsp");
+        }
+        (CType::Floating(Floating::LongDouble), CType::Integer(_)) => {
+            code!("sub $8, %rsp");
+            code!("fisttpq (%rsp)");
+            code!("mov (%rsp), %rax");
+            code!("add $8, %rsp");
+            gen_extend(to);
+        }
+        // fildは符号付きとして読み込むので、最上位ビットが立っていれば2^64を足す
+        (CType::Integer(Integer::ULong), CType::Floating(Floating::LongDouble))
+        | (CType::Integer(Integer::ULongLong), CType::Floating(Floating::LongDouble)) => {
+            let label = ctx.label;
+            ctx.label += 1;
+
+            code!("sub $8, %rsp");
+            code!("mov %rax, (%rsp)");
+            code!("fildq (%rsp)");
+            code!("test %rax, %rax");
+            code!("jns .Lend{}", label);
+            code!("movl $0x5f800000, (%rsp)");
+            code!("fadds (%rsp)");
+            label!(".Lend{}", label);
+            code!("add $8, %rsp");
+        }
+        // 整数はraxで64bitに拡張されている
+        (CType::Integer(_), CType::Floating(Floating::LongDouble)) => {
+            code!("sub $8, %rsp");
+            code!("mov %rax, (%rsp)");
+            code!("fildq (%rsp)");
+            code!("add $8, %rsp");
+        }
+        (CType::Floating(Floating::Float), CType::Floating(Floating::Double)) => {
+            code!("cvtss2sd %xmm0, %xmm0");
+        }
+        (CType::Floating(Floating::Double), CType::Floating(Floating::Float)) => {
+            code!("cvtsd2ss %xmm0, %xmm0");
+        }
+        (CType::Floating(_), CType::Floating(_)) => (),
+        // 0.0以外の値は全て1にする。NaNも真となる。
+        (CType::Floating(_), CType::Integer(Integer::Bool)) => {
+            code!("xorps %xmm1, %xmm1");
+            code!("ucomi{} %xmm1, %xmm0", float_suffix(from));
+            code!("setne %al");
+            code!("setp %dl");
+            code!("or %dl, %al");
+            code!("movzb %al, %rax");
+        }
+        // 2^63以上の値はcvttsd2siで変換できないので、
+        // 2^63を引いてから変換し最上位ビットを立てる
+        (CType::Floating(_), CType::Integer(Integer::ULong))
+        | (CType::Floating(_), CType::Integer(Integer::ULongLong)) => {
+            let label = ctx.label;
+            ctx.label += 1;
+
+            let sfx = float_suffix(from);
+            if sfx == "ss" {
+                code!("mov $0x5f000000, %eax");
+                code!("movd %eax, %xmm1");
+            } else {
+                code!("mov $0x43e0000000000000, %rax");
+                code!("movq %rax, %xmm1");
+            }
+            code!("ucomi{} %xmm1, %xmm0", sfx);
+            code!("jae .Lf2u{}", label);
+            code!("cvtt{}2si %xmm0, %rax", sfx);
+            code!("jmp .Lend{}", label);
+            label!(".Lf2u{}", label);
+            code!("sub{} %xmm1, %xmm0", sfx);
+            code!("cvtt{}2si %xmm0, %rax", sfx);
+            code!("btc $63, %rax");
+            label!(".Lend{}", label);
+        }
+        // 小数部は切り捨てる
+        (CType::Floating(_), CType::Integer(_)) => {
+            code!("cvtt{}2si %xmm0, %rax", float_suffix(from));
+            gen_extend(to);
+        }
+        // 最上位ビットが立っている値はcvtsi2sdで変換できないので、
+        // 半分にしてから変換し2倍する。丸めのため最下位ビットを残す。
+        (CType::Integer(Integer::ULong), CType::Floating(_))
+        | (CType::Integer(Integer::ULongLong), CType::Floating(_)) => {
+            let label = ctx.label;
+            ctx.label += 1;
+
+            let sfx = float_suffix(to);
+            code!("test %rax, %rax");
+            code!("js .Lu2f{}", label);
+            code!("cvtsi2{}q %rax, %xmm0", sfx);
+            code!("jmp .Lend{}", label);
+            label!(".Lu2f{}", label);
+            code!("mov %rax, %rdi");
+            code!("shr %rdi");
+            code!("and $1, %eax");
+            code!("or %rax, %rdi");
+            code!("cvtsi2{}q %rdi, %xmm0", sfx);
+            code!("add{} %xmm0, %xmm0", sfx);
+            label!(".Lend{}", label);
+        }
+        // 整数はraxで64bitに拡張されている
+        (CType::Integer(_), CType::Floating(_)) => {
+            code!("cvtsi2{}q %rax, %xmm0", float_suffix(to));
+        }
+        _ => gen_extend(to),
+    }
+}
+
+// 式文の値を捨てる。long doubleの値はx87のスタックから取り除く。
+fn gen_stmt(node: &Node, ctx: &mut Context) {
+    gen(node, ctx);
+    if is_x87(&node.ctype) {
+        code!("fstp %st(0)");
+    }
+}
+
+// 条件式の値をraxにセットする。浮動小数点数は真偽値に変換する。
+fn gen_cond(cond: &Node, ctx: &mut Context) {
+    gen(cond, ctx);
+    if cond.ctype.is_floating() {
+        gen_cast(&cond.ctype, &CType::Integer(Integer::Bool), ctx);
+    }
+}
+
 // デバッグ用にソース位置情報を出力
 fn gen_loc(node: &Node, ctx: &mut Context) {
-    let filename = match &node.token.common.src.path {
-        Some(path) => &path,
-        None => "<stdin>",
-    };
+    let filename = node.token.common.path().unwrap_or("<stdin>");
 
     let fileno = match ctx.debug.find_file(filename) {
         Some(fileno) => fileno,
         None => {
             let fileno = ctx.debug.add_file(filename).unwrap();
-            code!(".file {} \"{}\"", fileno, filename);
+            code!(".file {} {}", fileno, quote_asm_str(filename));
             fileno
         }
     };
 
-    let lineno = node.token.common.loc.row + 1;
+    let lineno = node.token.common.row() + 1;
 
     code!(".loc {} {}", fileno, lineno);
 }
@@ -227,30 +577,39 @@ fn gen(node: &Node, ctx: &mut Context) {
         }
         NodeKind::Block(nodes) => {
             for node in nodes {
-                gen(node, ctx);
+                gen_stmt(node, ctx);
             }
         }
-        NodeKind::StmtExpr(block) => {
-            gen(block, ctx);
-        }
+        // 最後の式文の値を式全体の値として残す
+        NodeKind::StmtExpr(block) => match &block.kind {
+            NodeKind::Block(nodes) => {
+                if let Some((last, nodes)) = nodes.split_last() {
+                    for node in nodes {
+                        gen_stmt(node, ctx);
+                    }
+                    gen(last, ctx);
+                }
+            }
+            _ => unreachable!(),
+        },
         NodeKind::If(cond_node, then_node, else_node) => {
             let label = ctx.label;
             ctx.label += 1;
 
-            gen(cond_node, ctx);
+            gen_cond(cond_node, ctx);
             // 0が偽、0以外は真なので0と比較する
             code!("cmp $0, %rax");
 
             // 0だったら偽としてelse節にジャンプする
             code!("je .Lelse{}", label);
 
-            gen(then_node, ctx);
+            gen_stmt(then_node, ctx);
             // then節が終わったらif文の終わりにジャンプ
             code!("jmp .Lend{}", label);
 
             label!(".Lelse{}", label);
 
-            gen(else_node, ctx);
+            gen_stmt(else_node, ctx);
 
             label!(".Lend{}", label);
         }
@@ -258,25 +617,42 @@ fn gen(node: &Node, ctx: &mut Context) {
             let label = ctx.label;
             ctx.label += 1;
 
-            gen(init_node, ctx);
+            gen_stmt(init_node, ctx);
 
             label!(".Lbegin{}", label);
 
-            gen(cond_node, ctx);
+            gen_cond(cond_node, ctx);
             // 0が偽、0以外は真なので0と比較する
             code!("cmp $0, %rax");
             code!("je .Lend{}", label);
 
-            gen(body_node, ctx);
+            gen_stmt(body_node, ctx);
 
-            gen(update_node, ctx);
+            gen_stmt(update_node, ctx);
 
             code!("jmp .Lbegin{}", label);
             label!(".Lend{}", label);
         }
         NodeKind::Return(child) => {
             gen(child, ctx);
-            code!("jmp .L{}__return", &ctx.fname);
+            code!("jmp {}", return_label(&ctx.fname));
+        }
+        // 代入式の値としてst(0)に読み直す
+        NodeKind::Assign(lhs, rhs) if is_x87(&lhs.ctype) => {
+            gen(rhs, ctx);
+            push_x87(ctx);
+            gen_lval(lhs, ctx);
+            pop_x87(ctx);
+            code!("fstpt (%rax)");
+            code!("fldt (%rax)");
+        }
+        NodeKind::Assign(lhs, rhs) if is_sse(&lhs.ctype) => {
+            gen(rhs, ctx);
+            push_xmm(0, ctx);
+            gen_lval(lhs, ctx);
+            pop_xmm(0, ctx);
+            // 代入式の値はxmm0に残る
+            code!("mov{} %xmm0, (%rax)", float_suffix(&lhs.ctype));
         }
         NodeKind::Assign(lhs, rhs) => {
             gen(rhs, ctx);
@@ -285,10 +661,6 @@ fn gen(node: &Node, ctx: &mut Context) {
             pop(Register::RDI, ctx);
 
             match &lhs.ctype {
-                CType::Integer(Integer::Char) => {
-                    code!("mov %dil, (%rax)");
-                    code!("movsbq %dil, %rax");
-                }
                 CType::Struct(..) | CType::Union(..) => {
                     for i in 0..lhs.ctype.size() {
                         code!("movb {}(%rdi), %sil", i);
@@ -296,11 +668,120 @@ fn gen(node: &Node, ctx: &mut Context) {
                     }
                 }
                 _ => {
-                    code!("mov %rdi, (%rax)");
+                    match lhs.ctype.size() {
+                        1 => {
+                            code!("mov %dil, (%rax)");
+                        }
+                        2 => {
+                            code!("mov %di, (%rax)");
+                        }
+                        4 => {
+                            code!("mov %edi, (%rax)");
+                        }
+                        _ => {
+                            code!("mov %rdi, (%rax)");
+                        }
+                    }
+                    // 代入式の値は左辺の型に変換した値
                     code!("mov %rdi, %rax");
+                    gen_extend(&lhs.ctype);
                 }
             }
         }
+        // 浮動小数点数の比較ではNaNがあるとPFが立つ。
+        // NaNとの比較は!=以外すべて偽となる。
+        // x87ではst(0)の右辺とst(1)の左辺を比較するので、
+        // SSEで左右を入れ替えた場合と同じフラグになる。
+        NodeKind::Eq(lhs, rhs) if is_x87(&lhs.ctype) => {
+            gen_binary_operator(lhs, rhs, ctx);
+            gen_x87_compare();
+            code!("sete %al");
+            code!("setnp %dl");
+            code!("and %dl, %al");
+            code!("movzb %al, %rax");
+        }
+        NodeKind::Neq(lhs, rhs) if is_x87(&lhs.ctype) => {
+            gen_binary_operator(lhs, rhs, ctx);
+            gen_x87_compare();
+            code!("setne %al");
+            code!("setp %dl");
+            code!("or %dl, %al");
+            code!("movzb %al, %rax");
+        }
+        NodeKind::LT(lhs, rhs) if is_x87(&lhs.ctype) => {
+            gen_binary_operator(lhs, rhs, ctx);
+            gen_x87_compare();
+            code!("seta %al");
+            code!("movzb %al, %rax");
+        }
+        NodeKind::LTE(lhs, rhs) if is_x87(&lhs.ctype) => {
+            gen_binary_operator(lhs, rhs, ctx);
+            gen_x87_compare();
+            code!("setae %al");
+            code!("movzb %al, %rax");
+        }
+        // AT&T記法のfsubrpとfdivrpはst(1)からst(0)を引く(割る)
+        NodeKind::Add(lhs, rhs) if is_x87(&node.ctype) => {
+            gen_binary_operator(lhs, rhs, ctx);
+            code!("faddp");
+        }
+        NodeKind::Sub(lhs, rhs) if is_x87(&node.ctype) => {
+            gen_binary_operator(lhs, rhs, ctx);
+            code!("fsubrp");
+        }
+        NodeKind::Mul(lhs, rhs) if is_x87(&node.ctype) => {
+            gen_binary_operator(lhs, rhs, ctx);
+            code!("fmulp");
+        }
+        NodeKind::Div(lhs, rhs) if is_x87(&node.ctype) => {
+            gen_binary_operator(lhs, rhs, ctx);
+            code!("fdivrp");
+        }
+        NodeKind::Eq(lhs, rhs) if lhs.ctype.is_floating() => {
+            gen_binary_operator(lhs, rhs, ctx);
+            code!("ucomi{} %xmm1, %xmm0", float_suffix(&lhs.ctype));
+            code!("sete %al");
+            code!("setnp %dl");
+            code!("and %dl, %al");
+            code!("movzb %al, %rax");
+        }
+        NodeKind::Neq(lhs, rhs) if lhs.ctype.is_floating() => {
+            gen_binary_operator(lhs, rhs, ctx);
+            code!("ucomi{} %xmm1, %xmm0", float_suffix(&lhs.ctype));
+            code!("setne %al");
+            code!("setp %dl");
+            code!("or %dl, %al");
+            code!("movzb %al, %rax");
+        }
+        // 左右を入れ替えて比較するとNaNのときCFが立ち偽になる
+        NodeKind::LT(lhs, rhs) if lhs.ctype.is_floating() => {
+            gen_binary_operator(lhs, rhs, ctx);
+            code!("ucomi{} %xmm0, %xmm1", float_suffix(&lhs.ctype));
+            code!("seta %al");
+            code!("movzb %al, %rax");
+        }
+        NodeKind::LTE(lhs, rhs) if lhs.ctype.is_floating() => {
+            gen_binary_operator(lhs, rhs, ctx);
+            code!("ucomi{} %xmm0, %xmm1", float_suffix(&lhs.ctype));
+            code!("setae %al");
+            code!("movzb %al, %rax");
+        }
+        NodeKind::Add(lhs, rhs) if node.ctype.is_floating() => {
+            gen_binary_operator(lhs, rhs, ctx);
+            code!("add{} %xmm1, %xmm0", float_suffix(&node.ctype));
+        }
+        NodeKind::Sub(lhs, rhs) if node.ctype.is_floating() => {
+            gen_binary_operator(lhs, rhs, ctx);
+            code!("sub{} %xmm1, %xmm0", float_suffix(&node.ctype));
+        }
+        NodeKind::Mul(lhs, rhs) if node.ctype.is_floating() => {
+            gen_binary_operator(lhs, rhs, ctx);
+            code!("mul{} %xmm1, %xmm0", float_suffix(&node.ctype));
+        }
+        NodeKind::Div(lhs, rhs) if node.ctype.is_floating() => {
+            gen_binary_operator(lhs, rhs, ctx);
+            code!("div{} %xmm1, %xmm0", float_suffix(&node.ctype));
+        }
         NodeKind::Eq(lhs, rhs) => {
             gen_binary_operator(lhs, rhs, ctx);
             code!("cmp %rdi, %rax");
@@ -316,31 +797,139 @@ fn gen(node: &Node, ctx: &mut Context) {
         NodeKind::LT(lhs, rhs) => {
             gen_binary_operator(lhs, rhs, ctx);
             code!("cmp %rdi, %rax");
-            code!("setl %al");
+            if is_signed_compare(&lhs.ctype) {
+                code!("setl %al");
+            } else {
+                code!("setb %al");
+            }
             code!("movzb %al, %rax");
         }
         NodeKind::LTE(lhs, rhs) => {
             gen_binary_operator(lhs, rhs, ctx);
             code!("cmp %rdi, %rax");
-            code!("setle %al");
+            if is_signed_compare(&lhs.ctype) {
+                code!("setle %al");
+            } else {
+                code!("setbe %al");
+            }
             code!("movzb %al, %rax");
         }
+        // 演算は64bitで行い、結果を型の幅に切り詰める
         NodeKind::Add(lhs, rhs) => {
             gen_binary_operator(lhs, rhs, ctx);
             code!("add %rdi, %rax");
+            gen_extend(&node.ctype);
         }
         NodeKind::Sub(lhs, rhs) => {
             gen_binary_operator(lhs, rhs, ctx);
             code!("sub %rdi, %rax");
+            gen_extend(&node.ctype);
         }
         NodeKind::Mul(lhs, rhs) => {
             gen_binary_operator(lhs, rhs, ctx);
             code!("imul %rdi, %rax");
+            gen_extend(&node.ctype);
         }
         NodeKind::Div(lhs, rhs) => {
             gen_binary_operator(lhs, rhs, ctx);
-            code!("cqo");
-            code!("idiv %rdi");
+            if node.ctype.is_unsigned() {
+                code!("mov $0, %rdx");
+                code!("div %rdi");
+            } else {
+                code!("cqo");
+                code!("idiv %rdi");
+            }
+            gen_extend(&node.ctype);
+        }
+        NodeKind::Shl(lhs, rhs) => {
+            gen_binary_operator(lhs, rhs, ctx);
+            code!("mov %rdi, %rcx");
+            code!("shl %cl, %rax");
+            gen_extend(&node.ctype);
+        }
+        NodeKind::Shr(lhs, rhs) => {
+            gen_binary_operator(lhs, rhs, ctx);
+            code!("mov %rdi, %rcx");
+            // 符号なし整数は論理シフト、符号付き整数は算術シフト
+            if node.ctype.is_unsigned() {
+                code!("shr %cl, %rax");
+            } else {
+                code!("sar %cl, %rax");
+            }
+        }
+        NodeKind::LogAnd(lhs, rhs) => {
+            let label = ctx.label;
+            ctx.label += 1;
+
+            // どちらかが0ならその時点で偽とする
+            gen_cond(lhs, ctx);
+            code!("cmp $0, %rax");
+            code!("je .Lfalse{}", label);
+            gen_cond(rhs, ctx);
+            code!("cmp $0, %rax");
+            code!("je .Lfalse{}", label);
+            code!("mov $1, %rax");
+            code!("jmp .Lend{}", label);
+            label!(".Lfalse{}", label);
+            code!("mov $0, %rax");
+            label!(".Lend{}", label);
+        }
+        NodeKind::LogOr(lhs, rhs) => {
+            let label = ctx.label;
+            ctx.label += 1;
+
+            // どちらかが0以外ならその時点で真とする
+            gen_cond(lhs, ctx);
+            code!("cmp $0, %rax");
+            code!("jne .Ltrue{}", label);
+            gen_cond(rhs, ctx);
+            code!("cmp $0, %rax");
+            code!("jne .Ltrue{}", label);
+            code!("mov $0, %rax");
+            code!("jmp .Lend{}", label);
+            label!(".Ltrue{}", label);
+            code!("mov $1, %rax");
+            label!(".Lend{}", label);
+        }
+        NodeKind::Not(operand) => {
+            gen_cond(operand, ctx);
+            code!("cmp $0, %rax");
+            code!("sete %al");
+            code!("movzbq %al, %rax");
+        }
+        NodeKind::Bswap(operand) => {
+            gen(operand, ctx);
+
+            match operand.ctype.size() {
+                2 => {
+                    code!("rol $8, %ax");
+                    code!("movzwl %ax, %eax");
+                }
+                4 => {
+                    code!("bswap %eax");
+                }
+                8 => {
+                    code!("bswap %rax");
+                }
+                _ => unreachable!(),
+            }
+        }
+        NodeKind::Cond(cond_node, then_node, else_node) => {
+            let label = ctx.label;
+            ctx.label += 1;
+
+            gen_cond(cond_node, ctx);
+            code!("cmp $0, %rax");
+            code!("je .Lelse{}", label);
+            gen(then_node, ctx);
+            code!("jmp .Lend{}", label);
+            label!(".Lelse{}", label);
+            gen(else_node, ctx);
+            label!(".Lend{}", label);
+        }
+        NodeKind::Cast(operand) => {
+            gen(operand, ctx);
+            gen_cast(&operand.ctype, &node.ctype, ctx);
         }
         NodeKind::Addr(operand) => {
             gen_lval(operand, ctx);
@@ -348,7 +937,7 @@ fn gen(node: &Node, ctx: &mut Context) {
         NodeKind::Deref(operand) => {
             gen_lval(node, ctx);
             let base = operand.ctype.base().unwrap();
-            gen_load(&base);
+            gen_load(base);
         }
         NodeKind::Member(base, offset) => {
             gen_lval(base, ctx);
@@ -358,70 +947,180 @@ fn gen(node: &Node, ctx: &mut Context) {
         NodeKind::Num(n) => {
             code!("mov ${}, %rax", n);
         }
+        // 即値を直接SSEレジスタにセットする命令はないのでrax経由でセットする
+        NodeKind::FNum(f) => match &node.ctype {
+            // 定数はrodataに置いてメモリから読み込む
+            CType::Floating(Floating::LongDouble) => {
+                let label = ctx.label;
+                ctx.label += 1;
+
+                code!(".pushsection .rodata");
+                code!(".align 16");
+                label!(".Lld{}", label);
+                gen_long_double(node);
+                code!(".popsection");
+                code!("fldt .Lld{}(%rip)", label);
+            }
+            CType::Floating(Floating::Float) => {
+                code!("mov ${}, %eax", (*f as f32).to_bits());
+                code!("movd %eax, %xmm0");
+            }
+            _ => {
+                code!("mov ${}, %rax", f.to_bits());
+                code!("movq %rax, %xmm0");
+            }
+        },
         NodeKind::LVar(_, ref ctype, _) | NodeKind::GVar(_, ref ctype) => {
             gen_lval(node, ctx);
             gen_load(ctype);
         }
-        NodeKind::Call(name, args) => {
-            // 関数呼び出しの引数をスタックに積む
-            for arg in args {
-                gen(arg, ctx);
-                push(Register::RAX, ctx);
-            }
-
-            // x86-64の呼び出し規約に従いレジスタに引数をセットする
-            for reg in ARG_REG64.iter().take(args.len()).rev() {
-                pop(*reg, ctx);
-            }
-
+        NodeKind::Call(callee, args) => {
             // x86-64では関数を呼び出す時はRSPが16の倍数でなければならない。
             // 関数呼び出しの際は呼び出し元アドレスがスタックに積まれるため
             // プッシュした回数が偶数ならば、RSPを調整する必要がある。
+            // メモリで渡す引数の下に隙間ができないよう、引数を積む前に調整する。
+            // is_multiple_ofはrustc 1.51には無い
+            #[allow(clippy::manual_is_multiple_of)]
             let needs_align_rsp = ctx.stack % 2 == 0;
 
             if needs_align_rsp {
+                ctx.stack += 1;
                 code!("sub $8, %rsp");
             }
 
-            // RAXには利用するSSEレジスタの数を入れる
-            // 浮動小数点型はサポートしないので0
-            code!("mov $0, %rax");
+            // long doubleの引数はメモリで渡す。
+            // 第1引数が最も低いアドレスになるように後ろから積む。
+            let (mem_args, reg_args): (Vec<&Node>, Vec<&Node>) =
+                args.iter().partition(|arg| is_x87(&arg.ctype));
+            for arg in mem_args.iter().rev() {
+                gen(arg, ctx);
+                push_x87(ctx);
+            }
+
+            // 関数呼び出しの引数をスタックに積む
+            for arg in reg_args.iter() {
+                gen(arg, ctx);
+                if is_sse(&arg.ctype) {
+                    push_xmm(0, ctx);
+                } else {
+                    push(Register::RAX, ctx);
+                }
+            }
+
+            // 関数名で呼び出せない場合は関数のアドレスを求めて間接呼び出しする。
+            // R11は引数の受け渡しに使われないので、引数をセットしても壊れない。
+            let name = match &callee.kind {
+                NodeKind::GVar(name, CType::Function(..)) => Some(name),
+                _ => None,
+            };
+            if name.is_none() {
+                gen(callee, ctx);
+                code!("mov %rax, %r11");
+            }
 
-            code!("call {}", name);
+            // x86-64の呼び出し規約に従いレジスタに引数をセットする。
+            // 整数とポインタは汎用レジスタに、floatとdoubleはSSEレジスタに
+            // それぞれ前から順にセットする。
+            let fp_num = reg_args.iter().filter(|arg| is_sse(&arg.ctype)).count();
+            let mut gp = reg_args.len() - fp_num;
+            let mut fp = fp_num;
+            for arg in reg_args.iter().rev() {
+                if is_sse(&arg.ctype) {
+                    fp -= 1;
+                    pop_xmm(fp, ctx);
+                } else {
+                    gp -= 1;
+                    pop(ARG_REG64[gp], ctx);
+                }
+            }
 
+            // 可変長引数の関数のためにRAXには利用するSSEレジスタの数を入れる
+            code!("mov ${}, %rax", fp_num);
+
+            match name {
+                Some(name) => {
+                    code!("call {}", symbol(name));
+                }
+                None => {
+                    code!("call *%r11");
+                }
+            }
+
+            // メモリで渡した引数とRSPの調整分を取り除く
+            let mut stack_size = mem_args.len() * 16;
+            ctx.stack -= mem_args.len() * 2;
             if needs_align_rsp {
-                code!("add $8, %rsp");
+                stack_size += 8;
+                ctx.stack -= 1;
             }
+            if stack_size > 0 {
+                code!("add ${}, %rsp", stack_size);
+            }
+
+            // 戻り値は下位のビットしか設定されていないことがある
+            gen_extend(&node.ctype);
         }
     }
 }
 
+// st(0)とst(1)を比較してフラグをセットし、x87のスタックから取り除く
+fn gen_x87_compare() {
+    code!("fucomip %st(1), %st");
+    code!("fstp %st(0)");
+}
+
+// 比較を符号付きで行うならば真を返す。
+// ポインタ同士は符号なしで比較する。
+fn is_signed_compare(ctype: &CType) -> bool {
+    matches!(ctype, CType::Integer(integer) if !integer.is_unsigned())
+}
+
 fn gen_str(string: &Str) {
     code!(".section .rodata");
     label!("{}", string.label);
-    for b in string.val.iter() {
-        code!(".byte 0x{:02x}", b);
+
+    // 要素はリトルエンディアンで格納されている
+    let directive = ctype_to_data_directive(&string.base);
+    for unit in string.val.chunks(string.base.size()) {
+        let val = unit.iter().rev().fold(0, |acc, b| acc << 8 | *b as usize);
+        code!("{} 0x{:0width$x}", directive, val, width = unit.len() * 2);
     }
 }
 
 fn gen_gvar(gvar: &GVar) {
+    // externで宣言のみされた変数は他の翻訳単位で定義されている
+    if gvar.is_extern {
+        return;
+    }
+
     code!(".data");
-    code!(".globl {}", gvar.name);
-    label!("{}", gvar.name);
+    if !gvar.is_static {
+        code!(".globl {}", symbol(&gvar.symbol));
+    }
+    code!(".align {}", gvar.ctype.alignof());
+    label!("{}", symbol(&gvar.symbol));
 
     if let Some(val) = &gvar.val {
         match &gvar.ctype {
-            CType::Integer(..) | CType::Pointer(..) => {
-                let size = ctype_to_data_directive(&gvar.ctype);
+            CType::Integer(..) | CType::Floating(..) | CType::Pointer(..) => {
                 let val = val.first().unwrap();
-                gen_init_val(val, size);
+                gen_init_val(val, &gvar.ctype);
             }
             CType::Array(..) => {
                 let base = gvar.ctype.array_base().unwrap();
-                let size = ctype_to_data_directive(&base);
 
-                for val in val.iter() {
-                    gen_init_val(val, size);
+                let mut i = 0;
+                while i < val.len() {
+                    if base.size() == 1 {
+                        let len = gen_incbin(&val[i..]);
+                        if len > 0 {
+                            i += len;
+                            continue;
+                        }
+                    }
+
+                    gen_init_val(&val[i], base);
+                    i += 1;
                 }
             }
             _ => unreachable!(),
@@ -431,17 +1130,128 @@ fn gen_gvar(gvar: &GVar) {
     }
 }
 
-fn gen_init_val(val: &Node, size: &str) {
+// #embedで展開された連続するバイトが十分に長ければ、
+// .byteを並べる代わりに.incbinでファイルを直接参照する。
+// 戻り値は.incbinで出力した要素数で、出力しなければ0。
+fn gen_incbin(val: &[Node]) -> usize {
+    const INCBIN_THRESHOLD: usize = 256;
+
+    let (file, offset) = match embed_byte(&val[0]) {
+        Some(embed) => embed,
+        None => return 0,
+    };
+
+    let len = val
+        .iter()
+        .enumerate()
+        .take_while(|(i, v)| match embed_byte(v) {
+            Some((f, o)) => Rc::ptr_eq(f, file) && o == offset + i,
+            None => false,
+        })
+        .count();
+
+    if len < INCBIN_THRESHOLD {
+        return 0;
+    }
+
+    code!(".incbin {}, {}, {}", quote_asm_str(&file.path), offset, len);
+
+    len
+}
+
+// アセンブラの文字列として"で囲む。
+// "と\はエスケープし、それ以外の表示できない文字は8進数で表す。
+fn quote_asm_str(s: &str) -> String {
+    let mut quoted = String::from("\"");
+
+    for b in s.bytes() {
+        match b {
+            b'"' | b'\\' => {
+                quoted.push('\\');
+                quoted.push(b as char);
+            }
+            0x20..=0x7e => quoted.push(b as char),
+            _ => quoted.push_str(&format!("\\{:03o}", b)),
+        }
+    }
+
+    quoted.push('"');
+    quoted
+}
+
+// #embedで展開されたバイトをそのまま値とするノードなら
+// 読み込んだファイルとオフセットを返す
+fn embed_byte(node: &Node) -> Option<(&Rc<EmbedFile>, usize)> {
+    match (&node.kind, &node.token.kind) {
+        (NodeKind::Num(n), TokenKind::EmbedByte(b, file, offset)) if *n == *b as isize => {
+            Some((file, *offset))
+        }
+        _ => None,
+    }
+}
+
+// long doubleの定数を拡張倍精度で計算し、16バイトで出力する
+fn gen_long_double(val: &Node) {
+    let f = val.to_f80();
+    if f.is_none() {
+        error_tok!(val.token, "初期値が定数式ではありません");
+    }
+    let f = f.unwrap();
+    code!(".quad {}", f.mantissa);
+    code!(".short {}", f.sign_exp);
+    code!(".zero 6");
+}
+
+// 初期値は変数の型に変換して出力する
+fn gen_init_val(val: &Node, ctype: &CType) {
+    if is_x87(ctype) {
+        gen_long_double(val);
+        return;
+    }
+
+    let size = ctype_to_data_directive(ctype);
+
     match &val.kind {
+        // アドレスは0にならない
+        NodeKind::GVar(..) if matches!(ctype, CType::Integer(Integer::Bool)) => {
+            code!("{} 1", size);
+        }
         NodeKind::GVar(ref name, ..) => {
-            code!("{} {}", size, name);
+            code!("{} {}", size, symbol(name));
+        }
+        NodeKind::Addr(var) if matches!(var.kind, NodeKind::GVar(..)) => {
+            gen_init_val(var, ctype);
+        }
+        // 浮動小数点数はビット列をそのまま出力する
+        _ if ctype.is_floating() => {
+            let f = val.to_f64();
+            if f.is_none() {
+                error_tok!(val.token, "初期値が定数式ではありません");
+            }
+            match ctype {
+                CType::Floating(Floating::Float) => {
+                    code!("{} {}", size, (f.unwrap() as f32).to_bits());
+                }
+                _ => {
+                    code!("{} {}", size, f.unwrap().to_bits());
+                }
+            }
         }
         _ => {
-            let n = val.to_isize();
+            // 浮動小数点数からの変換は変換後の値を計算する
+            let n = if val.ctype.is_floating() {
+                let mut val = val.clone();
+                if let Err(reason) = CType::cast(&mut val, ctype.clone()) {
+                    error_tok!(val.token, "{}", reason);
+                }
+                val.to_isize()
+            } else {
+                val.to_isize()
+            };
             if n.is_none() {
                 error_tok!(val.token, "初期値が定数式ではありません");
             }
-            code!("{} {}", size, n.unwrap());
+            code!("{} {}", size, ctype.convert_int(n.unwrap()));
         }
     }
 }
@@ -449,16 +1259,34 @@ fn gen_init_val(val: &Node, size: &str) {
 fn ctype_to_data_directive(ctype: &CType) -> &str {
     match ctype.size() {
         1 => ".byte",
+        2 => ".short",
+        4 => ".long",
         8 => ".quad",
         _ => unreachable!(),
     }
 }
 
-fn function_header(name: &str, ctx: &mut Context) {
+fn function_header(name: &str, fn_symbol: &str, is_static: bool, ctx: &mut Context) {
     ctx.fname = name.to_string();
     code!(".text");
-    code!(".globl {}", name);
-    label!("{}", name);
+    if !is_static {
+        code!(".globl {}", symbol(fn_symbol));
+    }
+    label!("{}", symbol(fn_symbol));
+}
+
+// アセンブラのシンボル名として出力する。
+// ASCII以外の文字を含む識別子はダブルクォートで囲む。
+fn symbol(name: &str) -> String {
+    if name.is_ascii() {
+        name.to_string()
+    } else {
+        format!("\"{}\"", name)
+    }
+}
+
+fn return_label(fname: &str) -> String {
+    symbol(&format!(".L{}__return", fname))
 }
 
 fn prologue(mut stack_size: usize, params: &[(usize, CType)], ctx: &mut Context) {
@@ -472,20 +1300,46 @@ fn prologue(mut stack_size: usize, params: &[(usize, CType)], ctx: &mut Context)
 
     // x86-64の呼び出し規約に従い引数をレジスタから
     // スタック上のローカル変数にセットする。
-    let iter = params.iter().zip(ARG_REG8.iter()).zip(ARG_REG64.iter());
-    for (((offset, ctype), reg8), reg64) in iter {
+    // 汎用レジスタとSSEレジスタはそれぞれ前から順に使われる。
+    let mut gp = 0;
+    let mut fp = 0;
+    let mut mem = 0;
+    for (offset, ctype) in params {
         code!("mov %rbp, %rax");
         code!("sub ${}, %rax", offset);
+        // long doubleはRBPの上に第1引数から順に16バイトずつ置かれている
+        if is_x87(ctype) {
+            code!("fldt {}(%rbp)", 16 + mem * 16);
+            code!("fstpt (%rax)");
+            mem += 1;
+            continue;
+        }
+        if is_sse(ctype) {
+            code!("mov{} %xmm{}, (%rax)", float_suffix(ctype), fp);
+            fp += 1;
+            continue;
+        }
         match ctype.size() {
-            1 => code!("movb {}, (%rax)", reg8),
-            8 => code!("mov {}, (%rax)", reg64),
+            1 => {
+                code!("movb {}, (%rax)", ARG_REG8[gp]);
+            }
+            2 => {
+                code!("movw {}, (%rax)", ARG_REG16[gp]);
+            }
+            4 => {
+                code!("movl {}, (%rax)", ARG_REG32[gp]);
+            }
+            8 => {
+                code!("mov {}, (%rax)", ARG_REG64[gp]);
+            }
             _ => unreachable!(),
         }
+        gp += 1;
     }
 }
 
 fn epilogue(ctx: &mut Context) {
-    label!(".L{}__return", &ctx.fname);
+    label!("{}", return_label(&ctx.fname));
     code!("mov %rbp, %rsp");
     pop(Register::RBP, ctx);
     code!("ret");
@@ -507,7 +1361,10 @@ pub fn codegen(nodes: &[Node], parse_ctx: &ParseContext) {
                 .stack_size(name)
                 .expect("関数情報が見つかりません");
 
-            function_header(name, &mut ctx);
+            let (fn_symbol, is_static) =
+                parse_ctx.fn_symbol(name).expect("関数情報が見つかりません");
+
+            function_header(name, &fn_symbol, is_static, &mut ctx);
 
             prologue(stack_size, params, &mut ctx);
 
@@ -1,3 +1,4 @@
+use std::cell::RefCell;
 use std::fmt;
 use std::mem::{replace, swap};
 use std::rc::Rc;
@@ -6,10 +7,80 @@ use super::node::{Node, NodeKind};
 use super::tokenize::Token;
 use super::util::align_to;
 
+// サイズとアライメントはSystem V ABI(LP64)に従う
 #[derive(Clone, PartialEq)]
 pub enum Integer {
+    Bool,
     Char,
+    UChar,
+    Short,
+    UShort,
     Int,
+    UInt,
+    Long,
+    ULong,
+    LongLong,
+    ULongLong,
+}
+
+impl Integer {
+    // 整数変換の順位
+    fn rank(&self) -> usize {
+        match self {
+            Self::Bool => 0,
+            Self::Char | Self::UChar => 1,
+            Self::Short | Self::UShort => 2,
+            Self::Int | Self::UInt => 3,
+            Self::Long | Self::ULong => 4,
+            Self::LongLong | Self::ULongLong => 5,
+        }
+    }
+
+    pub fn is_unsigned(&self) -> bool {
+        matches!(
+            self,
+            Self::Bool | Self::UChar | Self::UShort | Self::UInt | Self::ULong | Self::ULongLong
+        )
+    }
+
+    // 同じ順位の符号なし整数型を返す
+    pub fn to_unsigned(&self) -> Self {
+        match self {
+            Self::Bool => Self::Bool,
+            Self::Char | Self::UChar => Self::UChar,
+            Self::Short | Self::UShort => Self::UShort,
+            Self::Int | Self::UInt => Self::UInt,
+            Self::Long | Self::ULong => Self::ULong,
+            Self::LongLong | Self::ULongLong => Self::ULongLong,
+        }
+    }
+
+    // 汎整数拡張。intより順位の低い型は全ての値をintで表現できるのでintになる。
+    fn promote(&self) -> Self {
+        if self.rank() < Self::Int.rank() {
+            Self::Int
+        } else {
+            self.clone()
+        }
+    }
+}
+
+// IEEE 754の単精度と倍精度、x87の80bit拡張倍精度
+#[derive(Clone, PartialEq)]
+pub enum Floating {
+    Float,
+    Double,
+    LongDouble,
+}
+
+impl Floating {
+    fn rank(&self) -> usize {
+        match self {
+            Self::Float => 1,
+            Self::Double => 2,
+            Self::LongDouble => 3,
+        }
+    }
 }
 
 #[derive(Clone, PartialEq)]
@@ -17,22 +88,51 @@ pub struct Member {
     name: String,
     ctype: CType,
     offset: usize,
+    // #pragma packを反映したアライメント
+    align: usize,
 }
 
-#[derive(Clone, PartialEq)]
+#[derive(Clone)]
 pub enum CType {
+    Void,
     Integer(Integer),
+    Floating(Floating),
     Pointer(Box<Self>),
     Array(Box<Self>, usize),
     // NOTE: タグ名, メンバーだけを持つと、それらが一致していれば
     //       異なる箇所で定義された構造体であっても同一のものと
-    //       判定してしまう。定義された箇所のトークンを持つことで
+    //       判定してしまう。最初に宣言された箇所のトークンを持つことで
     //       そのような構造体を異なるものとして判定できるようにする。
-    Struct(Option<String>, Vec<Member>, Rc<Token>),
-    Union(Option<String>, Vec<Member>, Rc<Token>),
+    //       メンバーは不完全型として宣言された箇所と定義された箇所で
+    //       共有し、メンバーが空のものは宣言のみされた不完全型を表す。
+    Struct(Option<String>, Rc<RefCell<Vec<Member>>>, Rc<Token>),
+    Union(Option<String>, Rc<RefCell<Vec<Member>>>, Rc<Token>),
+    // 戻り値の型, 引数の型, 可変長引数か
+    // ()で宣言された引数の型が不明な関数は、引数が空の可変長引数の関数として表す。
+    Function(Box<Self>, Vec<Self>, bool),
     Statement,
 }
 
+// 構造体/共用体は最初に宣言された箇所のトークンで同一性を判定する。
+// 不完全型として宣言した後に定義しても同じ型となるよう、メンバーは比較しない。
+impl PartialEq for CType {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Void, Self::Void) | (Self::Statement, Self::Statement) => true,
+            (Self::Integer(a), Self::Integer(b)) => a == b,
+            (Self::Floating(a), Self::Floating(b)) => a == b,
+            (Self::Pointer(a), Self::Pointer(b)) => a == b,
+            (Self::Array(a, a_len), Self::Array(b, b_len)) => a == b && a_len == b_len,
+            (Self::Struct(_, _, a), Self::Struct(_, _, b))
+            | (Self::Union(_, _, a), Self::Union(_, _, b)) => Rc::ptr_eq(a, b),
+            (Self::Function(a_ret, a_params, a_va), Self::Function(b_ret, b_params, b_va)) => {
+                a_ret == b_ret && a_params == b_params && a_va == b_va
+            }
+            _ => false,
+        }
+    }
+}
+
 impl CType {
     pub fn new(token: &Rc<Token>, kind: &mut NodeKind) -> Result<Self, &'static str> {
         // kindの種別によってはkindを置き換える必要があるが
@@ -67,27 +167,31 @@ impl CType {
                     unreachable!("StmtExprの要素がBlockではありません");
                 }
             }
-            NodeKind::Assign(lhs, rhs) => match (&lhs.ctype, &rhs.ctype) {
-                (Self::Integer(_), Self::Integer(_)) => Ok(lhs.ctype.clone()),
-                (Self::Pointer(_), Self::Pointer(_)) if lhs.ctype == rhs.ctype => {
-                    Ok(lhs.ctype.clone())
+            NodeKind::Assign(lhs, rhs) => match Self::assign_conv(rhs, &lhs.ctype) {
+                Ok(()) => Ok(lhs.ctype.clone()),
+                Err(()) => Err(ERROR_INVALID_OPERAND),
+            },
+            NodeKind::Eq(lhs, rhs)
+            | NodeKind::Neq(lhs, rhs)
+            | NodeKind::LT(lhs, rhs)
+            | NodeKind::LTE(lhs, rhs) => {
+                // 算術型同士なら共通の型で比較する
+                if lhs.ctype.is_arithmetic() && rhs.ctype.is_arithmetic() {
+                    Self::usual_arith_conv(lhs, rhs);
                 }
-                (Self::Pointer(p_base), Self::Array(a_base, _)) if p_base == a_base => {
-                    Self::array_to_ptr(rhs);
-                    Ok(lhs.ctype.clone())
+                // 関数は関数へのポインタとして比較する
+                if matches!(lhs.ctype, Self::Function(..)) {
+                    Self::func_to_ptr(lhs);
                 }
-                (Self::Struct(..), Self::Struct(..)) | (Self::Union(..), Self::Union(..))
-                    if lhs.ctype == rhs.ctype =>
-                {
-                    Ok(lhs.ctype.clone())
+                if matches!(rhs.ctype, Self::Function(..)) {
+                    Self::func_to_ptr(rhs);
                 }
-                _ => Err(ERROR_INVALID_OPERAND),
-            },
-            NodeKind::Eq(..) | NodeKind::Neq(..) | NodeKind::LT(..) | NodeKind::LTE(..) => {
                 Ok(Self::Integer(Integer::Int))
             }
             NodeKind::Add(lhs, rhs) | NodeKind::Sub(lhs, rhs) => match (&lhs.ctype, &rhs.ctype) {
-                (Self::Integer(_), Self::Integer(_)) => Ok(Self::Integer(Integer::Int)),
+                (l, r) if l.is_arithmetic() && r.is_arithmetic() => {
+                    Ok(Self::usual_arith_conv(lhs, rhs))
+                }
                 (Self::Pointer(base), Self::Integer(_)) => {
                     Self::index(rhs, base.size());
                     Ok(lhs.ctype.clone())
@@ -111,9 +215,60 @@ impl CType {
                 _ => Err(ERROR_INVALID_OPERAND),
             },
             NodeKind::Mul(lhs, rhs) | NodeKind::Div(lhs, rhs) => match (&lhs.ctype, &rhs.ctype) {
-                (Self::Integer(_), Self::Integer(_)) => Ok(Self::Integer(Integer::Int)),
+                (l, r) if l.is_arithmetic() && r.is_arithmetic() => {
+                    Ok(Self::usual_arith_conv(lhs, rhs))
+                }
                 _ => Err(ERROR_INVALID_OPERAND),
             },
+            // 結果の型は左オペランドを汎整数拡張した型
+            NodeKind::Shl(lhs, rhs) | NodeKind::Shr(lhs, rhs) => {
+                Self::promote_operand(rhs)?;
+                Self::promote_operand(lhs)
+            }
+            NodeKind::LogAnd(lhs, rhs) | NodeKind::LogOr(lhs, rhs) => {
+                Self::scalar_operand(lhs)?;
+                Self::scalar_operand(rhs)?;
+                Ok(Self::Integer(Integer::Int))
+            }
+            NodeKind::Not(operand) => {
+                Self::scalar_operand(operand)?;
+                Ok(Self::Integer(Integer::Int))
+            }
+            NodeKind::Bswap(operand) => Ok(operand.ctype.clone()),
+            NodeKind::Cond(cond, then, els) => {
+                Self::scalar_operand(cond)?;
+                Self::decay(then);
+                Self::decay(els);
+
+                match (&then.ctype, &els.ctype) {
+                    (t, e) if t.is_arithmetic() && e.is_arithmetic() => {
+                        Ok(Self::usual_arith_conv(then, els))
+                    }
+                    (Self::Void, Self::Void) => Ok(Self::Void),
+                    (Self::Pointer(t_base), Self::Pointer(e_base)) => {
+                        if t_base == e_base {
+                            Ok(then.ctype.clone())
+                        } else if **t_base == Self::Void || **e_base == Self::Void {
+                            Ok(Self::Pointer(Box::new(Self::Void)))
+                        } else {
+                            Err(ERROR_INVALID_OPERAND)
+                        }
+                    }
+                    // 0はヌルポインタ定数としてポインタに合わせる
+                    (Self::Pointer(_), Self::Integer(_)) if els.to_isize() == Some(0) => {
+                        Ok(then.ctype.clone())
+                    }
+                    (Self::Integer(_), Self::Pointer(_)) if then.to_isize() == Some(0) => {
+                        Ok(els.ctype.clone())
+                    }
+                    (Self::Struct(..), Self::Struct(..)) | (Self::Union(..), Self::Union(..))
+                        if then.ctype == els.ctype =>
+                    {
+                        Ok(then.ctype.clone())
+                    }
+                    _ => Err(ERROR_INVALID_OPERAND),
+                }
+            }
             NodeKind::Addr(operand) => match &operand.kind {
                 NodeKind::LVar(..) | NodeKind::GVar(..) | NodeKind::Deref(..) => {
                     let base = Box::new(operand.ctype.clone());
@@ -122,6 +277,7 @@ impl CType {
                 _ => Err(ERROR_INVALID_OPERAND),
             },
             NodeKind::Deref(operand) => match &operand.ctype {
+                Self::Pointer(base) if **base == Self::Void => Err("voidポインタは参照できません"),
                 Self::Pointer(base) => Ok(*base.clone()),
                 Self::Array(base, _) => {
                     // operandを借用したままだとarray_to_ptrが呼べないので
@@ -130,27 +286,231 @@ impl CType {
                     Self::array_to_ptr(operand);
                     Ok(ctype)
                 }
+                // *fは関数へのポインタを経由してf自身となる
+                Self::Function(..) => {
+                    let ctype = operand.ctype.clone();
+                    Self::func_to_ptr(operand);
+                    Ok(ctype)
+                }
                 _ => Err(ERROR_INVALID_OPERAND),
             },
             NodeKind::Member(..) => {
                 unreachable!("MemberはNode側でCType生成しているのでここには来ないはず")
             }
+            NodeKind::Cast(..) => {
+                unreachable!("Castは変換先の型を指定して生成しているのでここには来ないはず")
+            }
             NodeKind::Num(..) => Ok(Self::Integer(Integer::Int)),
+            NodeKind::FNum(..) => Ok(Self::Floating(Floating::Double)),
             NodeKind::LVar(_, ctype, _) | NodeKind::GVar(_, ctype) => Ok(ctype.clone()),
-            NodeKind::Call(_, ref mut args) => {
+            NodeKind::Call(callee, ref mut args) => {
                 for arg in args.iter_mut() {
-                    if matches!(arg.ctype, Self::Array(..)) {
-                        Self::array_to_ptr(arg);
-                    }
+                    Self::decay(arg);
                 }
 
-                Ok(Self::Integer(Integer::Int))
+                match callee.ctype.fn_type() {
+                    Some(Self::Function(ret_type, ..)) => Ok(*ret_type.clone()),
+                    _ => Err("関数ではありません"),
+                }
+            }
+        }
+    }
+
+    // 通常の算術型変換。どちらかが浮動小数点数なら順位の高い浮動小数点型にする。
+    // 整数同士なら汎整数拡張をした後、符号の有無と順位から共通の型を決める。
+    // 両方のオペランドは共通の型に変換する。
+    fn usual_arith_conv(lhs: &mut Node, rhs: &mut Node) -> Self {
+        let ctype = match (&lhs.ctype, &rhs.ctype) {
+            (Self::Floating(l), Self::Floating(r)) => {
+                let higher = if l.rank() >= r.rank() { l } else { r };
+                Self::Floating(higher.clone())
+            }
+            (Self::Floating(f), Self::Integer(_)) | (Self::Integer(_), Self::Floating(f)) => {
+                Self::Floating(f.clone())
+            }
+            (Self::Integer(l), Self::Integer(r)) => {
+                Self::Integer(Self::common_integer(&l.promote(), &r.promote()))
             }
+            _ => unreachable!("算術型以外の算術型変換はできません"),
+        };
+
+        Self::convert(lhs, ctype.clone());
+        Self::convert(rhs, ctype.clone());
+
+        ctype
+    }
+
+    fn common_integer(lhs: &Integer, rhs: &Integer) -> Integer {
+        let (higher, lower) = if lhs.rank() >= rhs.rank() {
+            (lhs, rhs)
+        } else {
+            (rhs, lhs)
+        };
+
+        if higher.is_unsigned() == lower.is_unsigned() || higher.is_unsigned() {
+            // 符号の有無が同じか、順位の高い方が符号なしならその型
+            higher.clone()
+        } else if Self::Integer(higher.clone()).size() > Self::Integer(lower.clone()).size() {
+            // 符号付きの型が符号なしの型の全ての値を表現できるなら符号付きの型
+            higher.clone()
+        } else {
+            higher.to_unsigned()
         }
     }
 
+    // シフト演算のオペランドはそれぞれ汎整数拡張する
+    fn promote_operand(node: &mut Node) -> Result<Self, &'static str> {
+        match &node.ctype {
+            Self::Integer(integer) => {
+                let ctype = Self::Integer(integer.promote());
+                Self::convert(node, ctype.clone());
+                Ok(ctype)
+            }
+            _ => Err("無効なオペランドです"),
+        }
+    }
+
+    // nodeの値をctypeに変換する。型が同じなら何もしない。
+    // 代入と同じ規則でnodeをctypeに暗黙の型変換する。変換できなければErrを返す。
+    // 整数型同士は代入先の大きさで格納されるので変換しない。
+    pub fn assign_conv(node: &mut Node, ctype: &Self) -> Result<(), ()> {
+        match (ctype, &node.ctype) {
+            // _Boolへの代入では0以外のスカラー値を1にする
+            (Self::Integer(Integer::Bool), Self::Integer(_))
+            | (Self::Integer(Integer::Bool), Self::Floating(_))
+            | (Self::Integer(Integer::Bool), Self::Pointer(_)) => {
+                Self::convert(node, ctype.clone());
+            }
+            (Self::Integer(Integer::Bool), Self::Array(..)) => {
+                Self::array_to_ptr(node);
+                Self::convert(node, ctype.clone());
+            }
+            (Self::Integer(_), Self::Integer(_)) => (),
+            // 浮動小数点数が絡む場合は表現が異なるので明示的に変換する
+            (to, from) if to.is_arithmetic() && from.is_arithmetic() => {
+                Self::convert(node, ctype.clone());
+            }
+            (Self::Pointer(_), Self::Pointer(_)) if *ctype == node.ctype => (),
+            (Self::Pointer(base), Self::Function(..)) if **base == node.ctype => {
+                Self::func_to_ptr(node);
+            }
+            // 値が0の整数定数式は空ポインタ定数として任意のポインタに変換できる
+            (Self::Pointer(_), Self::Integer(_)) if node.to_isize() == Some(0) => {
+                Self::convert(node, ctype.clone());
+            }
+            // void*とオブジェクトへのポインタは相互に暗黙の型変換ができる。
+            // 関数へのポインタはオブジェクトへのポインタではないので変換できない。
+            (Self::Pointer(to_base), Self::Pointer(from_base))
+                if (**to_base == Self::Void || **from_base == Self::Void)
+                    && !matches!(**to_base, Self::Function(..))
+                    && !matches!(**from_base, Self::Function(..)) => {}
+            (Self::Pointer(p_base), Self::Array(a_base, _))
+                if p_base == a_base || **p_base == Self::Void =>
+            {
+                Self::array_to_ptr(node);
+            }
+            (Self::Struct(..), Self::Struct(..)) | (Self::Union(..), Self::Union(..))
+                if *ctype == node.ctype => {}
+            _ => return Err(()),
+        }
+
+        Ok(())
+    }
+
+    // プロトタイプの無い引数や可変長引数の部分の引数は既定の実引数拡張をする。
+    // 整数は汎整数拡張し、floatはdoubleに拡張する。
+    pub fn promote_arg(node: &mut Node) {
+        let ctype = match &node.ctype {
+            Self::Integer(integer) => Self::Integer(integer.promote()),
+            Self::Floating(Floating::Float) => Self::Floating(Floating::Double),
+            _ => return,
+        };
+        Self::convert(node, ctype);
+    }
+
+    fn convert(node: &mut Node, ctype: Self) {
+        if node.ctype != ctype {
+            Self::wrap_cast(node, ctype);
+        }
+    }
+
+    // nodeをctypeに変換するCastノードに置き換える
+    fn wrap_cast(node: &mut Node, ctype: Self) {
+        let token = Rc::clone(&node.token);
+
+        let dummy_node = Node::null_statement(Rc::clone(&token));
+        let org_node = replace(node, dummy_node);
+
+        let kind = NodeKind::Cast(Box::new(org_node));
+
+        let mut new_node = Node { token, kind, ctype };
+        swap(node, &mut new_node);
+    }
+
+    // キャスト演算子による明示的な型変換。nodeをctypeに変換するCastノードに置き換える。
+    pub fn cast(node: &mut Node, ctype: Self) -> Result<(), &'static str> {
+        Self::decay(node);
+
+        match (&node.ctype, &ctype) {
+            // 値を捨てる
+            (_, Self::Void) => (),
+            (from, to) if from.is_arithmetic() && to.is_arithmetic() => (),
+            (Self::Integer(_), Self::Pointer(_))
+            | (Self::Pointer(_), Self::Integer(_))
+            | (Self::Pointer(_), Self::Pointer(_)) => (),
+            _ => return Err("キャストできない型です"),
+        }
+
+        // 型が同じでもキャストの結果は左辺値ではなくなる
+        Self::wrap_cast(node, ctype);
+
+        Ok(())
+    }
+
+    // 整数nをこの型で表現される値に変換する。
+    // 64bitの符号なし整数はisizeに同じビット列で格納する。
+    pub fn convert_int(&self, n: isize) -> isize {
+        match self {
+            // 0以外の値は全て1になる
+            Self::Integer(Integer::Bool) => (n != 0) as isize,
+            Self::Integer(Integer::Char) => n as i8 as isize,
+            Self::Integer(Integer::UChar) => n as u8 as isize,
+            Self::Integer(Integer::Short) => n as i16 as isize,
+            Self::Integer(Integer::UShort) => n as u16 as isize,
+            Self::Integer(Integer::Int) => n as i32 as isize,
+            Self::Integer(Integer::UInt) => n as u32 as isize,
+            _ => n,
+        }
+    }
+
+    // 浮動小数点数fをこの型で表現される値に変換する。
+    // long doubleの定数式はf64の精度で計算する。
+    pub fn convert_float(&self, f: f64) -> f64 {
+        match self {
+            Self::Floating(Floating::Float) => f as f32 as f64,
+            _ => f,
+        }
+    }
+
+    pub fn is_unsigned(&self) -> bool {
+        match self {
+            Self::Integer(integer) => integer.is_unsigned(),
+            _ => false,
+        }
+    }
+
+    pub fn is_floating(&self) -> bool {
+        matches!(self, Self::Floating(_))
+    }
+
+    pub fn is_arithmetic(&self) -> bool {
+        matches!(self, Self::Integer(_) | Self::Floating(_))
+    }
+
+    // packがSomeなら各メンバーのアライメントをpack以下に制限する
     fn make_ctype_members<F>(
         members: Vec<(String, CType)>,
+        pack: Option<usize>,
         offset_fn: F,
     ) -> Result<Vec<Member>, &'static str>
     where
@@ -168,44 +528,53 @@ impl CType {
                 return Err("名前が重複しているメンバーがあります");
             }
 
-            let offset = offset_fn(current_offset, ctype.alignof());
+            let align = match pack {
+                Some(pack) if pack < ctype.alignof() => pack,
+                _ => ctype.alignof(),
+            };
+            let offset = offset_fn(current_offset, align);
             current_offset = offset + ctype.size();
             ret.push(Member {
                 name,
                 ctype,
                 offset,
+                align,
             });
         }
 
         Ok(ret)
     }
 
-    pub fn new_struct(
-        name: Option<String>,
-        members: Vec<(String, CType)>,
-        token: Rc<Token>,
-    ) -> Result<Self, &'static str> {
-        match Self::make_ctype_members(members, align_to) {
-            Ok(members) => Ok(Self::Struct(name, members, token)),
-            Err(msg) => Err(msg),
-        }
+    // メンバーを持たない不完全型の構造体/共用体を作る
+    pub fn new_incomplete_struct(name: Option<String>, token: Rc<Token>) -> Self {
+        Self::Struct(name, Rc::new(RefCell::new(Vec::new())), token)
+    }
+
+    pub fn new_incomplete_union(name: Option<String>, token: Rc<Token>) -> Self {
+        Self::Union(name, Rc::new(RefCell::new(Vec::new())), token)
     }
 
-    pub fn new_union(
-        name: Option<String>,
+    // 不完全型の構造体/共用体にメンバーを設定して完全型にする。
+    // メンバーは共有しているので、先に宣言された同じ型も完全型になる。
+    pub fn set_members(
+        &self,
         members: Vec<(String, CType)>,
-        token: Rc<Token>,
-    ) -> Result<Self, &'static str> {
-        match Self::make_ctype_members(members, |_, _| 0) {
-            Ok(members) => Ok(Self::Union(name, members, token)),
-            Err(msg) => Err(msg),
-        }
+        pack: Option<usize>,
+    ) -> Result<(), &'static str> {
+        let (members, cell) = match self {
+            Self::Struct(_, cell, _) => (Self::make_ctype_members(members, pack, align_to)?, cell),
+            Self::Union(_, cell, _) => (Self::make_ctype_members(members, pack, |_, _| 0)?, cell),
+            _ => unreachable!("構造体/共用体ではありません"),
+        };
+
+        *cell.borrow_mut() = members;
+        Ok(())
     }
 
     pub fn get_member(&self, name: &str) -> Result<(Self, usize), &str> {
         match self {
             Self::Struct(_, members, _) | Self::Union(_, members, _) => {
-                for m in members.iter() {
+                for m in members.borrow().iter() {
                     if m.name == name {
                         return Ok((m.ctype.clone(), m.offset));
                     }
@@ -236,29 +605,60 @@ impl CType {
         };
 
         // 同じ型のポインタ同士の減算なら
-        // ポインタが指す型で割るようにkindを置き換える
+        // ポインタが指す型で割るようにkindを置き換える。
+        // 結果の型はptrdiff_t(long)となる。
         if let Some(base_size) = base_size {
             Self::num_of_elements(token, kind, base_size);
-            Some(Self::Integer(Integer::Int))
+            Some(Self::Integer(Integer::Long))
         } else {
             None
         }
     }
 
+    // 配列と関数をポインタに変換する
+    fn decay(node: &mut Node) {
+        match node.ctype {
+            Self::Array(..) => Self::array_to_ptr(node),
+            Self::Function(..) => Self::func_to_ptr(node),
+            _ => (),
+        }
+    }
+
+    // 条件として評価するオペランドはスカラー型でなければならない
+    fn scalar_operand(node: &mut Node) -> Result<(), &'static str> {
+        Self::decay(node);
+
+        if node.ctype.is_arithmetic() || matches!(node.ctype, Self::Pointer(_)) {
+            Ok(())
+        } else {
+            Err("スカラー型ではありません")
+        }
+    }
+
     // 配列からポインタへの暗黙の型変換。
     // ポインタを返すようにAddr(node)に置き換える。
     fn array_to_ptr(node: &mut Node) {
         // Arrayのときだけ呼ばれるのでunwrapして問題ない
         let base = node.ctype.base().unwrap().clone();
-        let ctype = CType::Pointer(Box::new(base));
+        Self::wrap_addr(node, CType::Pointer(Box::new(base)));
+    }
 
+    // 関数から関数へのポインタへの暗黙の型変換。
+    // 関数のアドレスを返すようにAddr(node)に置き換える。
+    fn func_to_ptr(node: &mut Node) {
+        let ctype = CType::Pointer(Box::new(node.ctype.clone()));
+        Self::wrap_addr(node, ctype);
+    }
+
+    // nodeをctype型のAddr(node)に置き換える
+    fn wrap_addr(node: &mut Node, ctype: CType) {
         let token = Rc::clone(&node.token);
 
         // ダミーノードと元のノードを入れ替える
         let dummy_node = Node::null_statement(Rc::clone(&token));
         let org_node = replace(node, dummy_node);
 
-        // ポインタ演算するためにアドレスを返すようにする
+        // アドレスを返すようにする
         let kind = NodeKind::Addr(Box::new(org_node));
 
         // Addr(node)にしたノードと元のノードを入れ替える
@@ -268,19 +668,31 @@ impl CType {
 
     pub fn size(&self) -> usize {
         match self {
-            Self::Integer(Integer::Char) => 1,
-            Self::Integer(Integer::Int) => 8,
+            // GNU拡張と同様にvoid*や関数へのポインタの演算では1バイトとして扱う
+            Self::Void | Self::Function(..) => 1,
+            Self::Integer(integer) => match integer.rank() {
+                0 | 1 => 1,
+                2 => 2,
+                3 => 4,
+                _ => 8,
+            },
+            Self::Floating(Floating::Float) => 4,
+            Self::Floating(Floating::Double) => 8,
+            // 80bitだがアライメントのため16バイトとする
+            Self::Floating(Floating::LongDouble) => 16,
             Self::Pointer(_) => 8,
             Self::Array(base, size) => base.size() * size,
             Self::Struct(_, members, _) => {
-                if let Some(m) = members.last() {
+                // 不完全型のサイズは0とし、使われる箇所でエラーにする
+                if let Some(m) = members.borrow().last() {
                     let raw_size = m.offset + m.ctype.size();
                     align_to(raw_size, self.alignof())
                 } else {
-                    unreachable!("空の構造体は定義できません");
+                    0
                 }
             }
             Self::Union(_, members, _) => {
+                let members = members.borrow();
                 let max_size = members.iter().map(|m| m.ctype.size()).max().unwrap_or(0);
                 align_to(max_size, self.alignof())
             }
@@ -292,7 +704,13 @@ impl CType {
     // 例えば、int[2][3]なら6, intなら1。
     pub fn flat_len(&self) -> usize {
         match self {
-            Self::Integer(_) | Self::Pointer(_) | Self::Struct(..) | Self::Union(..) => 1,
+            Self::Void
+            | Self::Integer(_)
+            | Self::Floating(_)
+            | Self::Pointer(_)
+            | Self::Struct(..)
+            | Self::Union(..)
+            | Self::Function(..) => 1,
             Self::Array(base, size) => base.flat_len() * size,
             Self::Statement => 0,
         }
@@ -300,15 +718,29 @@ impl CType {
 
     pub fn alignof(&self) -> usize {
         match self {
-            Self::Integer(_) | Self::Pointer(_) => self.size(),
+            Self::Void
+            | Self::Integer(_)
+            | Self::Floating(_)
+            | Self::Pointer(_)
+            | Self::Function(..) => self.size(),
             Self::Array(base, _) => base.alignof(),
             Self::Struct(_, members, _) | Self::Union(_, members, _) => {
-                members.iter().map(|m| m.ctype.alignof()).max().unwrap_or(0)
+                members.borrow().iter().map(|m| m.align).max().unwrap_or(0)
             }
             Self::Statement => 0,
         }
     }
 
+    // 宣言のみされてメンバーが決まっていない構造体/共用体ならば真を返す
+    pub fn is_incomplete(&self) -> bool {
+        match self {
+            Self::Struct(_, members, _) | Self::Union(_, members, _) => members.borrow().is_empty(),
+            // 要素数を省略した配列は要素数0とする
+            Self::Array(base, len) => *len == 0 || base.is_incomplete(),
+            _ => false,
+        }
+    }
+
     // Arrayのbaseを再帰的に辿り返す。
     // 例えば、int[2][2]ならSome(int), (int*)[3]ならSome(int*)。
     pub fn array_base(&self) -> Option<&Self> {
@@ -323,16 +755,52 @@ impl CType {
         }
     }
 
+    // 関数か関数へのポインタならば関数の型を返す
+    pub fn fn_type(&self) -> Option<&Self> {
+        match self {
+            Self::Function(..) => Some(self),
+            Self::Pointer(base) if matches!(**base, Self::Function(..)) => Some(base),
+            _ => None,
+        }
+    }
+
+    // ()で宣言された、引数の型が不明な関数かどうか
+    pub fn is_unprototyped(&self) -> bool {
+        matches!(self, Self::Function(_, params, true) if params.is_empty())
+    }
+
+    // 同じ関数を再宣言したときの型を返す。
+    // 引数の型が不明な宣言は戻り値の型が同じならプロトタイプのある宣言と両立し、
+    // プロトタイプの型を使う。両立しなければNoneを返す。
+    pub fn composite(&self, other: &Self) -> Option<Self> {
+        match (self, other) {
+            (Self::Function(a_ret, ..), Self::Function(b_ret, ..))
+                if a_ret == b_ret && (self.is_unprototyped() || other.is_unprototyped()) =>
+            {
+                if self.is_unprototyped() {
+                    Some(other.clone())
+                } else {
+                    Some(self.clone())
+                }
+            }
+            _ if self == other => Some(self.clone()),
+            _ => None,
+        }
+    }
+
     pub fn base(&self) -> Option<&Self> {
         match self {
-            Self::Array(base, _) => Some(&base),
-            Self::Pointer(base) => Some(&base),
+            Self::Array(base, _) => Some(base),
+            Self::Pointer(base) => Some(base),
             _ => None,
         }
     }
 
     // ptr + nがptrのn番目の要素を指すようにnをsizeof(ptr)倍する
     fn index(node: &mut Node, size: usize) {
+        // intのままsizeof倍すると桁あふれするのでlongに変換する
+        Self::convert(node, Self::Integer(Integer::Long));
+
         let dummy_node = Node::null_statement(Rc::clone(&node.token));
         let org_node = Box::new(replace(node, dummy_node));
 
@@ -350,7 +818,7 @@ impl CType {
         let dummy_kind = NodeKind::Num(0);
         let org_kind = replace(kind, dummy_kind);
 
-        let ctype = Self::Integer(Integer::Int);
+        let ctype = Self::Integer(Integer::Long);
         let org_node = Box::new(Node {
             token: Rc::clone(token),
             kind: org_kind,
@@ -367,8 +835,21 @@ impl CType {
 impl fmt::Display for CType {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
+            Self::Void => write!(f, "void"),
+            Self::Integer(Integer::Bool) => write!(f, "_Bool"),
             Self::Integer(Integer::Char) => write!(f, "char"),
+            Self::Integer(Integer::UChar) => write!(f, "unsigned char"),
+            Self::Integer(Integer::Short) => write!(f, "short"),
+            Self::Integer(Integer::UShort) => write!(f, "unsigned short"),
             Self::Integer(Integer::Int) => write!(f, "int"),
+            Self::Integer(Integer::UInt) => write!(f, "unsigned int"),
+            Self::Integer(Integer::Long) => write!(f, "long"),
+            Self::Integer(Integer::ULong) => write!(f, "unsigned long"),
+            Self::Integer(Integer::LongLong) => write!(f, "long long"),
+            Self::Integer(Integer::ULongLong) => write!(f, "unsigned long long"),
+            Self::Floating(Floating::Float) => write!(f, "float"),
+            Self::Floating(Floating::Double) => write!(f, "double"),
+            Self::Floating(Floating::LongDouble) => write!(f, "long double"),
             Self::Pointer(base) => write!(f, "{}*", base),
             Self::Array(base, size) => write!(f, "{}[{}]", base, size),
             Self::Struct(name, members, _) | Self::Union(name, members, _) => {
@@ -378,12 +859,13 @@ impl fmt::Display for CType {
                     "union"
                 };
 
+                // メンバーに自身へのポインタを持つことがあるのでタグがあればタグのみ表示する
                 if let Some(name) = name {
-                    let _ = write!(f, "{} {} {{", struct_or_union, name);
-                } else {
-                    let _ = write!(f, "{} {{", struct_or_union);
+                    return write!(f, "{} {}", struct_or_union, name);
                 }
+                let _ = write!(f, "{} {{", struct_or_union);
 
+                let members = members.borrow();
                 let mut i = members.iter().peekable();
                 while let Some(m) = i.next() {
                     let _ = write!(
@@ -397,6 +879,20 @@ impl fmt::Display for CType {
 
                 write!(f, "}}")
             }
+            Self::Function(ret_type, params, variadic) => {
+                let _ = write!(f, "{} (", ret_type);
+
+                let mut i = params.iter().peekable();
+                while let Some(param) = i.next() {
+                    let _ = write!(f, "{}{}", param, if i.peek().is_some() { ", " } else { "" });
+                }
+
+                if *variadic && !params.is_empty() {
+                    let _ = write!(f, ", ...");
+                }
+
+                write!(f, ")")
+            }
             Self::Statement => write!(f, "Statement"),
         }
     }
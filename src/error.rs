@@ -1,3 +1,4 @@
+use super::src::Source;
 use super::tokenize::Loc;
 
 macro_rules! error {
@@ -12,42 +13,83 @@ macro_rules! error {
 }
 
 macro_rules! error_at_impl {
-    ($src:expr, $at:expr, $msg:expr) => {
-        use crate::error::get_error_line;
-
-        let (line, corr) = get_error_line(&$src.code, $at);
-        let path_row = match $src.path {
-            Some(ref p) => format!("{}:{}: ", p, $at.row + 1),
-            None => format!("-:{}: ", $at.row + 1),
-        };
-        let at = $at.col + corr + path_row.chars().count();
-
-        eprintln!("{}{}", path_row, line);
-        eprint!("{}^ ", " ".repeat(at));
-        eprintln!("{}", $msg);
+    ($src:expr, $at:expr, $path:expr, $row:expr, $msg:expr) => {
+        crate::error::print_at(&$src, $at, $path, $row, &$msg);
         std::process::exit(1);
     };
 }
 
 macro_rules! error_at {
     ($src:expr, $at:expr, $fmt:expr) => {
-        error_at_impl!($src, $at, $fmt);
+        error_at_impl!($src, $at, $src.path.as_deref(), $at.row, $fmt);
     };
     ($src:expr, $at:expr, $fmt:expr, $($arg:tt)*) => {
         let msg = format!($fmt, $($arg)*);
-        error_at_impl!($src, $at, msg);
+        error_at_impl!($src, $at, $src.path.as_deref(), $at.row, msg);
     };
 }
 
+// トークンの位置は#lineディレクティブを反映して報告する
 macro_rules! error_tok {
     ($tok:expr, $fmt:expr) => {
-        error_at!($tok.common.src, $tok.common.loc, $fmt);
+        error_at_impl!(
+            $tok.common.src,
+            $tok.common.loc,
+            $tok.common.path(),
+            $tok.common.row(),
+            $fmt
+        );
+    };
+    ($tok:expr, $fmt:expr, $($arg:tt)*) => {
+        let msg = format!($fmt, $($arg)*);
+        error_at_impl!(
+            $tok.common.src,
+            $tok.common.loc,
+            $tok.common.path(),
+            $tok.common.row(),
+            msg
+        );
     };
+}
+
+// エラーと同じ形式で出力するがコンパイルは継続する
+macro_rules! warn_tok {
     ($tok:expr, $fmt:expr, $($arg:tt)*) => {
-        error_at!($tok.common.src, $tok.common.loc, $fmt, $($arg)*);
+        let msg = format!(concat!("警告: ", $fmt), $($arg)*);
+        crate::error::print_at(
+            &$tok.common.src,
+            $tok.common.loc,
+            $tok.common.path(),
+            $tok.common.row(),
+            &msg,
+        );
     };
 }
 
+// トークンになる前の位置に対して警告を出力する
+macro_rules! warn_at {
+    ($src:expr, $at:expr, $fmt:expr, $($arg:tt)*) => {
+        let msg = format!(concat!("警告: ", $fmt), $($arg)*);
+        crate::error::print_at(&$src, $at, $src.path.as_deref(), $at.row, &msg);
+    };
+}
+
+// locが指すソースの行を表示し、その位置にmsgを出力する。
+// pathとrowは報告するファイル名と行で、#lineで変更されている場合は
+// ソースのパスやlocと異なることがある。
+pub fn print_at(src: &Source, loc: Loc, path: Option<&str>, row: usize, msg: &str) {
+    let (line, corr) = get_error_line(&src.code, loc);
+    let path_row = match path {
+        Some(p) => format!("{}:{}: ", p, row + 1),
+        None => format!("-:{}: ", row + 1),
+    };
+    let at = loc.col + corr + path_row.chars().count();
+
+    eprintln!("{}{}", path_row, line);
+    eprint!("{}^ ", " ".repeat(at));
+    eprintln!("{}", msg);
+}
+
 pub fn get_error_line(src: &str, loc: Loc) -> (String, usize) {
     let mut line = String::new();
     let mut cur_row = 0;
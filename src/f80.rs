@@ -0,0 +1,553 @@
+use std::cmp::Ordering;
+
+// 拡張倍精度の指数部のバイアスと最大値
+const EXP_BIAS: i64 = 16383;
+const EXP_MAX: u16 = 0x7fff;
+// 非正規化数の仮数の最下位ビットの指数
+const MIN_LSB_EXP: i64 = 1 - EXP_BIAS - 63;
+
+// x87の80bit拡張倍精度の浮動小数点数。
+// long doubleの定数式をホストのf64より高い精度で計算するために使う。
+// 演算はすべて最近接偶数丸めとする。
+#[derive(Clone, Copy)]
+pub struct F80 {
+    // 整数部の1ビットを省略しない64ビットの仮数部
+    pub mantissa: u64,
+    // 符号(最上位ビット)と15ビットの指数部
+    pub sign_exp: u16,
+}
+
+// 有効桁数がprecisionビットになるようにm * 2^expを丸め、仮数と最下位ビットの指数を返す。
+// stickyはmより下位に0でないビットが残っていることを表す。
+// min_lsb_expより小さい桁は表現できないので、非正規化数の場合は有効桁数が減る。
+pub fn round_bits(
+    m: u128,
+    exp: i64,
+    sticky: bool,
+    precision: i64,
+    min_lsb_exp: i64,
+) -> (u128, i64) {
+    let bits = 128 - m.leading_zeros() as i64;
+    let lsb = (exp + bits - precision).max(min_lsb_exp);
+    let shift = lsb - exp;
+
+    if shift <= 0 {
+        return (m << -shift, lsb);
+    }
+
+    let rest = m & 1u128.checked_shl(shift as u32).map_or(u128::MAX, |n| n - 1);
+    let half = 1u128.checked_shl(shift as u32 - 1).unwrap_or(0);
+    let mut m = m.checked_shr(shift as u32).unwrap_or(0);
+
+    if half != 0 && (rest > half || (rest == half && (sticky || m & 1 == 1))) {
+        m += 1;
+    }
+
+    // 繰り上がりで桁が増えたら1桁減らす
+    if m >> precision != 0 {
+        (m >> 1, lsb + 1)
+    } else {
+        (m, lsb)
+    }
+}
+
+impl F80 {
+    fn new(sign: bool, mantissa: u64, exp: u16) -> Self {
+        Self {
+            mantissa,
+            sign_exp: (sign as u16) << 15 | exp,
+        }
+    }
+
+    fn zero(sign: bool) -> Self {
+        Self::new(sign, 0, 0)
+    }
+
+    fn inf(sign: bool) -> Self {
+        Self::new(sign, 1 << 63, EXP_MAX)
+    }
+
+    fn nan() -> Self {
+        Self::new(false, 3 << 62, EXP_MAX)
+    }
+
+    // m * 2^expを拡張倍精度に丸める
+    pub fn from_parts(sign: bool, m: u128, exp: i64, sticky: bool) -> Self {
+        if m == 0 {
+            return Self::zero(sign);
+        }
+
+        // 範囲外の指数は計算途中であふれないように先に処理する
+        let top = exp + 127 - m.leading_zeros() as i64;
+        if top > EXP_BIAS {
+            return Self::inf(sign);
+        }
+        if top < MIN_LSB_EXP - 1 {
+            return Self::zero(sign);
+        }
+
+        let (m, lsb) = round_bits(m, exp, sticky, 64, MIN_LSB_EXP);
+        let exp = if m >> 63 == 0 { 0 } else { lsb + 63 + EXP_BIAS };
+
+        if exp >= EXP_MAX as i64 {
+            Self::inf(sign)
+        } else {
+            Self::new(sign, m as u64, exp as u16)
+        }
+    }
+
+    pub fn from_f64(f: f64) -> Self {
+        let bits = f.to_bits();
+        let sign = bits >> 63 == 1;
+        let exp = ((bits >> 52) & 0x7ff) as i64;
+        let frac = bits & ((1 << 52) - 1);
+
+        match exp {
+            0 => Self::from_parts(sign, frac as u128, -1074, false),
+            0x7ff if frac == 0 => Self::inf(sign),
+            0x7ff => Self::nan(),
+            _ => Self::from_parts(sign, (1 << 52 | frac) as u128, exp - 1075, false),
+        }
+    }
+
+    // 64ビットの整数は拡張倍精度で正確に表現できる
+    pub fn from_i128(n: i128) -> Self {
+        Self::from_parts(n < 0, n.unsigned_abs(), 0, false)
+    }
+
+    // 10進数の浮動小数点定数を正しく丸めて変換する。
+    // sには接尾辞と桁区切りを含めない。
+    pub fn from_decimal(s: &str) -> Option<Self> {
+        let (mantissa, exp) = match s.find(&['e', 'E'][..]) {
+            Some(pos) => (&s[..pos], &s[pos + 1..]),
+            None => (s, "0"),
+        };
+
+        let exp_digits = exp.strip_prefix(|c| c == '+' || c == '-').unwrap_or(exp);
+        if exp_digits.is_empty() || !exp_digits.chars().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+        // 桁数が多すぎる指数は飽和させる
+        let mut e = exp_digits.parse::<i64>().unwrap_or(i64::MAX / 2);
+        if exp.starts_with('-') {
+            e = -e;
+        }
+
+        let (int_part, frac_part) = match mantissa.find('.') {
+            Some(pos) => (&mantissa[..pos], &mantissa[pos + 1..]),
+            None => (mantissa, ""),
+        };
+        if (int_part.is_empty() && frac_part.is_empty())
+            || !int_part
+                .chars()
+                .chain(frac_part.chars())
+                .all(|c| c.is_ascii_digit())
+        {
+            return None;
+        }
+
+        let digits: Vec<u32> = int_part
+            .chars()
+            .chain(frac_part.chars())
+            .skip_while(|c| *c == '0')
+            .map(|c| c.to_digit(10).unwrap())
+            .collect();
+        if digits.is_empty() {
+            return Some(Self::zero(false));
+        }
+        let e = e.saturating_sub(frac_part.len() as i64);
+
+        // 10^4933を超える値は無限大、10^-4952未満の値は0になる
+        let top = e.saturating_add(digits.len() as i64);
+        if top > 4934 {
+            return Some(Self::inf(false));
+        }
+        if top < -4952 {
+            return Some(Self::zero(false));
+        }
+
+        let mut n = BigUint::from_u32(0);
+        for d in digits {
+            n.mul_add(10, d);
+        }
+
+        if e >= 0 {
+            for _ in 0..e {
+                n.mul_add(10, 0);
+            }
+            // 上位128ビットを仮数とし、残りはsticky bitに集約する
+            let shift = n.bits().saturating_sub(128);
+            let sticky = n.has_low_bits(shift);
+            n.shr(shift);
+            return Some(Self::from_parts(false, n.to_u128(), shift as i64, sticky));
+        }
+
+        // n / 10^-eの商が丸めに十分な80ビット程度になるように桁をずらしてから割る
+        let mut d = BigUint::from_u32(1);
+        for _ in 0..-e {
+            d.mul_add(10, 0);
+        }
+        let shift = d.bits() as i64 + 80 - n.bits() as i64;
+        if shift >= 0 {
+            n.shl(shift as usize);
+        } else {
+            d.shl(-shift as usize);
+        }
+        let (q, rem_is_zero) = n.div(&d);
+
+        Some(Self::from_parts(false, q, -shift, !rem_is_zero))
+    }
+
+    fn sign(&self) -> bool {
+        self.sign_exp >> 15 == 1
+    }
+
+    fn exp(&self) -> u16 {
+        self.sign_exp & EXP_MAX
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.exp() == 0 && self.mantissa == 0
+    }
+
+    fn is_inf(&self) -> bool {
+        self.exp() == EXP_MAX && self.mantissa << 1 == 0
+    }
+
+    fn is_nan(&self) -> bool {
+        self.exp() == EXP_MAX && self.mantissa << 1 != 0
+    }
+
+    // 有限の0でない値を最上位ビットが1の仮数と最下位ビットの指数にする
+    fn normalize(&self) -> (u64, i64) {
+        let exp = self.exp() as i64;
+        if exp == 0 {
+            let shift = self.mantissa.leading_zeros();
+            (self.mantissa << shift, MIN_LSB_EXP - shift as i64)
+        } else {
+            (self.mantissa, exp - EXP_BIAS - 63)
+        }
+    }
+
+    pub fn neg(self) -> Self {
+        Self {
+            mantissa: self.mantissa,
+            sign_exp: self.sign_exp ^ 1 << 15,
+        }
+    }
+
+    pub fn add(self, rhs: Self) -> Self {
+        if self.is_nan() || rhs.is_nan() {
+            return Self::nan();
+        }
+        if self.is_inf() || rhs.is_inf() {
+            return match (self.is_inf(), rhs.is_inf()) {
+                (true, true) if self.sign() != rhs.sign() => Self::nan(),
+                (true, _) => self,
+                _ => rhs,
+            };
+        }
+        if self.is_zero() {
+            // 符号の異なる0の和は+0とする
+            return if rhs.is_zero() {
+                Self::zero(self.sign() && rhs.sign())
+            } else {
+                rhs
+            };
+        }
+        if rhs.is_zero() {
+            return self;
+        }
+
+        let (lm, le) = self.normalize();
+        let (rm, re) = rhs.normalize();
+
+        // 大きい方の仮数の下に63ビットの余裕を持たせて桁を揃える。
+        // 小さい方からあふれたビットは最下位ビットに集約すれば丸めの結果は変わらない。
+        let base = le.max(re) - 63;
+        let align = |m: u64, e: i64| -> u128 {
+            let shift = e - base;
+            if shift >= 0 {
+                (m as u128) << shift
+            } else if -shift >= 128 {
+                1
+            } else {
+                let m = m as u128;
+                let rest = m & ((1 << -shift) - 1);
+                m >> -shift | (rest != 0) as u128
+            }
+        };
+        let lm = align(lm, le);
+        let rm = align(rm, re);
+
+        if self.sign() == rhs.sign() {
+            Self::from_parts(self.sign(), lm + rm, base, false)
+        } else if lm >= rm {
+            Self::from_parts(self.sign(), lm - rm, base, false)
+        } else {
+            Self::from_parts(rhs.sign(), rm - lm, base, false)
+        }
+    }
+
+    pub fn sub(self, rhs: Self) -> Self {
+        self.add(rhs.neg())
+    }
+
+    pub fn mul(self, rhs: Self) -> Self {
+        let sign = self.sign() != rhs.sign();
+
+        if self.is_nan() || rhs.is_nan() {
+            return Self::nan();
+        }
+        if self.is_inf() || rhs.is_inf() {
+            return if self.is_zero() || rhs.is_zero() {
+                Self::nan()
+            } else {
+                Self::inf(sign)
+            };
+        }
+        if self.is_zero() || rhs.is_zero() {
+            return Self::zero(sign);
+        }
+
+        let (lm, le) = self.normalize();
+        let (rm, re) = rhs.normalize();
+
+        Self::from_parts(sign, lm as u128 * rm as u128, le + re, false)
+    }
+
+    pub fn div(self, rhs: Self) -> Self {
+        let sign = self.sign() != rhs.sign();
+
+        if self.is_nan() || rhs.is_nan() || (self.is_inf() && rhs.is_inf()) {
+            return Self::nan();
+        }
+        if self.is_inf() {
+            return Self::inf(sign);
+        }
+        if rhs.is_inf() {
+            return Self::zero(sign);
+        }
+        if rhs.is_zero() {
+            return if self.is_zero() {
+                Self::nan()
+            } else {
+                Self::inf(sign)
+            };
+        }
+        if self.is_zero() {
+            return Self::zero(sign);
+        }
+
+        let (lm, le) = self.normalize();
+        let (rm, re) = rhs.normalize();
+
+        // 仮数はどちらも2^63以上なので、商の上位は2^63以上2^65未満になる。
+        // 丸めに使う下位の桁は余りをさらに割って求める。
+        let rm = rm as u128;
+        let q1 = ((lm as u128) << 64) / rm;
+        let r1 = ((lm as u128) << 64) % rm;
+        let q2 = (r1 << 64) / rm;
+        let r2 = (r1 << 64) % rm;
+
+        let m = q1 << 62 | q2 >> 2;
+        let sticky = q2 & 3 != 0 || r2 != 0;
+
+        Self::from_parts(sign, m, le - re - 64 - 62, sticky)
+    }
+
+    pub fn to_f64(self) -> f64 {
+        if self.is_nan() {
+            return f64::NAN;
+        }
+
+        let sign = if self.sign() { -1.0 } else { 1.0 };
+        if self.is_inf() {
+            return sign * f64::INFINITY;
+        }
+        if self.is_zero() {
+            return sign * 0.0;
+        }
+
+        let (m, e) = self.normalize();
+        let (m, lsb) = round_bits(m as u128, e, false, 53, -1074);
+        let exp = if m >> 52 == 0 { 0 } else { lsb + 52 + 1023 };
+
+        if exp >= 0x7ff {
+            sign * f64::INFINITY
+        } else {
+            let bits =
+                (self.sign() as u64) << 63 | (exp as u64) << 52 | (m as u64 & ((1 << 52) - 1));
+            f64::from_bits(bits)
+        }
+    }
+
+    // 小数部を切り捨てて整数にする。範囲外の値は飽和させる。
+    pub fn to_i128(self) -> i128 {
+        if self.is_nan() || self.is_zero() {
+            return 0;
+        }
+        if self.is_inf() {
+            return if self.sign() { i128::MIN } else { i128::MAX };
+        }
+
+        let (m, e) = self.normalize();
+        let n = if e >= 64 {
+            i128::MAX
+        } else if e >= 0 {
+            ((m as u128) << e) as i128
+        } else if e > -64 {
+            (m >> -e) as i128
+        } else {
+            0
+        };
+
+        if self.sign() {
+            -n
+        } else {
+            n
+        }
+    }
+}
+
+// 0は符号によらず等しく、NaNはどの値とも等しくない
+impl PartialEq for F80 {
+    fn eq(&self, other: &Self) -> bool {
+        self.partial_cmp(other) == Some(Ordering::Equal)
+    }
+}
+
+impl PartialOrd for F80 {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        if self.is_nan() || other.is_nan() {
+            return None;
+        }
+        if self.is_zero() && other.is_zero() {
+            return Some(Ordering::Equal);
+        }
+        if self.sign() != other.sign() {
+            return Some(if self.sign() {
+                Ordering::Less
+            } else {
+                Ordering::Greater
+            });
+        }
+
+        // 指数部と仮数部の順に比べれば絶対値の大小が決まる
+        let ord = (self.exp(), self.mantissa).cmp(&(other.exp(), other.mantissa));
+        Some(if self.sign() { ord.reverse() } else { ord })
+    }
+}
+
+// 10進数の変換に使う符号なし多倍長整数。
+// 32ビットごとに下位から格納する。
+struct BigUint {
+    digits: Vec<u32>,
+}
+
+impl BigUint {
+    fn from_u32(n: u32) -> Self {
+        Self { digits: vec![n] }
+    }
+
+    // self = self * m + a
+    fn mul_add(&mut self, m: u32, a: u32) {
+        let mut carry = a as u64;
+        for d in self.digits.iter_mut() {
+            let n = *d as u64 * m as u64 + carry;
+            *d = n as u32;
+            carry = n >> 32;
+        }
+        if carry != 0 {
+            self.digits.push(carry as u32);
+        }
+    }
+
+    fn bits(&self) -> usize {
+        match self.digits.iter().rposition(|d| *d != 0) {
+            Some(i) => i * 32 + 32 - self.digits[i].leading_zeros() as usize,
+            None => 0,
+        }
+    }
+
+    fn bit(&self, i: usize) -> bool {
+        matches!(self.digits.get(i / 32), Some(d) if d >> (i % 32) & 1 == 1)
+    }
+
+    // 下位nビットに0でないビットがあれば真を返す
+    fn has_low_bits(&self, n: usize) -> bool {
+        (0..n).any(|i| self.bit(i))
+    }
+
+    fn shl(&mut self, n: usize) {
+        let mut digits = vec![0; n / 32];
+        let shift = n % 32;
+        let mut carry = 0;
+        for d in self.digits.iter() {
+            digits.push(d << shift | carry);
+            carry = if shift == 0 { 0 } else { d >> (32 - shift) };
+        }
+        digits.push(carry);
+        self.digits = digits;
+    }
+
+    fn shr(&mut self, n: usize) {
+        let bits = self.bits();
+        let mut digits = vec![0; bits.saturating_sub(n) / 32 + 1];
+        for i in n..bits {
+            if self.bit(i) {
+                digits[(i - n) / 32] |= 1 << ((i - n) % 32);
+            }
+        }
+        self.digits = digits;
+    }
+
+    fn to_u128(&self) -> u128 {
+        self.digits
+            .iter()
+            .take(4)
+            .rev()
+            .fold(0, |acc, d| acc << 32 | *d as u128)
+    }
+
+    fn sub(&mut self, rhs: &Self) {
+        let mut borrow = 0;
+        for i in 0..self.digits.len() {
+            let r = *rhs.digits.get(i).unwrap_or(&0) as i64;
+            let n = self.digits[i] as i64 - r - borrow;
+            borrow = (n < 0) as i64;
+            self.digits[i] = n.rem_euclid(1 << 32) as u32;
+        }
+    }
+
+    fn cmp(&self, rhs: &Self) -> Ordering {
+        let len = self.digits.len().max(rhs.digits.len());
+        for i in (0..len).rev() {
+            let l = self.digits.get(i).unwrap_or(&0);
+            let r = rhs.digits.get(i).unwrap_or(&0);
+            if l != r {
+                return l.cmp(r);
+            }
+        }
+        Ordering::Equal
+    }
+
+    // 商が128ビットに収まる除算を筆算で行い、商と余りが0かどうかを返す
+    fn div(mut self, rhs: &Self) -> (u128, bool) {
+        let mut q = 0;
+        let top = self.bits().saturating_sub(rhs.bits());
+
+        for i in (0..=top).rev() {
+            let mut d = Self {
+                digits: rhs.digits.clone(),
+            };
+            d.shl(i);
+            if self.cmp(&d) != Ordering::Less {
+                self.sub(&d);
+                q |= 1 << i;
+            }
+        }
+
+        (q, self.bits() == 0)
+    }
+}
@@ -6,41 +6,176 @@ mod error;
 
 mod codegen;
 mod ctype;
+mod f80;
 mod node;
 mod parse;
 mod parse_context;
+mod pch;
 mod preprocess;
+mod preprocess_context;
 mod src;
 mod token_stream;
 mod tokenize;
+mod unicode;
 mod util;
 
 use codegen::codegen;
 use parse::parse;
-use preprocess::preprocess;
+use pch::{pch_path, write_pch, Pch, PchState};
+use preprocess::{
+    define_predefined_macros, join_adjacent_strings, preprocess, print_macros, print_preprocessed,
+    strip_gnu_extensions,
+};
+use preprocess_context::PreprocessContext;
 use src::read_input;
 use tokenize::{tokenize, Token};
 
-pub fn get_preprocessed_token(path: &str) -> Vec<Rc<Token>> {
+struct Options {
+    // 入力ファイルのパス。"-"なら標準入力
+    input: String,
+    // -E: プリプロセスのみ行い結果を出力する
+    preprocess_only: bool,
+    // -dM: -Eと共に指定し、定義済みのマクロを出力する
+    dump_macros: bool,
+    // -fgnu-compat: システムヘッダで使われるGNU拡張を受け付ける
+    gnu_compat: bool,
+    // -x c-header: 入力をヘッダとしてプリコンパイルする
+    header: bool,
+    // -o: プリコンパイル済みヘッダの出力先
+    output: Option<String>,
+}
+
+fn parse_args(args: &[String]) -> Options {
+    let mut input = None;
+    let mut preprocess_only = false;
+    let mut dump_macros = false;
+    let mut gnu_compat = false;
+    let mut header = false;
+    let mut output = None;
+
+    let mut args = args.iter().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-E" => preprocess_only = true,
+            "-dM" => dump_macros = true,
+            "-fgnu-compat" => gnu_compat = true,
+            "-x" => match args.next().map(|lang| lang.as_str()) {
+                Some("c") => header = false,
+                Some("c-header") => header = true,
+                Some(lang) => {
+                    error!("不明な言語です: {}", lang);
+                }
+                None => {
+                    error!("-xの後に言語を指定してください");
+                }
+            },
+            "-o" => match args.next() {
+                Some(path) => output = Some(path.clone()),
+                None => {
+                    error!("-oの後に出力先を指定してください");
+                }
+            },
+            _ if arg.starts_with('-') && arg != "-" => {
+                error!("不明なオプションです: {}", arg);
+            }
+            _ => {
+                if input.is_some() {
+                    error!("入力ファイルが複数指定されています");
+                }
+                input = Some(arg.clone());
+            }
+        }
+    }
+
+    if input.is_none() {
+        error!("入力ファイルが指定されていません");
+    }
+
+    if dump_macros && !preprocess_only {
+        error!("-dMは-Eと共に指定してください");
+    }
+
+    if output.is_some() && !header {
+        error!("-oは-x c-headerと共に指定してください");
+    }
+
+    if header && output.is_none() && input.as_deref() == Some("-") {
+        error!("標準入力をプリコンパイルする場合は-oを指定してください");
+    }
+
+    Options {
+        input: input.unwrap(),
+        preprocess_only,
+        dump_macros,
+        gnu_compat,
+        header,
+        output,
+    }
+}
+
+pub fn get_preprocessed_token(path: &str, ctx: &mut PreprocessContext) -> Vec<Rc<Token>> {
     let src = read_input(path);
     if src.is_err() {
         error!("ソースが読み込めません: {}", path);
     }
 
+    if path != "-" {
+        ctx.add_file(path);
+    }
+
     let token = tokenize(Rc::from(src.unwrap()));
 
-    preprocess(&token)
+    preprocess(&token, ctx)
+}
+
+// ヘッダのトークンとマクロ表をPCHとして保存する
+fn precompile_header(opts: &Options, ctx: &mut PreprocessContext) {
+    let before = PchState::new(ctx);
+
+    let mut token = get_preprocessed_token(&opts.input, ctx);
+    // 末尾のEOFを取り除く
+    token.pop();
+
+    let output = match &opts.output {
+        Some(output) => output.clone(),
+        None => pch_path(&opts.input),
+    };
+
+    if write_pch(&output, &Pch::new(token, before, ctx)).is_err() {
+        error!("PCHが書き込めません: {}", output);
+    }
 }
 
 fn main() {
     let args: Vec<String> = env::args().collect();
+    let opts = parse_args(&args);
 
-    if args.len() != 2 {
-        error!("引数の個数が正しくありません");
+    let mut pp_ctx = PreprocessContext::new();
+    pp_ctx.gnu_compat = opts.gnu_compat;
+    define_predefined_macros(&mut pp_ctx);
+
+    if opts.header && !opts.preprocess_only {
+        precompile_header(&opts, &mut pp_ctx);
+        return;
+    }
+
+    let mut token = get_preprocessed_token(&opts.input, &mut pp_ctx);
+
+    if opts.gnu_compat {
+        token = strip_gnu_extensions(&token);
     }
 
-    let token = get_preprocessed_token(&args[1]);
+    if opts.dump_macros {
+        print_macros(&pp_ctx);
+        return;
+    }
+
+    if opts.preprocess_only {
+        print_preprocessed(&token);
+        return;
+    }
 
+    let token = join_adjacent_strings(&token);
     let (node, parse_ctx) = parse(&token);
 
     codegen(&node, &parse_ctx);
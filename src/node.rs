@@ -1,8 +1,9 @@
 use std::rc::Rc;
 
-use super::ctype::CType;
+use super::ctype::{CType, Floating, Integer};
+use super::f80::F80;
 use super::parse_context::ParseContext;
-use super::tokenize::Token;
+use super::tokenize::{read_long_double, FloatType, Token, TokenKind};
 
 #[derive(Clone)]
 pub enum NodeKind {
@@ -27,17 +28,29 @@ pub enum NodeKind {
     Sub(Box<Node>, Box<Node>),
     Mul(Box<Node>, Box<Node>),
     Div(Box<Node>, Box<Node>),
+    Shl(Box<Node>, Box<Node>),
+    Shr(Box<Node>, Box<Node>),
+    LogAnd(Box<Node>, Box<Node>),
+    LogOr(Box<Node>, Box<Node>),
+    Not(Box<Node>),
+    // バイト順の反転。オペランドの型のサイズ単位で反転する。
+    Bswap(Box<Node>),
+    // cond, then, else
+    Cond(Box<Node>, Box<Node>, Box<Node>),
+    // 値をNodeのctypeに変換する
+    Cast(Box<Node>),
     Addr(Box<Node>),
     Deref(Box<Node>),
     // struct, offset
     Member(Box<Node>, usize),
     Num(isize),
+    FNum(f64),
     // name, type, offset
     LVar(String, CType, usize),
     // name, type
     GVar(String, CType),
-    // name, args
-    Call(String, Vec<Node>),
+    // 呼び出す関数, args
+    Call(Box<Node>, Vec<Node>),
 }
 
 #[derive(Clone)]
@@ -88,17 +101,185 @@ impl Node {
     }
 
     pub fn to_isize(&self) -> Option<isize> {
+        // 浮動小数点数同士の比較はf64として計算する。
+        // long doubleの比較は拡張倍精度で計算する。
         match &self.kind {
+            NodeKind::Eq(l, r) if is_long_double(&l.ctype) => {
+                return Self::cmp_f80(l, r, |l, r| l == r)
+            }
+            NodeKind::Neq(l, r) if is_long_double(&l.ctype) => {
+                return Self::cmp_f80(l, r, |l, r| l != r)
+            }
+            NodeKind::LT(l, r) if is_long_double(&l.ctype) => {
+                return Self::cmp_f80(l, r, |l, r| l < r)
+            }
+            NodeKind::LTE(l, r) if is_long_double(&l.ctype) => {
+                return Self::cmp_f80(l, r, |l, r| l <= r)
+            }
+            NodeKind::Eq(l, r) if l.ctype.is_floating() => {
+                return Self::cmp_f64(l, r, |l, r| l == r)
+            }
+            NodeKind::Neq(l, r) if l.ctype.is_floating() => {
+                return Self::cmp_f64(l, r, |l, r| l != r)
+            }
+            NodeKind::LT(l, r) if l.ctype.is_floating() => {
+                return Self::cmp_f64(l, r, |l, r| l < r)
+            }
+            NodeKind::LTE(l, r) if l.ctype.is_floating() => {
+                return Self::cmp_f64(l, r, |l, r| l <= r)
+            }
+            _ => (),
+        }
+
+        // 符号なし整数の比較、除算、右シフトは値をusizeとして計算する
+        let unsigned = match &self.kind {
+            NodeKind::LT(l, _) | NodeKind::LTE(l, _) => l.ctype.is_unsigned(),
+            _ => self.ctype.is_unsigned(),
+        };
+
+        let n = match &self.kind {
             NodeKind::Eq(l, r) => Self::bi_op(l, r, |l, r| if l == r { 1 } else { 0 }),
             NodeKind::Neq(l, r) => Self::bi_op(l, r, |l, r| if l != r { 1 } else { 0 }),
+            NodeKind::LT(l, r) if unsigned => {
+                Self::bi_op(l, r, |l, r| if (l as usize) < (r as usize) { 1 } else { 0 })
+            }
             NodeKind::LT(l, r) => Self::bi_op(l, r, |l, r| if l < r { 1 } else { 0 }),
+            NodeKind::LTE(l, r) if unsigned => {
+                Self::bi_op(l, r, |l, r| if l as usize <= r as usize { 1 } else { 0 })
+            }
             NodeKind::LTE(l, r) => Self::bi_op(l, r, |l, r| if l <= r { 1 } else { 0 }),
-            NodeKind::Add(l, r) => Self::bi_op(l, r, |l, r| l + r),
-            NodeKind::Sub(l, r) => Self::bi_op(l, r, |l, r| l - r),
-            NodeKind::Mul(l, r) => Self::bi_op(l, r, |l, r| l * r),
+            NodeKind::Add(l, r) => Self::bi_op(l, r, |l, r| l.wrapping_add(r)),
+            NodeKind::Sub(l, r) => Self::bi_op(l, r, |l, r| l.wrapping_sub(r)),
+            NodeKind::Mul(l, r) => Self::bi_op(l, r, |l, r| l.wrapping_mul(r)),
+            NodeKind::Div(l, r) if unsigned => {
+                Self::bi_op(l, r, |l, r| (l as usize / r as usize) as isize)
+            }
             NodeKind::Div(l, r) => Self::bi_op(l, r, |l, r| l / r),
+            NodeKind::Shl(l, r) => Self::bi_op(l, r, |l, r| l.wrapping_shl(r as u32)),
+            NodeKind::Shr(l, r) if unsigned => {
+                Self::bi_op(l, r, |l, r| (l as usize).wrapping_shr(r as u32) as isize)
+            }
+            NodeKind::Shr(l, r) => Self::bi_op(l, r, |l, r| l.wrapping_shr(r as u32)),
+            // 右オペランドは左オペランドで結果が決まらない場合のみ評価する
+            NodeKind::LogAnd(l, r) => match l.to_bool()? {
+                true => r.to_bool().map(|b| b as isize),
+                false => Some(0),
+            },
+            NodeKind::LogOr(l, r) => match l.to_bool()? {
+                true => Some(1),
+                false => r.to_bool().map(|b| b as isize),
+            },
+            NodeKind::Not(operand) => operand.to_bool().map(|b| !b as isize),
+            NodeKind::Cond(cond, then, els) => match cond.to_bool()? {
+                true => then.to_isize(),
+                false => els.to_isize(),
+            },
+            // 浮動小数点数から整数への変換は小数部を切り捨てる
+            NodeKind::Cast(operand) if is_long_double(&operand.ctype) => {
+                operand.to_f80().map(|f| match &self.ctype {
+                    CType::Integer(Integer::Bool) => !f.is_zero() as isize,
+                    ctype if ctype.is_unsigned() => {
+                        f.to_i128().max(0).min(u64::MAX as i128) as usize as isize
+                    }
+                    _ => f.to_i128().max(i64::MIN as i128).min(i64::MAX as i128) as isize,
+                })
+            }
+            NodeKind::Cast(operand) if operand.ctype.is_floating() => {
+                operand.to_f64().map(|f| match &self.ctype {
+                    CType::Integer(Integer::Bool) => (f != 0.0) as isize,
+                    ctype if ctype.is_unsigned() => f as usize as isize,
+                    _ => f as isize,
+                })
+            }
+            NodeKind::Cast(operand) => operand.to_isize(),
             NodeKind::Num(n) => Some(*n),
             _ => None,
+        };
+
+        // 計算結果を型で表現できる値にする
+        n.map(|n| self.ctype.convert_int(n))
+    }
+
+    pub fn to_f64(&self) -> Option<f64> {
+        // 整数の式は整数として計算してから変換する
+        if !self.ctype.is_floating() {
+            return self.to_isize().map(|n| {
+                if self.ctype.is_unsigned() {
+                    n as usize as f64
+                } else {
+                    n as f64
+                }
+            });
+        }
+
+        // long doubleは拡張倍精度で計算してからf64に丸める
+        if is_long_double(&self.ctype) {
+            return self.to_f80().map(F80::to_f64);
+        }
+
+        let f = match &self.kind {
+            NodeKind::Add(l, r) => Self::bi_op_f64(l, r, |l, r| l + r),
+            NodeKind::Sub(l, r) => Self::bi_op_f64(l, r, |l, r| l - r),
+            NodeKind::Mul(l, r) => Self::bi_op_f64(l, r, |l, r| l * r),
+            NodeKind::Div(l, r) => Self::bi_op_f64(l, r, |l, r| l / r),
+            NodeKind::Cond(cond, then, els) => match cond.to_bool()? {
+                true => then.to_f64(),
+                false => els.to_f64(),
+            },
+            NodeKind::Cast(operand) => operand.to_f64(),
+            NodeKind::FNum(f) => Some(*f),
+            _ => None,
+        };
+
+        // 計算結果を型で表現できる値にする
+        f.map(|f| self.ctype.convert_float(f))
+    }
+
+    pub fn to_f80(&self) -> Option<F80> {
+        // long double以外の式はその型で計算してから変換する
+        if !is_long_double(&self.ctype) {
+            if self.ctype.is_floating() {
+                return self.to_f64().map(F80::from_f64);
+            }
+            return self.to_isize().map(|n| {
+                if self.ctype.is_unsigned() {
+                    F80::from_i128(n as usize as i128)
+                } else {
+                    F80::from_i128(n as i128)
+                }
+            });
+        }
+
+        match &self.kind {
+            NodeKind::Add(l, r) => Self::bi_op_f80(l, r, F80::add),
+            NodeKind::Sub(l, r) => Self::bi_op_f80(l, r, F80::sub),
+            NodeKind::Mul(l, r) => Self::bi_op_f80(l, r, F80::mul),
+            NodeKind::Div(l, r) => Self::bi_op_f80(l, r, F80::div),
+            NodeKind::Cond(cond, then, els) => match cond.to_bool()? {
+                true => then.to_f80(),
+                false => els.to_f80(),
+            },
+            NodeKind::Cast(operand) => operand.to_f80(),
+            // long doubleの定数は元の文字列から変換して精度を保つ
+            NodeKind::FNum(f) => match &self.token.kind {
+                TokenKind::Float(_, FloatType::LongDouble) => {
+                    let s = &self.token.common.token_str;
+                    read_long_double(&s[..s.len() - 1].replace('\'', ""))
+                }
+                _ => Some(F80::from_f64(*f)),
+            },
+            _ => None,
+        }
+    }
+
+    // 条件式として評価した値を返す
+    fn to_bool(&self) -> Option<bool> {
+        if is_long_double(&self.ctype) {
+            self.to_f80().map(|f| !f.is_zero())
+        } else if self.ctype.is_floating() {
+            self.to_f64().map(|f| f != 0.0)
+        } else {
+            self.to_isize().map(|n| n != 0)
         }
     }
 
@@ -114,6 +295,46 @@ impl Node {
         }
     }
 
+    fn bi_op_f64<F>(lhs: &Self, rhs: &Self, bi_fn: F) -> Option<f64>
+    where
+        F: Fn(f64, f64) -> f64,
+    {
+        match (lhs.to_f64(), rhs.to_f64()) {
+            (Some(lhs), Some(rhs)) => Some(bi_fn(lhs, rhs)),
+            _ => None,
+        }
+    }
+
+    fn cmp_f64<F>(lhs: &Self, rhs: &Self, cmp_fn: F) -> Option<isize>
+    where
+        F: Fn(f64, f64) -> bool,
+    {
+        match (lhs.to_f64(), rhs.to_f64()) {
+            (Some(lhs), Some(rhs)) => Some(cmp_fn(lhs, rhs) as isize),
+            _ => None,
+        }
+    }
+
+    fn bi_op_f80<F>(lhs: &Self, rhs: &Self, bi_fn: F) -> Option<F80>
+    where
+        F: Fn(F80, F80) -> F80,
+    {
+        match (lhs.to_f80(), rhs.to_f80()) {
+            (Some(lhs), Some(rhs)) => Some(bi_fn(lhs, rhs)),
+            _ => None,
+        }
+    }
+
+    fn cmp_f80<F>(lhs: &Self, rhs: &Self, cmp_fn: F) -> Option<isize>
+    where
+        F: Fn(F80, F80) -> bool,
+    {
+        match (lhs.to_f80(), rhs.to_f80()) {
+            (Some(lhs), Some(rhs)) => Some(cmp_fn(lhs, rhs) as isize),
+            _ => None,
+        }
+    }
+
     pub fn cast(&mut self, ctype: CType) {
         self.ctype = ctype;
     }
@@ -232,6 +453,55 @@ impl Node {
                 eprintln!("{}rhs", head);
                 rhs.debug_print_impl(depth + 1);
             }
+            NodeKind::Shl(lhs, rhs) => {
+                eprintln!("{}Shl", head);
+                eprintln!("{}lhs", head);
+                lhs.debug_print_impl(depth + 1);
+                eprintln!("{}rhs", head);
+                rhs.debug_print_impl(depth + 1);
+            }
+            NodeKind::Shr(lhs, rhs) => {
+                eprintln!("{}Shr", head);
+                eprintln!("{}lhs", head);
+                lhs.debug_print_impl(depth + 1);
+                eprintln!("{}rhs", head);
+                rhs.debug_print_impl(depth + 1);
+            }
+            NodeKind::LogAnd(lhs, rhs) => {
+                eprintln!("{}LogAnd", head);
+                eprintln!("{}lhs", head);
+                lhs.debug_print_impl(depth + 1);
+                eprintln!("{}rhs", head);
+                rhs.debug_print_impl(depth + 1);
+            }
+            NodeKind::LogOr(lhs, rhs) => {
+                eprintln!("{}LogOr", head);
+                eprintln!("{}lhs", head);
+                lhs.debug_print_impl(depth + 1);
+                eprintln!("{}rhs", head);
+                rhs.debug_print_impl(depth + 1);
+            }
+            NodeKind::Not(node) => {
+                eprintln!("{}Not", head);
+                node.debug_print_impl(depth + 1);
+            }
+            NodeKind::Bswap(node) => {
+                eprintln!("{}Bswap", head);
+                node.debug_print_impl(depth + 1);
+            }
+            NodeKind::Cond(cond, then, els) => {
+                eprintln!("{}Cond", head);
+                eprintln!("{}cond", head);
+                cond.debug_print_impl(depth + 1);
+                eprintln!("{}then", head);
+                then.debug_print_impl(depth + 1);
+                eprintln!("{}else", head);
+                els.debug_print_impl(depth + 1);
+            }
+            NodeKind::Cast(node) => {
+                eprintln!("{}Cast({})", head, self.ctype);
+                node.debug_print_impl(depth + 1);
+            }
             NodeKind::Addr(node) => {
                 eprintln!("{}Addr", head);
                 node.debug_print_impl(depth + 1);
@@ -247,14 +517,18 @@ impl Node {
             NodeKind::Num(n) => {
                 eprintln!("{}Num({})", head, n);
             }
+            NodeKind::FNum(f) => {
+                eprintln!("{}FNum({}, {})", head, f, self.ctype);
+            }
             NodeKind::LVar(name, ctype, offset) => {
                 eprintln!("{}LVar({}, {}, {})", head, &name, &ctype, &offset);
             }
             NodeKind::GVar(name, ctype) => {
                 eprintln!("{}GVar({}, {})", head, &name, &ctype);
             }
-            NodeKind::Call(name, args) => {
-                eprintln!("{}Call({})", head, name);
+            NodeKind::Call(callee, args) => {
+                eprintln!("{}Call", head);
+                callee.debug_print_impl(depth + 1);
                 for arg in args.iter() {
                     arg.debug_print_impl(depth + 1);
                 }
@@ -262,3 +536,8 @@ impl Node {
         }
     }
 }
+
+// long doubleの定数式は拡張倍精度で計算する
+fn is_long_double(ctype: &CType) -> bool {
+    matches!(ctype, CType::Floating(Floating::LongDouble))
+}
@@ -1,14 +1,16 @@
 use std::rc::Rc;
 
-use super::ctype::{CType, Integer};
+use super::ctype::{CType, Floating, Integer};
 use super::node::{Node, NodeKind};
 use super::parse_context::ParseContext;
+use super::src::Source;
 use super::token_stream::TokenStream;
-use super::tokenize::Token;
+use super::tokenize::{tokenize, Encoding, FloatType, IntType, Token, TokenKind};
 
 pub fn parse(token: &[Rc<Token>]) -> (Vec<Node>, ParseContext) {
     let mut stream = TokenStream::new(token);
     let mut ctx = ParseContext::new();
+    define_builtin_types(&mut ctx);
     let nodes = program(&mut stream, &mut ctx);
 
     if !stream.at_eof() {
@@ -18,6 +20,46 @@ pub fn parse(token: &[Rc<Token>]) -> (Vec<Node>, ParseContext) {
     (nodes, ctx)
 }
 
+// コンパイラが組み込みで定義する型を登録する。
+// __builtin_va_listはx86-64のABIで定められた構造体の配列とする。
+fn define_builtin_types(ctx: &mut ParseContext) {
+    let src = Source {
+        path: Some("<built-in>".to_string()),
+        code: "typedef struct __va_list_tag {
+                   unsigned int gp_offset;
+                   unsigned int fp_offset;
+                   void *overflow_arg_area;
+                   void *reg_save_area;
+               } __builtin_va_list[1];"
+            .to_string(),
+    };
+    let token: Vec<Rc<Token>> = tokenize(Rc::new(src))
+        .into_iter()
+        .filter(|token| token.kind != TokenKind::LF)
+        .collect();
+
+    let mut stream = TokenStream::new(&token);
+    declaration(&mut stream, ctx);
+}
+
+// #ifの条件式を評価する。tokenの末尾にはEOFが必要。
+pub fn const_expr(token: &[Rc<Token>]) -> isize {
+    let mut stream = TokenStream::new(token);
+    let mut ctx = ParseContext::new();
+    let node = expr(&mut stream, &mut ctx);
+
+    if !stream.at_eof() {
+        error_tok!(stream.current().unwrap(), "余分なトークンがあります");
+    }
+
+    match node.to_isize() {
+        Some(n) => n,
+        None => {
+            error_tok!(node.token, "定数式ではありません");
+        }
+    }
+}
+
 // program := (function_definition | declaration)*
 fn program(stream: &mut TokenStream, ctx: &mut ParseContext) -> Vec<Node> {
     let mut nodes = Vec::new();
@@ -36,15 +78,171 @@ fn program(stream: &mut TokenStream, ctx: &mut ParseContext) -> Vec<Node> {
     nodes
 }
 
-// type_specifier := "int" | "char" | struct_or_union_specifier
+// type_specifier := "void"
+//                 | ("_Bool" | "bool")
+//                 | "float"
+//                 | "long"? "double"
+//                 | ("char" | "short" | "int" | "long" | "signed" | "unsigned")+
+//                 | struct_or_union_specifier
+//                 | enum_specifier
+//                 | typedef_name
 fn type_specifier(stream: &mut TokenStream, ctx: &mut ParseContext) -> Option<(CType, Rc<Token>)> {
-    if let Some(token) = stream.consume_keyword("int") {
-        Some((CType::Integer(Integer::Int), token))
-    } else if let Some(token) = stream.consume_keyword("char") {
-        Some((CType::Integer(Integer::Char), token))
+    type_qualifiers(stream);
+
+    let spec = struct_or_union_specifier(stream, ctx)
+        .or_else(|| enum_specifier(stream, ctx))
+        // typedef名は他の型指定子と組み合わせられないので先頭でのみ調べる。
+        // 先頭でなければint Tのように同名の変数を宣言していることになる。
+        .or_else(|| typedef_name(stream, ctx));
+    if spec.is_some() {
+        type_qualifiers(stream);
+        return spec;
+    }
+
+    // 各キーワードの出現回数の組み合わせで型を決める
+    const KEYWORDS: [&str; 11] = [
+        "void", "_Bool", "bool", "char", "short", "int", "long", "signed", "unsigned", "float",
+        "double",
+    ];
+    let mut counts = [0; KEYWORDS.len()];
+    let mut first_token = None;
+
+    'consume: loop {
+        if type_qualifiers(stream) {
+            continue;
+        }
+
+        for (keyword, count) in KEYWORDS.iter().zip(counts.iter_mut()) {
+            if let Some(token) = stream.consume_keyword(keyword) {
+                *count += 1;
+                first_token.get_or_insert(token);
+                continue 'consume;
+            }
+        }
+        break;
+    }
+
+    let token = first_token?;
+    let [void, bool_, bool_c23, char, short, int, long, signed, unsigned, float, double] = counts;
+    let total = counts.iter().sum::<usize>();
+
+    // voidと_Boolは他の型指定子と組み合わせられない
+    if void + bool_ + bool_c23 > 0 {
+        if total > 1 {
+            error_tok!(token, "不正な型指定子の組み合わせです");
+        }
+
+        let ctype = if void > 0 {
+            CType::Void
+        } else {
+            CType::Integer(Integer::Bool)
+        };
+        return Some((ctype, token));
+    }
+
+    if float + double > 0 {
+        let ctype = match (float, double, long, total) {
+            (1, 0, 0, 1) => CType::Floating(Floating::Float),
+            (0, 1, 0, 1) => CType::Floating(Floating::Double),
+            (0, 1, 1, 2) => CType::Floating(Floating::LongDouble),
+            _ => {
+                error_tok!(token, "不正な型指定子の組み合わせです");
+            }
+        };
+        return Some((ctype, token));
+    }
+
+    if signed + unsigned > 1 {
+        error_tok!(token, "不正な型指定子の組み合わせです");
+    }
+
+    // signed/unsignedのみの場合はintとなる
+    let integer = match [char, short, int, long] {
+        [1, 0, 0, 0] => Integer::Char,
+        [0, 1, 0, 0] | [0, 1, 1, 0] => Integer::Short,
+        [0, 0, 1, 0] | [0, 0, 0, 0] => Integer::Int,
+        [0, 0, 0, 1] | [0, 0, 1, 1] => Integer::Long,
+        [0, 0, 0, 2] | [0, 0, 1, 2] => Integer::LongLong,
+        _ => {
+            error_tok!(token, "不正な型指定子の組み合わせです");
+        }
+    };
+
+    let integer = if unsigned == 1 {
+        integer.to_unsigned()
     } else {
-        struct_or_union_specifier(stream, ctx)
+        integer
+    };
+
+    Some((CType::Integer(integer), token))
+}
+
+// type_qualifier := "const" | "volatile" | "restrict"
+// 型修飾子はコード生成に影響しないので読み捨てる。
+// 型修飾子を読み進めた場合は真を返す。
+fn type_qualifiers(stream: &mut TokenStream) -> bool {
+    let mut consumed = false;
+
+    while ["const", "volatile", "restrict"]
+        .iter()
+        .any(|qualifier| stream.consume_keyword(qualifier).is_some())
+    {
+        consumed = true;
+    }
+
+    consumed
+}
+
+// 記憶域クラス指定子と関数指定子
+#[derive(Default)]
+struct StorageClass {
+    is_static: bool,
+    is_extern: bool,
+    // 最初の指定子のトークン
+    token: Option<Rc<Token>>,
+}
+
+// storage_class := ("extern" | "static" | "register" | "inline" | "_Noreturn")*
+// registerと関数指定子はコード生成に影響しないので読み捨てる。
+fn storage_class(stream: &mut TokenStream) -> StorageClass {
+    let mut storage = StorageClass::default();
+
+    'consume: loop {
+        for keyword in ["extern", "static", "register", "inline", "_Noreturn"].iter() {
+            if let Some(token) = stream.consume_keyword(keyword) {
+                match *keyword {
+                    "extern" => storage.is_extern = true,
+                    "static" => storage.is_static = true,
+                    _ => (),
+                }
+
+                if storage.is_extern && storage.is_static {
+                    error_tok!(token, "不正な記憶域クラス指定子の組み合わせです");
+                }
+
+                storage.token.get_or_insert(token);
+                continue 'consume;
+            }
+        }
+        break;
     }
+
+    storage
+}
+
+// typedef_name := ident
+fn typedef_name(stream: &mut TokenStream, ctx: &mut ParseContext) -> Option<(CType, Rc<Token>)> {
+    let state = stream.save();
+
+    if let Some((token, name)) = stream.consume_identifier() {
+        if let Some(ctype) = ctx.find_typedef(&name) {
+            return Some((ctype, token));
+        }
+    }
+
+    // 変数名などtypedef名でない識別子は読み進めない
+    stream.restore(state);
+    None
 }
 
 enum StructOrUnion {
@@ -55,12 +253,12 @@ enum StructOrUnion {
 // struct_or_union := "struct" | "union"
 fn struct_or_union(stream: &mut TokenStream) -> Option<(StructOrUnion, Rc<Token>)> {
     if let Some(token) = stream.consume_keyword("struct") {
-        Some((StructOrUnion::Struct, token))
-    } else if let Some(token) = stream.consume_keyword("union") {
-        Some((StructOrUnion::Union, token))
-    } else {
-        None
+        return Some((StructOrUnion::Struct, token));
     }
+
+    stream
+        .consume_keyword("union")
+        .map(|token| (StructOrUnion::Union, token))
 }
 
 // struct_or_union_specifier := struct_or_union ( ident? ("{" struct_declaration "}") | ident )
@@ -72,6 +270,29 @@ fn struct_or_union_specifier(
         let tag = stream.consume_identifier().map(|ret| ret.1);
 
         if stream.consume_punctuator("{").is_some() {
+            // 同じスコープで宣言のみされていればその型を完全型にする
+            let ctype = match &tag {
+                Some(tag) => match ctx.find_current_tag(tag) {
+                    Some((ctype, _)) if !is_same_kind(&struct_or_union, &ctype) => {
+                        error_tok!(token, "{}は別の種類のタグとして定義されています", tag);
+                    }
+                    Some((ctype, false)) => ctype,
+                    Some((_, true)) => {
+                        error_tok!(token, "{}はすでに定義されています", tag);
+                    }
+                    None => new_incomplete_type(&struct_or_union, Some(tag.clone()), &token),
+                },
+                None => new_incomplete_type(&struct_or_union, None, &token),
+            };
+
+            // メンバーから自身を参照できるように、メンバーを読む前に登録する。
+            // タグをつけていない構造体は後から参照できないので登録しない。
+            if let Some(tag) = &tag {
+                if let Err(msg) = ctx.add_tag(tag, ctype.clone(), true) {
+                    error_tok!(token, "{}", msg);
+                }
+            }
+
             let members = struct_declaration(stream, ctx);
 
             stream.expect_punctuator("}");
@@ -80,31 +301,30 @@ fn struct_or_union_specifier(
                 error_tok!(token, "空の構造体/共用体は定義できません");
             }
 
-            let new_type = match struct_or_union {
-                StructOrUnion::Struct => CType::new_struct(tag, members, Rc::clone(&token)),
-                StructOrUnion::Union => CType::new_union(tag, members, Rc::clone(&token)),
-            };
-
-            match new_type {
-                Ok(ctype) => {
-                    if let Err(msg) = ctx.add_tag(ctype.clone()) {
-                        error_tok!(token, "{}", msg);
-                    }
-                    Some((ctype, token))
-                }
-                Err(msg) => {
-                    error_tok!(token, "{}", msg);
-                }
+            if let Err(msg) = ctype.set_members(members, stream.pack()) {
+                error_tok!(token, "{}", msg);
             }
+
+            Some((ctype, token))
         } else {
             if tag.is_none() {
                 error_tok!(token, "構造体/共用体のタグが指定されていません");
             }
+            let tag = tag.unwrap();
 
-            if let Some(ctype) = ctx.find_tag(tag.as_ref().unwrap()) {
-                Some((ctype, token))
-            } else {
-                error_tok!(token, "構造体/共用体{}の定義が存在しません", tag.unwrap());
+            match ctx.find_tag(&tag) {
+                Some(ctype) if is_same_kind(&struct_or_union, &ctype) => Some((ctype, token)),
+                Some(_) => {
+                    error_tok!(token, "{}は別の種類のタグとして定義されています", tag);
+                }
+                // 定義の無いタグは不完全型として宣言する
+                None => {
+                    let ctype = new_incomplete_type(&struct_or_union, Some(tag.clone()), &token);
+                    if let Err(msg) = ctx.add_tag(&tag, ctype.clone(), false) {
+                        error_tok!(token, "{}", msg);
+                    }
+                    Some((ctype, token))
+                }
             }
         }
     } else {
@@ -112,7 +332,103 @@ fn struct_or_union_specifier(
     }
 }
 
-// struct_declaration := (type_specifier declarator ";")*
+fn is_same_kind(struct_or_union: &StructOrUnion, ctype: &CType) -> bool {
+    matches!(
+        (struct_or_union, ctype),
+        (StructOrUnion::Struct, CType::Struct(..)) | (StructOrUnion::Union, CType::Union(..))
+    )
+}
+
+fn new_incomplete_type(
+    struct_or_union: &StructOrUnion,
+    tag: Option<String>,
+    token: &Rc<Token>,
+) -> CType {
+    match struct_or_union {
+        StructOrUnion::Struct => CType::new_incomplete_struct(tag, Rc::clone(token)),
+        StructOrUnion::Union => CType::new_incomplete_union(tag, Rc::clone(token)),
+    }
+}
+
+// enum_specifier := "enum" ( ident? "{" enumerator_list "}" | ident )
+fn enum_specifier(stream: &mut TokenStream, ctx: &mut ParseContext) -> Option<(CType, Rc<Token>)> {
+    let token = stream.consume_keyword("enum")?;
+    let tag = stream.consume_identifier().map(|ret| ret.1);
+
+    // 列挙型はintと互換の型とする
+    let ctype = CType::Integer(Integer::Int);
+
+    if stream.consume_punctuator("{").is_some() {
+        enumerator_list(stream, ctx);
+
+        stream.expect_punctuator("}");
+
+        if let Some(tag) = tag {
+            if let Err(msg) = ctx.add_tag(&tag, ctype.clone(), true) {
+                error_tok!(token, "{}", msg);
+            }
+        }
+
+        Some((ctype, token))
+    } else {
+        if tag.is_none() {
+            error_tok!(token, "列挙型のタグが指定されていません");
+        }
+
+        match ctx.find_tag(tag.as_ref().unwrap()) {
+            Some(CType::Integer(_)) => Some((ctype, token)),
+            Some(_) => {
+                error_tok!(
+                    token,
+                    "{}は別の種類のタグとして定義されています",
+                    tag.unwrap()
+                );
+            }
+            None => {
+                error_tok!(token, "列挙型{}の定義が存在しません", tag.unwrap());
+            }
+        }
+    }
+}
+
+// enumerator_list := enumerator ("," enumerator)* ","?
+// enumerator := ident ("=" expr)?
+fn enumerator_list(stream: &mut TokenStream, ctx: &mut ParseContext) {
+    // 値が指定されなければ直前の列挙定数の値+1とする
+    let mut val = 0;
+
+    loop {
+        let (token, name) = stream.expect_identifier();
+
+        if stream.consume_punctuator("=").is_some() {
+            let node = expr(stream, ctx);
+            match node.to_isize() {
+                Some(n) => val = n,
+                None => {
+                    error_tok!(node.token, "定数式ではありません");
+                }
+            }
+        }
+
+        // 列挙定数の型はintなので、値はintで表せなければならない。
+        // 値を指定しなかった場合も直前の値+1が範囲外になりうる。
+        if val < i32::MIN as isize || val > i32::MAX as isize {
+            error_tok!(token, "列挙定数の値がintの範囲を超えています");
+        }
+
+        // 後続の列挙定数の値に使えるようにすぐに登録する
+        if let Err(msg) = ctx.add_const(&name, val) {
+            error_tok!(token, "{}", msg);
+        }
+        val += 1;
+
+        if stream.consume_punctuator(",").is_none() || stream.is_punctuator("}") {
+            break;
+        }
+    }
+}
+
+// struct_declaration := (type_specifier declarator ("," declarator)* ";")*
 fn struct_declaration(stream: &mut TokenStream, ctx: &mut ParseContext) -> Vec<(String, CType)> {
     let mut members = Vec::new();
 
@@ -123,8 +439,18 @@ fn struct_declaration(stream: &mut TokenStream, ctx: &mut ParseContext) -> Vec<(
         }
         let base = base.unwrap().0;
 
-        let (name, ctype, _) = declarator(stream, ctx, &base);
-        members.push((name, ctype));
+        loop {
+            let (name, ctype, token) = declarator(stream, ctx, &base);
+            check_not_void(&ctype, &token);
+            if ctype.is_incomplete() {
+                error_tok!(token, "不完全型のメンバーは宣言できません");
+            }
+            members.push((name, ctype));
+
+            if stream.consume_punctuator(",").is_none() {
+                break;
+            }
+        }
 
         stream.expect_punctuator(";");
     }
@@ -132,18 +458,23 @@ fn struct_declaration(stream: &mut TokenStream, ctx: &mut ParseContext) -> Vec<(
     members
 }
 
-// type_specifier "*"* ident "(" ならば真を返す
-// それ以外は偽を返す
+// storage_class type_specifier declarator "{" で、宣言子が識別子の直後に
+// 引数リストを持つ関数型ならば真を返す。それ以外は偽を返す。
+// 本体の無い関数宣言は偽となる。
 fn is_function(stream: &mut TokenStream, ctx: &mut ParseContext) -> bool {
     let mut result = false;
 
     let stream_state = stream.save();
     let ctx_state = ctx.save();
 
-    if type_specifier(stream, ctx).is_some() {
-        while stream.consume_punctuator("*").is_some() {}
-        if stream.consume_identifier().is_some() && stream.consume_punctuator("(").is_some() {
-            result = true;
+    storage_class(stream);
+    if let Some((base, _)) = type_specifier(stream, ctx) {
+        // struct S;のように宣言子の無い宣言は関数定義ではない
+        if !stream.is_punctuator(";") {
+            let (_, ctype, params) = declarator_impl(stream, ctx, &base, false);
+            result = matches!(ctype, CType::Function(..))
+                && params.is_some()
+                && stream.is_punctuator("{");
         }
     }
 
@@ -153,24 +484,42 @@ fn is_function(stream: &mut TokenStream, ctx: &mut ParseContext) -> bool {
     result
 }
 
-// function_definition := type_specifier function_declarator "{" compound_stmt
+// function_definition := storage_class type_specifier declarator "{" compound_stmt
 fn function_definition(stream: &mut TokenStream, ctx: &mut ParseContext) -> Node {
-    if type_specifier(stream, ctx).is_none() {
-        error_tok!(stream.current().unwrap(), "型ではありません");
-    }
+    let storage = storage_class(stream);
+    let base = match type_specifier(stream, ctx) {
+        Some((ctype, _)) => ctype,
+        None => {
+            error_tok!(stream.current().unwrap(), "型ではありません");
+        }
+    };
 
-    let (token, name, params) = function_declarator(stream, ctx);
+    // is_functionで確認しているので識別子と引数は必ずある
+    let (ident, ctype, params) = declarator_impl(stream, ctx, &base, false);
+    let (token, name) = ident.unwrap();
+    let params = params.unwrap();
 
-    if params.len() > 6 {
-        error_tok!(token, "引数が6つを超える関数定義はサポートしていません");
+    if let CType::Function(_, param_types, _) = &ctype {
+        if let Err(msg) = check_arg_regs(param_types) {
+            error_tok!(token, "{}", msg);
+        }
     }
 
-    if let Err(msg) = ctx.enter_fn(&name) {
+    if let Err(msg) = ctx.enter_fn(&name, ctype, storage.is_static) {
         error_tok!(token, "{}", msg);
     }
 
     // 引数をローカル変数として登録する
-    for Parameter { token, name, ctype } in params.iter() {
+    let params: Vec<(Rc<Token>, String, CType)> = params
+        .into_iter()
+        .map(|Parameter { token, name, ctype }| match name {
+            Some(name) => (token, name, ctype),
+            None => {
+                error_tok!(token, "引数名がありません");
+            }
+        })
+        .collect();
+    for (token, name, ctype) in params.iter() {
         if let Err(msg) = ctx.add_var(name, ctype.clone()) {
             error_tok!(token, "{}", msg);
         }
@@ -178,12 +527,7 @@ fn function_definition(stream: &mut TokenStream, ctx: &mut ParseContext) -> Node
 
     // Defun構築用に引数のオフセットを取得する
     let mut offsets = Vec::new();
-    for Parameter {
-        token: _,
-        name,
-        ctype,
-    } in params.into_iter()
-    {
+    for (_, name, ctype) in params.into_iter() {
         if let Some(NodeKind::LVar(_, _, offset)) = ctx.find_lvar(&name) {
             offsets.push((offset, ctype));
         } else {
@@ -203,53 +547,77 @@ fn function_definition(stream: &mut TokenStream, ctx: &mut ParseContext) -> Node
 
 struct Parameter {
     token: Rc<Token>,
-    name: String,
+    // 関数宣言では引数名を省略できる
+    name: Option<String>,
     ctype: CType,
 }
 
 impl Parameter {
-    fn new(token: Rc<Token>, name: String, ctype: CType) -> Self {
+    fn new(token: Rc<Token>, name: Option<String>, ctype: CType) -> Self {
         Self { token, name, ctype }
     }
 }
 
-// function_declarator := ident "(" (type_specifier declarator ("," type_specifier declarator)*)? ")"
-fn function_declarator(
-    stream: &mut TokenStream,
-    ctx: &mut ParseContext,
-) -> (Rc<Token>, String, Vec<Parameter>) {
-    let (func_token, func_name) = stream.expect_identifier();
-
-    stream.expect_punctuator("(");
-
+// func_params := ("void" | param ("," param)* ("," "...")?)? ")"
+// param := type_specifier (declarator | abstract_declarator)
+// 戻り値: 引数, 可変長引数か
+fn func_params(stream: &mut TokenStream, ctx: &mut ParseContext) -> (Vec<Parameter>, bool) {
     let mut params = Vec::new();
 
+    // ()は引数の型が不明な関数を表し、任意の引数を取れる
     if stream.consume_punctuator(")").is_some() {
-        return (func_token, func_name, params);
+        return (params, true);
+    }
+
+    // (void)は引数が無いことを表す
+    let state = stream.save();
+    if stream.consume_keyword("void").is_some() && stream.consume_punctuator(")").is_some() {
+        return (params, false);
     }
+    stream.restore(state);
 
+    let mut variadic = false;
     loop {
-        let type_spec = type_specifier(stream, ctx);
-        if type_spec.is_none() {
-            error_tok!(stream.current().unwrap(), "型ではありません");
-        }
-        let base = type_spec.unwrap().0;
+        let (base, base_token) = match type_specifier(stream, ctx) {
+            Some(spec) => spec,
+            None => {
+                error_tok!(stream.current().unwrap(), "型ではありません");
+            }
+        };
 
-        let (name, ctype, token) = declarator(stream, ctx, &base);
+        let (ident, ctype, _) = declarator_impl(stream, ctx, &base, true);
+        let (token, name) = match ident {
+            Some((token, name)) => (token, Some(name)),
+            None => (base_token, None),
+        };
+        check_not_void(&ctype, &token);
+        check_not_struct(&ctype, &token);
+
+        // 関数型の引数は関数へのポインタ、配列型の引数は要素へのポインタとして扱う
+        let ctype = match ctype {
+            CType::Function(..) => CType::Pointer(Box::new(ctype)),
+            CType::Array(base, _) => CType::Pointer(base),
+            _ => ctype,
+        };
 
         params.push(Parameter::new(token, name, ctype));
 
         if stream.consume_punctuator(",").is_none() {
             break;
         }
+
+        if stream.consume_punctuator("...").is_some() {
+            variadic = true;
+            break;
+        }
     }
 
     stream.expect_punctuator(")");
 
-    (func_token, func_name, params)
+    (params, variadic)
 }
 
-// stmt := "return" expr ";"
+// stmt := "return" expr? ";"
 //       | "{" compound_stmt
 //       | "if" "(" expr ")" stmt ("else" stmt)?
 //       | "for" "(" expr_stmt expr? ";" expr? ")" stmt
@@ -257,8 +625,35 @@ fn function_declarator(
 //       | expr_stmt ";"
 fn stmt(stream: &mut TokenStream, ctx: &mut ParseContext) -> Node {
     if let Some(token) = stream.consume_keyword("return") {
-        let node = expr(stream, ctx);
+        // 関数の外にreturnは書けないのでunwrapして問題ない
+        let ret_type = ctx.ret_type().unwrap().clone();
+
+        if stream.consume_punctuator(";").is_some() {
+            if ret_type != CType::Void {
+                warn_tok!(token, "{}", "値を返す関数で値を返していません");
+            }
+            let node = Node::null_statement(Rc::clone(&token));
+            return Node::new(token, NodeKind::Return(Box::new(node)));
+        }
+
+        let mut node = expr(stream, ctx);
         stream.expect_punctuator(";");
+
+        if ret_type == CType::Void {
+            error_tok!(node.token, "voidを返す関数で値を返しています");
+        }
+
+        // 戻り値は代入と同じ規則で関数の戻り値の型に変換する
+        if CType::assign_conv(&mut node, &ret_type).is_err() {
+            error_tok!(
+                node.token,
+                "{}型の値は{}型を返す関数の戻り値にできません",
+                node.ctype,
+                ret_type
+            );
+        }
+        implicit_cast(&mut node, &ret_type);
+
         Node::new(token, NodeKind::Return(Box::new(node)))
     } else if stream.consume_punctuator("{").is_some() {
         compound_stmt(stream, ctx)
@@ -355,21 +750,67 @@ fn compound_stmt(stream: &mut TokenStream, ctx: &mut ParseContext) -> Node {
     Node::new(token.unwrap(), NodeKind::Block(nodes))
 }
 
-// declaration := type_specifier init_declarator
+// declaration := "typedef" type_specifier typedef_declarator
+//              | storage_class type_specifier init_declarator
 fn declaration(stream: &mut TokenStream, ctx: &mut ParseContext) -> Option<Vec<Node>> {
-    if let Some((ctype, token)) = type_specifier(stream, ctx) {
-        let mut init_nodes = init_declarator(stream, ctx, &ctype);
-        // ({int x=1;})のようなstatement expressionの値がintに
-        // ならないように、最後にCType::Statementとなるノードを入れる。
-        init_nodes.push(Node::null_statement(token));
-        Some(init_nodes)
+    if let Some(token) = stream.consume_keyword("typedef") {
+        let base = match type_specifier(stream, ctx) {
+            Some((ctype, _)) => ctype,
+            None => {
+                error_tok!(stream.current().unwrap(), "型ではありません");
+            }
+        };
+        typedef_declarator(stream, ctx, &base);
+        Some(vec![Node::null_statement(token)])
     } else {
-        None
+        let storage = storage_class(stream);
+
+        match type_specifier(stream, ctx) {
+            Some((ctype, token)) => {
+                let mut init_nodes = init_declarator(stream, ctx, &ctype, &storage);
+                // ({int x=1;})のようなstatement expressionの値がintに
+                // ならないように、最後にCType::Statementとなるノードを入れる。
+                init_nodes.push(Node::null_statement(token));
+                Some(init_nodes)
+            }
+            None => {
+                if storage.token.is_some() {
+                    error_tok!(stream.current().unwrap(), "型ではありません");
+                }
+                None
+            }
+        }
     }
 }
 
-// init_declarator := (declarator ("=" initializer)? ("," declarator ("=" initializer)?)*)? ";"
-fn init_declarator(stream: &mut TokenStream, ctx: &mut ParseContext, base: &CType) -> Vec<Node> {
+// typedef_declarator := (declarator ("," declarator)*)? ";"
+fn typedef_declarator(stream: &mut TokenStream, ctx: &mut ParseContext, base: &CType) {
+    if stream.consume_punctuator(";").is_some() {
+        return;
+    }
+
+    loop {
+        let (name, ctype, token) = declarator(stream, ctx, base);
+
+        if let Err(msg) = ctx.add_typedef(&name, ctype) {
+            error_tok!(token, "{}", msg);
+        }
+
+        if stream.consume_punctuator(",").is_none() {
+            break;
+        }
+    }
+
+    stream.expect_punctuator(";");
+}
+
+// init_declarator := (declarator asm_label? ("=" initializer)? ("," declarator asm_label? ("=" initializer)?)*)? ";"
+fn init_declarator(
+    stream: &mut TokenStream,
+    ctx: &mut ParseContext,
+    base: &CType,
+    storage: &StorageClass,
+) -> Vec<Node> {
     let mut init_nodes = Vec::new();
 
     if stream.consume_punctuator(";").is_some() {
@@ -378,12 +819,51 @@ fn init_declarator(stream: &mut TokenStream, ctx: &mut ParseContext, base: &CTyp
 
     loop {
         let (ident, ctype, ident_token) = declarator(stream, ctx, base);
+        check_not_void(&ctype, &ident_token);
+        let label = asm_label(stream);
+
+        // 関数宣言は変数を定義せずに関数の型だけを登録する
+        if matches!(ctype, CType::Function(..)) {
+            if let Err(msg) = ctx.declare_fn(&ident, ctype, storage.is_static, label) {
+                error_tok!(ident_token, "{}", msg);
+            }
 
-        if let Err(msg) = ctx.add_var(&ident, ctype.clone()) {
+            if stream.consume_punctuator(",").is_none() {
+                break;
+            }
+            continue;
+        }
+
+        // externで宣言する変数は他の翻訳単位で完全型として定義される
+        if ctype.is_incomplete() && !storage.is_extern {
+            error_tok!(ident_token, "不完全型の変数は宣言できません");
+        }
+
+        let result = if !ctx.in_function() {
+            ctx.add_gvar(
+                &ident,
+                ctype.clone(),
+                storage.is_static,
+                storage.is_extern,
+                label,
+            )
+        } else if storage.is_static || storage.is_extern || label.is_some() {
+            error_tok!(
+                storage.token.as_ref().unwrap_or(&ident_token),
+                "関数内のstatic/extern/asmラベル付きの変数はサポートしていません"
+            );
+        } else {
+            ctx.add_var(&ident, ctype.clone())
+        };
+        if let Err(msg) = result {
             error_tok!(&ident_token, "{}", msg);
         }
 
         if let Some(assign_token) = stream.consume_punctuator("=") {
+            if storage.is_extern {
+                error_tok!(assign_token, "externで宣言した変数は初期化できません");
+            }
+
             // 配列だったらinitializerが"{"で始まるかチェックする
             if matches!(&ctype, CType::Array(..)) {
                 let state = stream.save();
@@ -421,42 +901,182 @@ fn init_declarator(stream: &mut TokenStream, ctx: &mut ParseContext, base: &CTyp
     init_nodes
 }
 
-// declarator := "*"* ident ("[" expr "]")*
+// asm_label := ("__asm__" | "__asm") "(" str ")"
+// 変数や関数のアセンブラのシンボル名を指定する
+fn asm_label(stream: &mut TokenStream) -> Option<String> {
+    let state = stream.save();
+
+    match stream.consume_identifier() {
+        Some((_, name)) if name == "__asm__" || name == "__asm" => (),
+        _ => {
+            stream.restore(state);
+            return None;
+        }
+    }
+
+    stream.expect_punctuator("(");
+    let (token, mut label) = stream.expect_string();
+    stream.expect_punctuator(")");
+
+    // 末尾のヌル文字を除く
+    label.pop();
+    if !matches!(token.kind, TokenKind::Str(_, Encoding::Char)) || label.is_empty() {
+        error_tok!(token, "不正なasmラベルです");
+    }
+
+    match String::from_utf8(label) {
+        Ok(label) => Some(label),
+        Err(_) => {
+            error_tok!(token, "不正なasmラベルです");
+        }
+    }
+}
+
+// declarator := ("*" type_qualifier*)* ("(" declarator ")" | ident) type_suffix
 fn declarator(
     stream: &mut TokenStream,
     ctx: &mut ParseContext,
     base: &CType,
 ) -> (String, CType, Rc<Token>) {
+    let (ident, ctype, _) = declarator_impl(stream, ctx, base, false);
+
+    // 抽象宣言子を許さない場合は識別子が必ずある
+    let (token, name) = ident.unwrap();
+
+    (name, ctype, token)
+}
+
+// abstract_declarator := ("*" type_qualifier*)* ("(" abstract_declarator ")")? type_suffix
+fn abstract_declarator(stream: &mut TokenStream, ctx: &mut ParseContext, base: &CType) -> CType {
+    let (ident, ctype, _) = declarator_impl(stream, ctx, base, true);
+
+    if let Some((token, _)) = ident {
+        error_tok!(token, "型名に識別子は書けません");
+    }
+
+    ctype
+}
+
+// 宣言子の識別子のトークンと名前
+type DeclIdent = (Rc<Token>, String);
+
+// allow_abstractが真ならば識別子を省略した抽象宣言子も読む。
+// 識別子の直後が引数リストの場合は、関数定義で使うためにその引数も返す。
+fn declarator_impl(
+    stream: &mut TokenStream,
+    ctx: &mut ParseContext,
+    base: &CType,
+    allow_abstract: bool,
+) -> (Option<DeclIdent>, CType, Option<Vec<Parameter>>) {
     let mut ctype = base.clone();
     while stream.consume_punctuator("*").is_some() {
         ctype = CType::Pointer(Box::new(ctype));
+        type_qualifiers(stream);
     }
 
-    let (token, name) = stream.expect_identifier();
+    // 括弧の中の宣言子は括弧の後ろの型を基にする。
+    // 例えばint (*x)[3]はint[3]を基にして*xを読み、xはint[3]へのポインタとなる。
+    // そのため、先に括弧の中を読み飛ばして後ろの型を決めてから括弧の中を読み直す。
+    if is_nested_declarator(stream, allow_abstract) {
+        stream.expect_punctuator("(");
+        let start = stream.save();
+        declarator_impl(stream, ctx, &CType::Void, allow_abstract);
+        stream.expect_punctuator(")");
 
-    let mut array_sizes = Vec::new();
-    while stream.consume_punctuator("[").is_some() {
-        let n_node = expr(stream, ctx);
+        let (ctype, params) = type_suffix_with_params(stream, ctx, ctype);
+        let end = stream.save();
 
-        if let Some(n) = n_node.to_isize() {
-            if n <= 0 {
-                error_tok!(n_node.token, "要素数が0以下の配列は定義できません");
-            }
-            array_sizes.push(n as usize);
-        } else {
-            error_tok!(n_node.token, "要素数が定数式ではありません");
+        stream.restore(start);
+        let (ident, inner, inner_params) = declarator_impl(stream, ctx, &ctype, allow_abstract);
+        stream.restore(end);
+
+        // int (f)(int a)のように括弧の中が識別子だけならば後ろの引数は識別子のもの
+        if inner_params.is_none() && inner == ctype {
+            return (ident, inner, params);
+        }
+        return (ident, inner, inner_params);
+    }
+
+    let ident = if allow_abstract {
+        stream.consume_identifier()
+    } else {
+        Some(stream.expect_identifier())
+    };
+
+    let (ctype, params) = type_suffix_with_params(stream, ctx, ctype);
+
+    (ident, ctype, params)
+}
+
+// 括弧で囲まれた宣言子ならば真を返す。
+// 抽象宣言子ではint (int)のような引数の括弧と区別するため
+// 括弧の後ろが"*"か"("の場合のみ真とする。
+fn is_nested_declarator(stream: &mut TokenStream, allow_abstract: bool) -> bool {
+    let state = stream.save();
+
+    let result = stream.consume_punctuator("(").is_some()
+        && (!allow_abstract || stream.is_punctuator("*") || stream.is_punctuator("("));
+
+    stream.restore(state);
+
+    result
+}
+
+// type_suffix := "(" func_params | "[" expr? "]" type_suffix | ε
+fn type_suffix(stream: &mut TokenStream, ctx: &mut ParseContext, ctype: CType) -> CType {
+    type_suffix_with_params(stream, ctx, ctype).0
+}
+
+// 関数型の場合は型と一緒に引数も返す
+fn type_suffix_with_params(
+    stream: &mut TokenStream,
+    ctx: &mut ParseContext,
+    ctype: CType,
+) -> (CType, Option<Vec<Parameter>>) {
+    if let Some(token) = stream.consume_punctuator("(") {
+        let (params, variadic) = func_params(stream, ctx);
+
+        if matches!(ctype, CType::Function(..) | CType::Array(..)) {
+            error_tok!(token, "関数や配列を返す関数は宣言できません");
         }
 
-        stream.expect_punctuator("]");
+        let param_types = params.iter().map(|param| param.ctype.clone()).collect();
+        let ctype = CType::Function(Box::new(ctype), param_types, variadic);
+        return (ctype, Some(params));
     }
 
-    // int[2][3]はArray(Array(int, 3), 2)となるので
-    // 逆順に配列サイズを見ていく。
-    for n in array_sizes.into_iter().rev() {
-        ctype = CType::Array(Box::new(ctype), n);
+    if let Some(token) = stream.consume_punctuator("[") {
+        // 要素数を省略した配列は要素数0の不完全型とする
+        let n = if stream.consume_punctuator("]").is_some() {
+            0
+        } else {
+            let n_node = expr(stream, ctx);
+
+            let n = match n_node.to_isize() {
+                Some(n) if n <= 0 => {
+                    error_tok!(n_node.token, "要素数が0以下の配列は定義できません");
+                }
+                Some(n) => n as usize,
+                None => {
+                    error_tok!(n_node.token, "要素数が定数式ではありません");
+                }
+            };
+
+            stream.expect_punctuator("]");
+            n
+        };
+
+        // int[2][3]はArray(Array(int, 3), 2)となるので
+        // 後ろの配列サイズから型を決める。
+        let base = type_suffix(stream, ctx, ctype);
+        if matches!(base, CType::Function(..)) {
+            error_tok!(token, "関数の配列は宣言できません");
+        }
+
+        return (CType::Array(Box::new(base), n), None);
     }
 
-    (name, ctype, token)
+    (ctype, None)
 }
 
 // initializer := expr | "{" initializer ("," initializer)* ","? "}"
@@ -517,7 +1137,7 @@ fn set_init_val_to_lvar(
     let mut init_nodes = Vec::new();
 
     match &ctype {
-        CType::Integer(_) | CType::Pointer(_) => {
+        CType::Integer(_) | CType::Floating(_) | CType::Pointer(_) => {
             let lhs = Box::new(Node::var(ident_name, ident_token, ctx));
             let rhs = Box::new(initializer_nodes.pop().unwrap());
             let init_node = Node::new(assign_token, NodeKind::Assign(lhs, rhs));
@@ -567,7 +1187,7 @@ fn set_init_val_to_lvar(
 }
 
 fn set_init_val_to_gvar(ident_name: &str, initializer_nodes: Vec<Node>, ctx: &mut ParseContext) {
-    if ctx.set_val(&ident_name, initializer_nodes).is_err() {
+    if ctx.set_val(ident_name, initializer_nodes).is_err() {
         unreachable!();
     }
 }
@@ -588,9 +1208,9 @@ fn expr(stream: &mut TokenStream, ctx: &mut ParseContext) -> Node {
     assign(stream, ctx)
 }
 
-// assign := equality ("=" assign)?
+// assign := conditional ("=" assign)?
 fn assign(stream: &mut TokenStream, ctx: &mut ParseContext) -> Node {
-    let mut node = equality(stream, ctx);
+    let mut node = conditional(stream, ctx);
 
     if let Some(token) = stream.consume_punctuator("=") {
         let lhs = Box::new(node);
@@ -601,6 +1221,46 @@ fn assign(stream: &mut TokenStream, ctx: &mut ParseContext) -> Node {
     node
 }
 
+// conditional := logor ("?" expr ":" conditional)?
+fn conditional(stream: &mut TokenStream, ctx: &mut ParseContext) -> Node {
+    let cond = logor(stream, ctx);
+
+    if let Some(token) = stream.consume_punctuator("?") {
+        let then = Box::new(expr(stream, ctx));
+        stream.expect_punctuator(":");
+        let els = Box::new(conditional(stream, ctx));
+        Node::new(token, NodeKind::Cond(Box::new(cond), then, els))
+    } else {
+        cond
+    }
+}
+
+// logor := logand ("||" logand)*
+fn logor(stream: &mut TokenStream, ctx: &mut ParseContext) -> Node {
+    let mut node = logand(stream, ctx);
+
+    while let Some(token) = stream.consume_punctuator("||") {
+        let lhs = Box::new(node);
+        let rhs = Box::new(logand(stream, ctx));
+        node = Node::new(token, NodeKind::LogOr(lhs, rhs));
+    }
+
+    node
+}
+
+// logand := equality ("&&" equality)*
+fn logand(stream: &mut TokenStream, ctx: &mut ParseContext) -> Node {
+    let mut node = equality(stream, ctx);
+
+    while let Some(token) = stream.consume_punctuator("&&") {
+        let lhs = Box::new(node);
+        let rhs = Box::new(equality(stream, ctx));
+        node = Node::new(token, NodeKind::LogAnd(lhs, rhs));
+    }
+
+    node
+}
+
 // equality := relational ("==" relational | "!=" relational)*
 fn equality(stream: &mut TokenStream, ctx: &mut ParseContext) -> Node {
     let mut node = relational(stream, ctx);
@@ -620,27 +1280,27 @@ fn equality(stream: &mut TokenStream, ctx: &mut ParseContext) -> Node {
     }
 }
 
-// relational := add ("<" add | "<=" add | ">" add | ">=" add)*
+// relational := shift ("<" shift | "<=" shift | ">" shift | ">=" shift)*
 fn relational(stream: &mut TokenStream, ctx: &mut ParseContext) -> Node {
-    let mut node = add(stream, ctx);
+    let mut node = shift(stream, ctx);
 
     loop {
         if let Some(token) = stream.consume_punctuator("<") {
             let lhs = Box::new(node);
-            let rhs = Box::new(add(stream, ctx));
+            let rhs = Box::new(shift(stream, ctx));
             node = Node::new(token, NodeKind::LT(lhs, rhs));
         } else if let Some(token) = stream.consume_punctuator("<=") {
             let lhs = Box::new(node);
-            let rhs = Box::new(add(stream, ctx));
+            let rhs = Box::new(shift(stream, ctx));
             node = Node::new(token, NodeKind::LTE(lhs, rhs));
         } else if let Some(token) = stream.consume_punctuator(">") {
             let lhs = Box::new(node);
-            let rhs = Box::new(add(stream, ctx));
+            let rhs = Box::new(shift(stream, ctx));
             // LTの左右のオペランドを入れ替えてGTにする
             node = Node::new(token, NodeKind::LT(rhs, lhs));
         } else if let Some(token) = stream.consume_punctuator(">=") {
             let lhs = Box::new(node);
-            let rhs = Box::new(add(stream, ctx));
+            let rhs = Box::new(shift(stream, ctx));
             // LTEの左右のオペランドを入れ替えてGTEにする
             node = Node::new(token, NodeKind::LTE(rhs, lhs));
         } else {
@@ -649,6 +1309,25 @@ fn relational(stream: &mut TokenStream, ctx: &mut ParseContext) -> Node {
     }
 }
 
+// shift := add ("<<" add | ">>" add)*
+fn shift(stream: &mut TokenStream, ctx: &mut ParseContext) -> Node {
+    let mut node = add(stream, ctx);
+
+    loop {
+        if let Some(token) = stream.consume_punctuator("<<") {
+            let lhs = Box::new(node);
+            let rhs = Box::new(add(stream, ctx));
+            node = Node::new(token, NodeKind::Shl(lhs, rhs));
+        } else if let Some(token) = stream.consume_punctuator(">>") {
+            let lhs = Box::new(node);
+            let rhs = Box::new(add(stream, ctx));
+            node = Node::new(token, NodeKind::Shr(lhs, rhs));
+        } else {
+            return node;
+        }
+    }
+}
+
 // expr := mul ("+" mul | "-" mul)*
 fn add(stream: &mut TokenStream, ctx: &mut ParseContext) -> Node {
     let mut node = mul(stream, ctx);
@@ -668,18 +1347,18 @@ fn add(stream: &mut TokenStream, ctx: &mut ParseContext) -> Node {
     }
 }
 
-// mul := unary ("*" unary | "/" unary)*
+// mul := cast ("*" cast | "/" cast)*
 fn mul(stream: &mut TokenStream, ctx: &mut ParseContext) -> Node {
-    let mut node = unary(stream, ctx);
+    let mut node = cast(stream, ctx);
 
     loop {
         if let Some(token) = stream.consume_punctuator("*") {
             let lhs = Box::new(node);
-            let rhs = Box::new(unary(stream, ctx));
+            let rhs = Box::new(cast(stream, ctx));
             node = Node::new(token, NodeKind::Mul(lhs, rhs));
         } else if let Some(token) = stream.consume_punctuator("/") {
             let lhs = Box::new(node);
-            let rhs = Box::new(unary(stream, ctx));
+            let rhs = Box::new(cast(stream, ctx));
             node = Node::new(token, NodeKind::Div(lhs, rhs));
         } else {
             return node;
@@ -687,29 +1366,95 @@ fn mul(stream: &mut TokenStream, ctx: &mut ParseContext) -> Node {
     }
 }
 
-// unary := (("+" | "-" | "&" | "*" | "sizeof")? unary) | postfix
+// cast := "(" type_name ")" cast | unary
+fn cast(stream: &mut TokenStream, ctx: &mut ParseContext) -> Node {
+    if !is_cast(stream, ctx) {
+        return unary(stream, ctx);
+    }
+
+    let token = stream.expect_punctuator("(");
+    let ctype = type_name(stream, ctx);
+    stream.expect_punctuator(")");
+
+    let mut node = cast(stream, ctx);
+    if let Err(msg) = CType::cast(&mut node, ctype) {
+        error_tok!(token, "{}", msg);
+    }
+
+    node
+}
+
+// "(" type_specifier ならば真を返す
+// それ以外は偽を返す
+fn is_cast(stream: &mut TokenStream, ctx: &mut ParseContext) -> bool {
+    let mut result = false;
+
+    let stream_state = stream.save();
+    let ctx_state = ctx.save();
+
+    if stream.consume_punctuator("(").is_some() && type_specifier(stream, ctx).is_some() {
+        result = true;
+    }
+
+    ctx.restore(ctx_state);
+    stream.restore(stream_state);
+
+    result
+}
+
+// type_name := type_specifier abstract_declarator
+fn type_name(stream: &mut TokenStream, ctx: &mut ParseContext) -> CType {
+    let base = match type_specifier(stream, ctx) {
+        Some((ctype, _)) => ctype,
+        None => {
+            error_tok!(stream.current().unwrap(), "型ではありません");
+        }
+    };
+
+    abstract_declarator(stream, ctx, &base)
+}
+
+// unary := ("+" | "-" | "&" | "*" | "!") cast | "sizeof" ("(" type_name ")" | unary) | postfix
 fn unary(stream: &mut TokenStream, ctx: &mut ParseContext) -> Node {
     if stream.consume_punctuator("+").is_some() {
-        unary(stream, ctx)
+        cast(stream, ctx)
     } else if let Some(token) = stream.consume_punctuator("-") {
         let lhs = Box::new(Node::new(Rc::clone(&token), NodeKind::Num(0)));
-        let rhs = Box::new(unary(stream, ctx));
+        let rhs = Box::new(cast(stream, ctx));
         Node::new(token, NodeKind::Sub(lhs, rhs))
     } else if let Some(token) = stream.consume_punctuator("&") {
-        let operand = Box::new(unary(stream, ctx));
+        let operand = Box::new(cast(stream, ctx));
         Node::new(token, NodeKind::Addr(operand))
     } else if let Some(token) = stream.consume_punctuator("*") {
-        let operand = Box::new(unary(stream, ctx));
+        let operand = Box::new(cast(stream, ctx));
         Node::new(token, NodeKind::Deref(operand))
+    } else if let Some(token) = stream.consume_punctuator("!") {
+        let operand = Box::new(cast(stream, ctx));
+        Node::new(token, NodeKind::Not(operand))
     } else if let Some(token) = stream.consume_keyword("sizeof") {
-        let operand = unary(stream, ctx);
-        Node::new(token, NodeKind::Num(operand.ctype.size() as isize))
+        let ctype = if is_cast(stream, ctx) {
+            stream.expect_punctuator("(");
+            let ctype = type_name(stream, ctx);
+            stream.expect_punctuator(")");
+            ctype
+        } else {
+            unary(stream, ctx).ctype
+        };
+
+        if ctype.is_incomplete() {
+            error_tok!(token, "不完全型のサイズは取得できません");
+        }
+
+        // sizeofの結果はsize_t(unsigned long)
+        let mut node = Node::new(token, NodeKind::Num(ctype.size() as isize));
+        node.cast(CType::Integer(Integer::ULong));
+        node
     } else {
         postfix(stream, ctx)
     }
 }
 
-// postfix := primary ( "[" expr "]" | "." ident | "->" ident )*
+// postfix := primary ( "[" expr "]" | "." ident | "->" ident | call_args )*
 fn postfix(stream: &mut TokenStream, ctx: &mut ParseContext) -> Node {
     let mut node = primary(stream, ctx);
 
@@ -733,6 +1478,8 @@ fn postfix(stream: &mut TokenStream, ctx: &mut ParseContext) -> Node {
 
             node = Node::new(arrow_token, NodeKind::Deref(Box::new(node)));
             node = Node::member(mem_token, node, &mem_name);
+        } else if let Some(args) = call_args(stream, ctx) {
+            node = func_call(node, args);
         } else {
             break;
         }
@@ -741,7 +1488,12 @@ fn postfix(stream: &mut TokenStream, ctx: &mut ParseContext) -> Node {
     node
 }
 
-// primary := "(" "{" compound_stmt ")" | "(" expr ")" | num | str | ident call_args?
+// primary := "(" "{" compound_stmt ")"
+//          | "(" expr ")"
+//          | num
+//          | "true" | "false"
+//          | str
+//          | ident
 fn primary(stream: &mut TokenStream, ctx: &mut ParseContext) -> Node {
     if let Some(token) = stream.consume_punctuator("(") {
         if stream.consume_punctuator("{").is_some() {
@@ -754,24 +1506,131 @@ fn primary(stream: &mut TokenStream, ctx: &mut ParseContext) -> Node {
             node
         }
     } else if let Some((token, n)) = stream.consume_number() {
-        Node::new(token, NodeKind::Num(n))
+        // 整数定数はトークナイズ時に決まった型とする
+        let integer = match token.kind {
+            TokenKind::Num(_, IntType::UInt) => Integer::UInt,
+            TokenKind::Num(_, IntType::Long) => Integer::Long,
+            TokenKind::Num(_, IntType::ULong) => Integer::ULong,
+            TokenKind::Num(_, IntType::LongLong) => Integer::LongLong,
+            TokenKind::Num(_, IntType::ULongLong) => Integer::ULongLong,
+            _ => Integer::Int,
+        };
+        let mut node = Node::new(token, NodeKind::Num(n));
+        node.cast(CType::Integer(integer));
+        node
+    } else if let Some(token) = stream.consume_keyword("true") {
+        let mut node = Node::new(token, NodeKind::Num(1));
+        node.cast(CType::Integer(Integer::Bool));
+        node
+    } else if let Some(token) = stream.consume_keyword("false") {
+        let mut node = Node::new(token, NodeKind::Num(0));
+        node.cast(CType::Integer(Integer::Bool));
+        node
+    } else if let Some((token, f, float_type)) = stream.consume_float() {
+        // 接尾辞fならfloat、lならlong double、それ以外はdoubleとする
+        let mut node = Node::new(token, NodeKind::FNum(f));
+        match float_type {
+            FloatType::Float => node.cast(CType::Floating(Floating::Float)),
+            FloatType::LongDouble => node.cast(CType::Floating(Floating::LongDouble)),
+            FloatType::Double => (),
+        }
+        node
     } else if let Some((token, s)) = stream.consume_string() {
-        let (label, ctype) = ctx.add_str(s);
+        let base = match token.kind {
+            TokenKind::Str(_, enc) => match enc {
+                Encoding::Char | Encoding::Utf8 => Integer::Char,
+                // char16_t, char32_t
+                Encoding::Utf16 => Integer::UShort,
+                Encoding::Utf32 => Integer::UInt,
+                Encoding::Wide => Integer::Int,
+            },
+            _ => unreachable!(),
+        };
+        let (label, ctype) = ctx.add_str(s, CType::Integer(base));
         Node::new(token, NodeKind::GVar(label, ctype))
+    } else if let Some(node) = builtin_bswap(stream, ctx) {
+        node
     } else {
         let (token, name) = stream.expect_identifier();
 
-        if let Some(args) = call_args(stream, ctx) {
-            // 関数呼び出し
-            if args.len() > 6 {
-                error_tok!(token, "引数が6つを超える関数呼び出しはサポートしていません");
+        // 宣言されていない関数はintを返し、任意の引数を取る関数とみなす
+        if ctx.find_var(&name).is_none() && stream.is_punctuator("(") {
+            let ctype = CType::Function(Box::new(CType::Integer(Integer::Int)), Vec::new(), true);
+            return Node::new(token, NodeKind::GVar(name, ctype));
+        }
+
+        Node::var(&name, token, ctx)
+    }
+}
+
+// builtin_bswap := ("__builtin_bswap16" | "__builtin_bswap32" | "__builtin_bswap64") "(" assign ")"
+fn builtin_bswap(stream: &mut TokenStream, ctx: &mut ParseContext) -> Option<Node> {
+    let state = stream.save();
+
+    let (token, integer) = match stream.consume_identifier() {
+        Some((token, name)) if name == "__builtin_bswap16" => (token, Integer::UShort),
+        Some((token, name)) if name == "__builtin_bswap32" => (token, Integer::UInt),
+        Some((token, name)) if name == "__builtin_bswap64" => (token, Integer::ULong),
+        _ => {
+            stream.restore(state);
+            return None;
+        }
+    };
+
+    stream.expect_punctuator("(");
+    let mut operand = assign(stream, ctx);
+    stream.expect_punctuator(")");
+
+    if !matches!(operand.ctype, CType::Integer(_)) {
+        error_tok!(operand.token, "整数ではありません");
+    }
+    implicit_cast(&mut operand, &CType::Integer(integer));
+
+    Some(Node::new(token, NodeKind::Bswap(Box::new(operand))))
+}
+
+// 関数呼び出しのノードを作る。
+// 引数は関数の引数の型に変換し、可変長引数の部分では既定の実引数拡張をする。
+fn func_call(callee: Node, mut args: Vec<Node>) -> Node {
+    let token = Rc::clone(&callee.token);
+
+    let (params, variadic) = match callee.ctype.fn_type() {
+        Some(CType::Function(_, params, variadic)) => (params.clone(), *variadic),
+        _ => {
+            error_tok!(token, "関数ではありません");
+        }
+    };
+
+    if args.len() < params.len() || (!variadic && args.len() > params.len()) {
+        error_tok!(token, "引数の数が一致しません");
+    }
+
+    for (i, arg) in args.iter_mut().enumerate() {
+        check_not_struct(&arg.ctype, &arg.token);
+
+        match params.get(i) {
+            // 引数は代入と同じ規則で引数の型に変換する
+            Some(param) => {
+                if CType::assign_conv(arg, param).is_err() {
+                    error_tok!(
+                        arg.token,
+                        "{}型の値は{}型の引数に渡せません",
+                        arg.ctype,
+                        param
+                    );
+                }
+                implicit_cast(arg, param);
             }
-            Node::new(token, NodeKind::Call(name, args))
-        } else {
-            // 変数
-            Node::var(&name, token, ctx)
+            None => CType::promote_arg(arg),
         }
     }
+
+    let arg_types: Vec<CType> = args.iter().map(|arg| arg.ctype.clone()).collect();
+    if let Err(msg) = check_arg_regs(&arg_types) {
+        error_tok!(token, "{}", msg);
+    }
+
+    Node::new(token, NodeKind::Call(Box::new(callee), args))
 }
 
 // call_args := "(" (expr ("," expr)*)? ")"
@@ -798,3 +1657,58 @@ fn call_args(stream: &mut TokenStream, ctx: &mut ParseContext) -> Option<Vec<Nod
         None
     }
 }
+
+// 引数がすべてレジスタで渡せるか確認する。
+// 整数とポインタは6つ、floatとdoubleは8つまでレジスタで渡せる。
+// long doubleは常にメモリで渡す。
+fn check_arg_regs(ctypes: &[CType]) -> Result<(), &'static str> {
+    let fp = ctypes
+        .iter()
+        .filter(|ctype| {
+            matches!(
+                ctype,
+                CType::Floating(Floating::Float) | CType::Floating(Floating::Double)
+            )
+        })
+        .count();
+    let gp = ctypes.iter().filter(|ctype| !ctype.is_floating()).count();
+
+    if gp > 6 {
+        Err("整数の引数が6つを超える関数はサポートしていません")
+    } else if fp > 8 {
+        Err("浮動小数点数の引数が8つを超える関数はサポートしていません")
+    } else {
+        Ok(())
+    }
+}
+
+// 引数や戻り値を代入と同じように変換先の型に変換する
+fn implicit_cast(node: &mut Node, ctype: &CType) {
+    let convertible = match (&node.ctype, ctype) {
+        (from, to) if from.is_arithmetic() && to.is_arithmetic() => from != to,
+        (CType::Pointer(_), CType::Integer(Integer::Bool)) => true,
+        // 関数は関数へのポインタに変換する
+        (CType::Function(..), CType::Pointer(_)) => true,
+        _ => false,
+    };
+
+    if convertible {
+        if let Err(msg) = CType::cast(node, ctype.clone()) {
+            error_tok!(node.token, "{}", msg);
+        }
+    }
+}
+
+// 構造体と共用体を値で渡す引数はサポートしていない
+fn check_not_struct(ctype: &CType, token: &Rc<Token>) {
+    if matches!(ctype, CType::Struct(..) | CType::Union(..)) {
+        error_tok!(token, "構造体や共用体の引数はサポートしていません");
+    }
+}
+
+// 変数、引数、メンバーはvoid型にできない
+fn check_not_void(ctype: &CType, token: &Rc<Token>) {
+    if *ctype == CType::Void {
+        error_tok!(token, "void型の変数は宣言できません");
+    }
+}
@@ -1,7 +1,8 @@
 use std::mem::swap;
 
-use super::ctype::{CType, Integer};
+use super::ctype::CType;
 use super::node::{Node, NodeKind};
+use super::util::align_to;
 
 #[derive(Clone)]
 pub struct LVar {
@@ -17,8 +18,14 @@ pub struct LVar {
 pub struct GVar {
     // 変数の名前
     pub name: String,
+    // アセンブラのシンボル名。asmラベルが指定されていなければ変数の名前。
+    pub symbol: String,
     // 変数の型
     pub ctype: CType,
+    // staticならば他の翻訳単位から参照できない
+    pub is_static: bool,
+    // externならば他の翻訳単位で定義されたものを参照する
+    pub is_extern: bool,
     // 初期値
     pub val: Option<Vec<Node>>,
 }
@@ -29,87 +36,180 @@ pub struct Str {
     pub label: String,
     // 文字列の中身
     pub val: Vec<u8>,
+    // 要素の型
+    pub base: CType,
+}
+
+#[derive(Clone)]
+struct FnDecl {
+    // 関数の名前
+    name: String,
+    // 関数の型
+    ctype: CType,
+    // asmラベルで指定されたシンボル名
+    label: Option<String>,
+    // staticならば他の翻訳単位から参照できない
+    is_static: bool,
+}
+
+impl FnDecl {
+    fn symbol(&self) -> &str {
+        self.label.as_ref().unwrap_or(&self.name)
+    }
+}
+
+#[derive(Clone)]
+struct EnumConst {
+    // 列挙定数の名前
+    name: String,
+    // 列挙定数の値
+    val: isize,
+}
+
+#[derive(Clone)]
+struct Typedef {
+    // typedef名
+    name: String,
+    // typedef名が表す型
+    ctype: CType,
+}
+
+// 通常の識別子の名前空間に属するもの。
+// 内側のスコープで宣言されたものは外側の同名のものを隠す。
+#[derive(Clone)]
+enum Ident {
+    Var(LVar),
+    Const(EnumConst),
+    Typedef(Typedef),
+}
+
+impl Ident {
+    fn name(&self) -> &str {
+        match self {
+            Self::Var(lvar) => &lvar.name,
+            Self::Const(c) => &c.name,
+            Self::Typedef(typedef) => &typedef.name,
+        }
+    }
+
+    // 変数と列挙定数は式の中で値を表すノードとなる。
+    // 列挙定数は値そのものを表すノードとなる。
+    fn to_node_kind(&self) -> Option<NodeKind> {
+        match self {
+            Self::Var(v) => Some(NodeKind::LVar(v.name.clone(), v.ctype.clone(), v.offset)),
+            Self::Const(c) => Some(NodeKind::Num(c.val)),
+            Self::Typedef(..) => None,
+        }
+    }
+
+    fn to_typedef(&self) -> Option<CType> {
+        match self {
+            Self::Typedef(typedef) => Some(typedef.ctype.clone()),
+            _ => None,
+        }
+    }
+
+    // 同じ型を表すtypedef名は同じスコープで再定義できる
+    fn is_redefinable(&self, new: &Self) -> bool {
+        match (self, new) {
+            (Self::Typedef(defined), Self::Typedef(new)) => defined.ctype == new.ctype,
+            _ => false,
+        }
+    }
+}
+
+#[derive(Clone)]
+struct Tag {
+    // 構造体/共用体/列挙型のタグ
+    name: String,
+    // タグが表す型。列挙型はintとする。
+    ctype: CType,
+    // 偽ならば宣言のみされた不完全型の構造体/共用体
+    defined: bool,
 }
 
 #[derive(Clone)]
 struct Scope {
     child: Option<Box<Self>>,
-    lvars: Vec<LVar>,
-    tags: Vec<CType>,
+    idents: Vec<Ident>,
+    tags: Vec<Tag>,
 }
 
-fn find_tag(tags: &[CType], name: &str) -> Option<CType> {
-    tags
-        .iter()
-        .find(|c| matches!(c, CType::Struct(n, ..) | CType::Union(n, ..) if n.as_ref().map_or(false, |n| n == name)))
-        .cloned()
+fn find_tag(tags: &[Tag], name: &str) -> Option<CType> {
+    tags.iter()
+        .find(|tag| tag.name == name)
+        .map(|tag| tag.ctype.clone())
 }
 
-fn get_tag<'a>(ctype: &'a CType) -> Result<Option<&'a str>, &'static str> {
-    match ctype {
-        CType::Struct(name, ..) | CType::Union(name, ..) => {
-            if let Some(name) = name.as_ref() {
-                Ok(Some(name))
-            } else {
-                Ok(None)
-            }
+// 同じスコープで宣言のみされた構造体/共用体のタグは、同じ型として再宣言や定義ができる
+fn add_tag(
+    tags: &mut Vec<Tag>,
+    name: &str,
+    ctype: CType,
+    defined: bool,
+) -> Result<(), &'static str> {
+    if let Some(tag) = tags.iter_mut().find(|tag| tag.name == name) {
+        if tag.defined || tag.ctype != ctype {
+            return Err("すでに定義されています");
         }
-        _ => Err("構造体/共用体ではありません"),
+
+        tag.defined = defined;
+        return Ok(());
     }
+
+    tags.push(Tag {
+        name: name.to_string(),
+        ctype,
+        defined,
+    });
+    Ok(())
+}
+
+fn find_ident<'a>(idents: &'a [Ident], name: &str) -> Option<&'a Ident> {
+    idents.iter().find(|ident| ident.name() == name)
 }
 
 impl Scope {
     fn new() -> Self {
         Self {
             child: None,
-            lvars: Vec::new(),
+            idents: Vec::new(),
             tags: Vec::new(),
         }
     }
 
-    fn add_var(&mut self, name: &str, ctype: CType, offset: usize) -> Result<(), &str> {
+    fn add_ident(&mut self, ident: Ident) -> Result<(), &str> {
         if let Some(ref mut child) = self.child {
-            child.add_var(name, ctype, offset)
-        } else if self.find_current_var(name).is_some() {
-            Err("すでに定義されています")
+            child.add_ident(ident)
+        } else if let Some(defined) = find_ident(&self.idents, ident.name()) {
+            if defined.is_redefinable(&ident) {
+                Ok(())
+            } else {
+                Err("すでに定義されています")
+            }
         } else {
-            self.lvars.push(LVar {
-                name: name.to_string(),
-                ctype,
-                offset,
-            });
+            self.idents.push(ident);
 
             Ok(())
         }
     }
 
-    fn find_var(&self, name: &str) -> Option<NodeKind> {
+    fn find_ident(&self, name: &str) -> Option<&Ident> {
         if let Some(ref child) = self.child {
-            let lvar = child.find_var(name);
-            if lvar.is_some() {
-                return lvar;
+            let ident = child.find_ident(name);
+            if ident.is_some() {
+                return ident;
             }
         }
 
-        self.find_current_var(name)
-    }
-
-    fn find_current_var(&self, name: &str) -> Option<NodeKind> {
-        self.lvars
-            .iter()
-            .find(|v| v.name == name)
-            .map(|v| NodeKind::LVar(v.name.clone(), v.ctype.clone(), v.offset))
+        find_ident(&self.idents, name)
     }
 
-    fn add_tag(&mut self, name: &str, ctype: CType) -> Result<(), &str> {
+    fn add_tag(&mut self, name: &str, ctype: CType, defined: bool) -> Result<(), &str> {
         if let Some(ref mut child) = self.child {
-            child.add_tag(name, ctype)
-        } else if self.find_current_tag(name).is_some() {
-            Err("すでに定義されています")
+            child.add_tag(name, ctype, defined)
         } else {
-            self.tags.push(ctype);
-
-            Ok(())
+            add_tag(&mut self.tags, name, ctype, defined)
         }
     }
 
@@ -121,11 +221,19 @@ impl Scope {
             }
         }
 
-        self.find_current_tag(name)
+        find_tag(&self.tags, name)
     }
 
-    fn find_current_tag(&self, name: &str) -> Option<CType> {
-        find_tag(&self.tags, name)
+    // 最も内側のスコープのタグと定義済みかどうかを返す
+    fn find_current_tag(&self, name: &str) -> Option<(CType, bool)> {
+        if let Some(ref child) = self.child {
+            child.find_current_tag(name)
+        } else {
+            self.tags
+                .iter()
+                .find(|tag| tag.name == name)
+                .map(|tag| (tag.ctype.clone(), tag.defined))
+        }
     }
 
     fn enter(&mut self) {
@@ -165,8 +273,10 @@ impl Scope {
     #[allow(dead_code)]
     fn debug_print_lvars_impl(&self, depth: usize) {
         eprintln!("{}DEPTH={}", " ".repeat(depth), depth);
-        for lvar in self.lvars.iter() {
-            eprintln!("{}{} {}", " ".repeat(depth), &lvar.ctype, &lvar.name);
+        for ident in self.idents.iter() {
+            if let Ident::Var(lvar) = ident {
+                eprintln!("{}{} {}", " ".repeat(depth), &lvar.ctype, &lvar.name);
+            }
         }
 
         if let Some(ref child) = self.child {
@@ -178,22 +288,37 @@ impl Scope {
 #[derive(Clone)]
 pub struct Function {
     name: String,
+    // 関数の型
+    ctype: CType,
     stack_size: usize,
     scope: Scope,
 }
 
 impl Function {
-    fn new(name: &str) -> Self {
+    fn new(name: &str, ctype: CType) -> Self {
         Self {
             name: name.to_string(),
+            ctype,
             stack_size: 0,
             scope: Scope::new(),
         }
     }
 
+    fn ret_type(&self) -> &CType {
+        match &self.ctype {
+            CType::Function(ret_type, ..) => ret_type,
+            _ => unreachable!("関数の型ではありません"),
+        }
+    }
+
     fn add_var(&mut self, name: &str, ctype: CType) -> Result<(), &str> {
-        let offset = self.stack_size + ctype.size();
-        let result = self.scope.add_var(name, ctype, offset);
+        // 変数の先頭アドレス(rbp - offset)が型のアライメントに揃うようにする
+        let offset = align_to(self.stack_size + ctype.size(), ctype.alignof());
+        let result = self.scope.add_ident(Ident::Var(LVar {
+            name: name.to_string(),
+            ctype,
+            offset,
+        }));
 
         // 変数の追加に成功したらスタックサイズを更新する
         if result.is_ok() {
@@ -203,18 +328,26 @@ impl Function {
         result
     }
 
-    fn find_var(&self, name: &str) -> Option<NodeKind> {
-        self.scope.find_var(name)
+    fn add_ident(&mut self, ident: Ident) -> Result<(), &str> {
+        self.scope.add_ident(ident)
+    }
+
+    fn find_ident(&self, name: &str) -> Option<&Ident> {
+        self.scope.find_ident(name)
     }
 
-    fn add_tag(&mut self, name: &str, ctype: CType) -> Result<(), &str> {
-        self.scope.add_tag(name, ctype)
+    fn add_tag(&mut self, name: &str, ctype: CType, defined: bool) -> Result<(), &str> {
+        self.scope.add_tag(name, ctype, defined)
     }
 
     fn find_tag(&self, name: &str) -> Option<CType> {
         self.scope.find_tag(name)
     }
 
+    fn find_current_tag(&self, name: &str) -> Option<(CType, bool)> {
+        self.scope.find_current_tag(name)
+    }
+
     fn enter(&mut self) {
         self.scope.enter();
     }
@@ -233,7 +366,11 @@ impl Function {
 pub struct ParseContext {
     pub funcs: Vec<Function>,
     pub gvars: Vec<GVar>,
-    pub tags: Vec<CType>,
+    // 宣言された関数。定義された関数も含む。
+    fn_decls: Vec<FnDecl>,
+    // グローバル変数と関数以外の通常の識別子
+    idents: Vec<Ident>,
+    tags: Vec<Tag>,
     pub strs: Vec<Str>,
     current_fn: Option<String>,
     str_n: usize,
@@ -244,6 +381,8 @@ impl ParseContext {
         Self {
             funcs: Vec::new(),
             gvars: Vec::new(),
+            fn_decls: Vec::new(),
+            idents: Vec::new(),
             tags: Vec::new(),
             strs: Vec::new(),
             current_fn: None,
@@ -265,18 +404,72 @@ impl ParseContext {
         self.find_fn(name).map(|func| func.stack_size)
     }
 
-    pub fn enter_fn(&mut self, name: &str) -> Result<(), &str> {
+    pub fn enter_fn(&mut self, name: &str, ctype: CType, is_static: bool) -> Result<(), &str> {
         if self.current_fn.is_some() {
             return Err("関数内での関数定義です");
         }
 
-        if self.find_fn(name).is_none() && self.find_gvar(name).is_none() {
-            self.funcs.push(Function::new(name));
-            self.current_fn = Some(name.to_string());
-            Ok(())
-        } else {
-            Err("すでに定義されています")
+        if self.find_fn(name).is_some() {
+            return Err("すでに定義されています");
         }
+
+        self.declare_fn(name, ctype.clone(), is_static, None)?;
+        self.funcs.push(Function::new(name, ctype));
+        self.current_fn = Some(name.to_string());
+        Ok(())
+    }
+
+    // 関数の宣言を登録する。
+    // 同じ関数を複数回宣言する場合は型が両立していなければならない。
+    // asmラベルは後の宣言で追加できる。
+    pub fn declare_fn(
+        &mut self,
+        name: &str,
+        ctype: CType,
+        is_static: bool,
+        label: Option<String>,
+    ) -> Result<(), &'static str> {
+        if let Some(decl) = self.fn_decls.iter_mut().find(|decl| decl.name == name) {
+            decl.ctype = match decl.ctype.composite(&ctype) {
+                Some(ctype) => ctype,
+                None => return Err("以前の宣言と型が一致しません"),
+            };
+            // staticの後の宣言はstaticを省略できるが、その逆はできない
+            if is_static && !decl.is_static {
+                return Err("以前の宣言と記憶域クラスが一致しません");
+            }
+
+            return match (&decl.label, label) {
+                (Some(prev), Some(label)) if *prev != label => {
+                    Err("以前の宣言とasmラベルが一致しません")
+                }
+                (None, label @ Some(_)) => {
+                    decl.label = label;
+                    Ok(())
+                }
+                _ => Ok(()),
+            };
+        }
+
+        if self.is_global_defined(name) {
+            return Err("すでに定義されています");
+        }
+
+        self.fn_decls.push(FnDecl {
+            name: name.to_string(),
+            ctype,
+            label,
+            is_static,
+        });
+        Ok(())
+    }
+
+    // 関数のシンボル名とstaticかどうかを返す
+    pub fn fn_symbol(&self, name: &str) -> Option<(String, bool)> {
+        self.fn_decls
+            .iter()
+            .find(|decl| decl.name == name)
+            .map(|decl| (decl.symbol().to_string(), decl.is_static))
     }
 
     pub fn exit_fn(&mut self) -> Result<(), &str> {
@@ -288,6 +481,18 @@ impl ParseContext {
         Ok(())
     }
 
+    pub fn in_function(&self) -> bool {
+        self.current_fn.is_some()
+    }
+
+    // 定義中の関数の戻り値の型を返す
+    pub fn ret_type(&self) -> Option<&CType> {
+        match &self.current_fn {
+            Some(name) => self.find_fn(name).map(|func| func.ret_type()),
+            None => None,
+        }
+    }
+
     pub fn find_fn(&self, name: &str) -> Option<&Function> {
         self.funcs.iter().find(|f| f.name == name)
     }
@@ -302,59 +507,164 @@ impl ParseContext {
             let fn_name = self.current_fn.as_ref().unwrap().clone();
             let func = self.find_fn_mut(&fn_name).unwrap();
             func.add_var(name, ctype)
-        } else if self.find_gvar(name).is_some() || self.find_fn(name).is_some() {
-            Err("すでに定義されています")
         } else {
-            self.gvars.push(GVar {
-                name: name.to_string(),
-                ctype,
-                val: None,
-            });
-            Ok(())
+            self.add_gvar(name, ctype, false, false, None)
         }
     }
 
-    pub fn add_tag(&mut self, ctype: CType) -> Result<(), &str> {
-        let name = match get_tag(&ctype) {
-            Ok(Some(name)) => name,
-            // タグをつけていない構造体は後から
-            // 参照できないので登録せずにOkを返す。
-            Ok(_) => return Ok(()),
-            Err(msg) => return Err(msg),
-        };
+    // グローバル変数を登録する。
+    // externで宣言された変数は同じ型で再宣言したり定義したりできる。
+    pub fn add_gvar(
+        &mut self,
+        name: &str,
+        ctype: CType,
+        is_static: bool,
+        is_extern: bool,
+        label: Option<String>,
+    ) -> Result<(), &'static str> {
+        if let Some(gvar) = self.gvars.iter_mut().find(|v| v.name == name) {
+            // 要素数を省略した配列は後の宣言で要素数を決められる
+            match (&gvar.ctype, &ctype) {
+                (CType::Array(prev, 0), CType::Array(base, _)) if prev == base => {
+                    gvar.ctype = ctype
+                }
+                (CType::Array(prev, _), CType::Array(base, 0)) if prev == base => (),
+                (prev, ctype) if prev == ctype => (),
+                _ => return Err("以前の宣言と型が一致しません"),
+            }
+            if !gvar.is_extern && !is_extern {
+                return Err("すでに定義されています");
+            }
+            if is_static && !gvar.is_static {
+                return Err("以前の宣言と記憶域クラスが一致しません");
+            }
+            if let Some(label) = label {
+                if gvar.symbol != name && gvar.symbol != label {
+                    return Err("以前の宣言とasmラベルが一致しません");
+                }
+                gvar.symbol = label;
+            }
+
+            gvar.is_extern &= is_extern;
+            return Ok(());
+        }
+
+        if self.is_global_defined(name) {
+            return Err("すでに定義されています");
+        }
+
+        self.gvars.push(GVar {
+            name: name.to_string(),
+            symbol: label.unwrap_or_else(|| name.to_string()),
+            ctype,
+            is_static,
+            is_extern,
+            val: None,
+        });
+        Ok(())
+    }
 
+    pub fn add_const(&mut self, name: &str, val: isize) -> Result<(), &str> {
+        self.add_ident(Ident::Const(EnumConst {
+            name: name.to_string(),
+            val,
+        }))
+    }
+
+    pub fn add_typedef(&mut self, name: &str, ctype: CType) -> Result<(), &str> {
+        self.add_ident(Ident::Typedef(Typedef {
+            name: name.to_string(),
+            ctype,
+        }))
+    }
+
+    // 関数内ならば現在のスコープに、そうでなければグローバルに登録する
+    fn add_ident(&mut self, ident: Ident) -> Result<(), &str> {
         if self.current_fn.is_some() {
             let fn_name = self.current_fn.as_ref().unwrap().clone();
             let func = self.find_fn_mut(&fn_name).unwrap();
-            func.add_tag(&name.to_string(), ctype)
-        } else if self.find_tag(name).is_some() {
-            Err("すでに定義されています")
+            func.add_ident(ident)
+        } else if self.is_global_defined(ident.name()) {
+            match find_ident(&self.idents, ident.name()) {
+                Some(defined) if defined.is_redefinable(&ident) => Ok(()),
+                _ => Err("すでに定義されています"),
+            }
         } else {
-            self.tags.push(ctype);
+            self.idents.push(ident);
             Ok(())
         }
     }
 
-    pub fn add_str(&mut self, val: Vec<u8>) -> (String, CType) {
+    // グローバルな通常の識別子として定義済みならば真を返す
+    fn is_global_defined(&self, name: &str) -> bool {
+        self.find_gvar(name).is_some()
+            || self.find_fn_decl(name).is_some()
+            || find_ident(&self.idents, name).is_some()
+    }
+
+    // definedが偽ならば構造体/共用体を宣言のみする
+    pub fn add_tag(&mut self, name: &str, ctype: CType, defined: bool) -> Result<(), &str> {
+        if self.current_fn.is_some() {
+            let fn_name = self.current_fn.as_ref().unwrap().clone();
+            let func = self.find_fn_mut(&fn_name).unwrap();
+            func.add_tag(name, ctype, defined)
+        } else {
+            add_tag(&mut self.tags, name, ctype, defined)
+        }
+    }
+
+    // 現在のスコープで宣言されたタグと定義済みかどうかを返す
+    pub fn find_current_tag(&self, name: &str) -> Option<(CType, bool)> {
+        if let Some(ref fn_name) = self.current_fn {
+            let func = self.find_fn(fn_name).unwrap();
+            func.find_current_tag(name)
+        } else {
+            self.tags
+                .iter()
+                .find(|tag| tag.name == name)
+                .map(|tag| (tag.ctype.clone(), tag.defined))
+        }
+    }
+
+    pub fn add_str(&mut self, val: Vec<u8>, base: CType) -> (String, CType) {
         let label = format!(".L__String{}", self.str_n);
         let ret = label.clone();
-        let base = Box::new(CType::Integer(Integer::Char));
-        let ctype = CType::Array(base, val.len());
-        self.strs.push(Str { label, val });
+        let ctype = CType::Array(Box::new(base.clone()), val.len() / base.size());
+        self.strs.push(Str { label, val, base });
 
         self.str_n += 1;
 
         (ret, ctype)
     }
 
+    // 同名のtypedef名が内側のスコープにあれば変数は見つからない
     pub fn find_var(&self, name: &str) -> Option<NodeKind> {
-        self.find_lvar(name).or_else(|| self.find_gvar(name))
+        match self.find_ident(name) {
+            Some(ident) => ident.to_node_kind(),
+            None => self.find_gvar(name).or_else(|| self.find_fn_decl(name)),
+        }
     }
 
     pub fn find_lvar(&self, name: &str) -> Option<NodeKind> {
+        self.find_lident(name)
+            .and_then(|ident| ident.to_node_kind())
+    }
+
+    // 同名の変数が内側のスコープにあればtypedef名は見つからない
+    pub fn find_typedef(&self, name: &str) -> Option<CType> {
+        self.find_ident(name).and_then(|ident| ident.to_typedef())
+    }
+
+    // 最も内側のスコープで宣言された、グローバル変数と関数以外の通常の識別子を返す
+    fn find_ident(&self, name: &str) -> Option<&Ident> {
+        self.find_lident(name)
+            .or_else(|| find_ident(&self.idents, name))
+    }
+
+    fn find_lident(&self, name: &str) -> Option<&Ident> {
         if let Some(ref fn_name) = self.current_fn {
             let func = self.find_fn(fn_name).unwrap();
-            func.find_var(name)
+            func.find_ident(name)
         } else {
             None
         }
@@ -363,7 +673,15 @@ impl ParseContext {
         self.gvars
             .iter()
             .find(|v| v.name == name)
-            .map(|v| NodeKind::GVar(v.name.clone(), v.ctype.clone()))
+            .map(|v| NodeKind::GVar(v.symbol.clone(), v.ctype.clone()))
+    }
+
+    // 関数名は関数の型を持つグローバルなシンボルとして参照する
+    pub fn find_fn_decl(&self, name: &str) -> Option<NodeKind> {
+        self.fn_decls
+            .iter()
+            .find(|decl| decl.name == name)
+            .map(|decl| NodeKind::GVar(decl.symbol().to_string(), decl.ctype.clone()))
     }
 
     pub fn find_tag(&self, name: &str) -> Option<CType> {
@@ -415,7 +733,7 @@ impl ParseContext {
 
     #[allow(dead_code)]
     fn debug_print_lvars(&self) {
-        self.find_fn(&self.current_fn.as_ref().unwrap())
+        self.find_fn(self.current_fn.as_ref().unwrap())
             .unwrap()
             .debug_print_lvars();
     }
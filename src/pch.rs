@@ -0,0 +1,705 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::{Duration, UNIX_EPOCH};
+
+use super::preprocess::pch_include_dirs;
+use super::preprocess_context::{Macro, MacroKind, PreprocessContext};
+use super::src::Source;
+use super::tokenize::{
+    EmbedFile, Encoding, FloatType, IntType, LineMarker, Loc, Token, TokenCommon, TokenKind,
+};
+
+// プリコンパイル済みヘッダ(PCH)のファイル形式
+//
+// magic version
+// sources embed_files line_markers
+// before files tokens macros once_files pack pack_stack counter
+//
+// 整数はすべて8バイトのリトルエンディアン、文字列とバイト列は長さを前置する。
+// トークンが参照するソースなどは、ポインタの同一性を保つために表にまとめて
+// インデックスで参照する。
+const MAGIC: &[u8; 12] = b"SUMORUCC-PCH";
+const VERSION: u64 = 7;
+
+// コンパイルごとに値が変わるので、PCHの有効性の判定には使わないマクロ
+const VOLATILE_MACROS: &[&str] = &["__DATE__", "__TIME__"];
+
+// ヘッダを読み込む直前のプリプロセッサの状態。
+// インクルードする時点の状態と一致しなければPCHは使えない。
+pub struct PchState {
+    // -fgnu-compatが指定されているか
+    gnu_compat: bool,
+    // ソースのディレクトリ以外のインクルードファイルの探索ディレクトリ
+    include_dirs: Vec<String>,
+    // 事前定義マクロを含めて定義されているマクロ
+    macros: Vec<Macro>,
+    pack: Option<usize>,
+    pack_stack: Vec<Option<usize>>,
+    counter: usize,
+}
+
+impl PchState {
+    pub fn new(ctx: &PreprocessContext) -> Self {
+        Self {
+            gnu_compat: ctx.gnu_compat,
+            include_dirs: pch_include_dirs(),
+            macros: ctx.macros().to_vec(),
+            pack: ctx.pack,
+            pack_stack: ctx.pack_stack.clone(),
+            counter: ctx.counter,
+        }
+    }
+
+    fn matches(&self, ctx: &PreprocessContext) -> bool {
+        self.gnu_compat == ctx.gnu_compat
+            && self.include_dirs == pch_include_dirs()
+            && self.same_macros(ctx)
+            && self.pack == ctx.pack
+            && self.pack_stack == ctx.pack_stack
+            && self.counter == ctx.counter
+    }
+
+    fn same_macros(&self, ctx: &PreprocessContext) -> bool {
+        let stable = |m: &&Macro| !VOLATILE_MACROS.contains(&m.name.as_str());
+
+        let count = self.macros.iter().filter(stable).count();
+        if count != ctx.macros().iter().filter(stable).count() {
+            return false;
+        }
+
+        self.macros.iter().filter(stable).all(|m| {
+            let defined = ctx.find_macro(&m.name);
+            matches!(defined, Some(d) if same_macro_kind(&m.kind, &d.kind))
+        })
+    }
+}
+
+// ヘッダを読み込んだ結果としてPCHに保存する状態
+pub struct Pch {
+    // ヘッダを読み込む直前の状態
+    before: PchState,
+    // ヘッダとそこからインクルードや#embedで読み込んだファイル、およびその更新日時
+    files: Vec<(String, Duration)>,
+    // 末尾のEOFを除いたプリプロセス済みのトークン
+    pub token: Vec<Rc<Token>>,
+    // ヘッダで定義(Some)、もしくは#undef(None)されたマクロ
+    pub macros: Vec<(String, Option<MacroKind>)>,
+    // ヘッダ内で#pragma onceが指定されたファイル
+    pub once_files: Vec<PathBuf>,
+    // ヘッダを読み込んだ後の#pragma packと__COUNTER__の状態
+    pack: Option<usize>,
+    pack_stack: Vec<Option<usize>>,
+    counter: usize,
+}
+
+impl Pch {
+    // ヘッダのプリプロセス前後のマクロ表の差分からPCHに保存する状態を作る
+    pub fn new(token: Vec<Rc<Token>>, before: PchState, ctx: &PreprocessContext) -> Self {
+        let mut macros = Vec::new();
+
+        for m in ctx.macros().iter() {
+            let defined = before.macros.iter().find(|b| b.name == m.name);
+            if !matches!(defined, Some(b) if same_macro_kind(&b.kind, &m.kind)) {
+                macros.push((m.name.clone(), Some(m.kind.clone())));
+            }
+        }
+
+        for b in before.macros.iter() {
+            if ctx.find_macro(&b.name).is_none() {
+                macros.push((b.name.clone(), None));
+            }
+        }
+
+        // 更新日時が取得できないファイルは、読み込む際に必ず更新されたものとみなされる
+        let files = ctx
+            .files()
+            .iter()
+            .map(|path| (path.clone(), modified_time(path).unwrap_or_default()))
+            .collect();
+
+        Self {
+            before,
+            files,
+            token,
+            macros,
+            once_files: ctx.once_files().to_vec(),
+            pack: ctx.pack,
+            pack_stack: ctx.pack_stack.clone(),
+            counter: ctx.counter,
+        }
+    }
+
+    pub fn apply(&self, ctx: &mut PreprocessContext) {
+        for (name, kind) in self.macros.iter() {
+            match kind {
+                Some(kind) => ctx.define(name, kind.clone()),
+                None => ctx.undef(name),
+            }
+        }
+
+        for path in self.once_files.iter() {
+            ctx.add_once_file(path);
+        }
+
+        for (path, _) in self.files.iter() {
+            ctx.add_file(path);
+        }
+
+        ctx.pack = self.pack;
+        ctx.pack_stack = self.pack_stack.clone();
+        ctx.counter = self.counter;
+    }
+}
+
+fn same_macro_kind(a: &MacroKind, b: &MacroKind) -> bool {
+    match (a, b) {
+        (MacroKind::Object(a), MacroKind::Object(b)) => same_tokens(a, b),
+        (MacroKind::Function(a_params, a_va, a), MacroKind::Function(b_params, b_va, b)) => {
+            a_params == b_params && a_va == b_va && same_tokens(a, b)
+        }
+        (MacroKind::File, MacroKind::File) => true,
+        (MacroKind::Line, MacroKind::Line) => true,
+        (MacroKind::Counter, MacroKind::Counter) => true,
+        _ => false,
+    }
+}
+
+fn same_tokens(a: &[Rc<Token>], b: &[Rc<Token>]) -> bool {
+    a.len() == b.len()
+        && a.iter()
+            .zip(b.iter())
+            .all(|(a, b)| a.common.token_str == b.common.token_str)
+}
+
+// ファイルの更新日時をUNIXエポックからの経過時間で返す
+fn modified_time(path: &str) -> Option<Duration> {
+    let time = fs::metadata(path).ok()?.modified().ok()?;
+    time.duration_since(UNIX_EPOCH).ok()
+}
+
+// ヘッダに対応するPCHのパス
+pub fn pch_path(header: &str) -> String {
+    format!("{}.pch", header)
+}
+
+pub fn write_pch(path: &str, pch: &Pch) -> Result<(), ()> {
+    let mut writer = PchWriter::new();
+
+    writer.u8(pch.before.gnu_compat as u8);
+    writer.u64(pch.before.include_dirs.len() as u64);
+    for dir in pch.before.include_dirs.iter() {
+        writer.str(dir);
+    }
+    writer.u64(pch.before.macros.len() as u64);
+    for m in pch.before.macros.iter() {
+        writer.str(&m.name);
+        writer.macro_kind(Some(&m.kind));
+    }
+    writer.pack(pch.before.pack, &pch.before.pack_stack);
+    writer.u64(pch.before.counter as u64);
+
+    writer.u64(pch.files.len() as u64);
+    for (path, time) in pch.files.iter() {
+        writer.str(path);
+        writer.u64(time.as_secs());
+        writer.u64(time.subsec_nanos() as u64);
+    }
+
+    writer.u64(pch.token.len() as u64);
+    for token in pch.token.iter() {
+        writer.token(token);
+    }
+
+    writer.u64(pch.macros.len() as u64);
+    for (name, kind) in pch.macros.iter() {
+        writer.str(name);
+        writer.macro_kind(kind.as_ref());
+    }
+
+    writer.u64(pch.once_files.len() as u64);
+    for path in pch.once_files.iter() {
+        writer.str(path.to_str().unwrap());
+    }
+
+    writer.pack(pch.pack, &pch.pack_stack);
+    writer.u64(pch.counter as u64);
+
+    fs::write(path, writer.finish()).map_err(|_| ())
+}
+
+// ヘッダより新しく、現在の状態でヘッダを読み込んだ場合と同じ結果になる
+// PCHがあれば読み込む。
+// PCHが無い、古い、状態が異なる、作成後に読み込んだファイルが更新されている、
+// もしくは壊れている場合はNoneを返す。
+pub fn read_pch(header: &str, ctx: &PreprocessContext) -> Option<Pch> {
+    let path = pch_path(header);
+
+    let header_time = fs::metadata(header).ok()?.modified().ok()?;
+    let pch_time = fs::metadata(&path).ok()?.modified().ok()?;
+    if pch_time < header_time {
+        return None;
+    }
+
+    let buf = fs::read(&path).ok()?;
+    let mut reader = PchReader::new(&buf)?;
+
+    let gnu_compat = reader.u8()? != 0;
+    let mut include_dirs = Vec::new();
+    for _ in 0..reader.u64()? {
+        include_dirs.push(reader.str()?);
+    }
+    let mut before_macros = Vec::new();
+    for _ in 0..reader.u64()? {
+        let name = reader.str()?;
+        let kind = reader.macro_kind()??;
+        before_macros.push(Macro { name, kind });
+    }
+    let (pack, pack_stack) = reader.pack()?;
+    let before = PchState {
+        gnu_compat,
+        include_dirs,
+        macros: before_macros,
+        pack,
+        pack_stack,
+        counter: reader.u64()? as usize,
+    };
+    if !before.matches(ctx) {
+        return None;
+    }
+
+    let mut files = Vec::new();
+    for _ in 0..reader.u64()? {
+        let path = reader.str()?;
+        let secs = reader.u64()?;
+        let nanos = reader.u64()? as u32;
+        let time = Duration::new(secs, nanos);
+        if modified_time(&path) != Some(time) {
+            return None;
+        }
+        files.push((path, time));
+    }
+
+    let mut token = Vec::new();
+    for _ in 0..reader.u64()? {
+        token.push(reader.token()?);
+    }
+
+    let mut macros = Vec::new();
+    for _ in 0..reader.u64()? {
+        let name = reader.str()?;
+        let kind = reader.macro_kind()?;
+        macros.push((name, kind));
+    }
+
+    let mut once_files = Vec::new();
+    for _ in 0..reader.u64()? {
+        once_files.push(Path::new(&reader.str()?).to_path_buf());
+    }
+
+    let (pack, pack_stack) = reader.pack()?;
+    let counter = reader.u64()? as usize;
+
+    if !reader.at_end() {
+        return None;
+    }
+
+    Some(Pch {
+        before,
+        files,
+        token,
+        macros,
+        once_files,
+        pack,
+        pack_stack,
+        counter,
+    })
+}
+
+struct PchWriter {
+    // 表の後ろに続く本体
+    body: Vec<u8>,
+    sources: Vec<Rc<Source>>,
+    embed_files: Vec<Rc<EmbedFile>>,
+    line_markers: Vec<Rc<LineMarker>>,
+}
+
+impl PchWriter {
+    fn new() -> Self {
+        Self {
+            body: Vec::new(),
+            sources: Vec::new(),
+            embed_files: Vec::new(),
+            line_markers: Vec::new(),
+        }
+    }
+
+    fn u8(&mut self, n: u8) {
+        self.body.push(n);
+    }
+
+    fn u64(&mut self, n: u64) {
+        self.body.extend(n.to_le_bytes().iter());
+    }
+
+    fn bytes(&mut self, bytes: &[u8]) {
+        self.u64(bytes.len() as u64);
+        self.body.extend(bytes);
+    }
+
+    fn str(&mut self, s: &str) {
+        self.bytes(s.as_bytes());
+    }
+
+    fn opt_str(&mut self, s: &Option<String>) {
+        match s {
+            Some(s) => {
+                self.u8(1);
+                self.str(s);
+            }
+            None => self.u8(0),
+        }
+    }
+
+    // マクロの定義(Some)、もしくは#undef(None)
+    fn macro_kind(&mut self, kind: Option<&MacroKind>) {
+        match kind {
+            None => self.u8(0),
+            Some(MacroKind::Object(body)) => {
+                self.u8(1);
+                self.u64(body.len() as u64);
+                for token in body.iter() {
+                    self.token(token);
+                }
+            }
+            Some(MacroKind::File) => self.u8(2),
+            Some(MacroKind::Line) => self.u8(3),
+            Some(MacroKind::Counter) => self.u8(4),
+            Some(MacroKind::Function(params, variadic, body)) => {
+                self.u8(5);
+                self.u64(params.len() as u64);
+                for param in params.iter() {
+                    self.str(param);
+                }
+                self.u8(*variadic as u8);
+                self.u64(body.len() as u64);
+                for token in body.iter() {
+                    self.token(token);
+                }
+            }
+        }
+    }
+
+    // 指定なし(None)を0とし、アライメントは1を足して区別する
+    fn pack_alignment(&mut self, pack: Option<usize>) {
+        self.u64(pack.map_or(0, |n| n as u64 + 1));
+    }
+
+    // #pragma packのアライメントと退避したアライメント
+    fn pack(&mut self, pack: Option<usize>, pack_stack: &[Option<usize>]) {
+        self.pack_alignment(pack);
+        self.u64(pack_stack.len() as u64);
+        for pack in pack_stack.iter() {
+            self.pack_alignment(*pack);
+        }
+    }
+
+    // 同じ要素が表になければ追加し、インデックスを返す
+    fn index_of<T>(table: &mut Vec<Rc<T>>, elem: &Rc<T>) -> u64 {
+        match table.iter().position(|e| Rc::ptr_eq(e, elem)) {
+            Some(i) => i as u64,
+            None => {
+                table.push(Rc::clone(elem));
+                (table.len() - 1) as u64
+            }
+        }
+    }
+
+    fn token(&mut self, token: &Token) {
+        let common = &token.common;
+
+        self.str(&common.token_str);
+        let src = Self::index_of(&mut self.sources, &common.src);
+        self.u64(src);
+        self.u64(common.loc.row as u64);
+        self.u64(common.loc.col as u64);
+        match &common.line_marker {
+            Some(marker) => {
+                let marker = Self::index_of(&mut self.line_markers, marker);
+                self.u64(marker + 1);
+            }
+            None => self.u64(0),
+        }
+
+        match &token.kind {
+            TokenKind::Punctuator => self.u8(0),
+            TokenKind::Ident => self.u8(1),
+            TokenKind::Keyword => self.u8(2),
+            TokenKind::Num(n, ctype) => {
+                self.u8(3);
+                self.u64(*n as u64);
+                self.u8(*ctype as u8);
+            }
+            TokenKind::Float(f, ctype) => {
+                self.u8(9);
+                self.u64(f.to_bits());
+                self.u8(*ctype as u8);
+            }
+            TokenKind::Str(s, enc) => {
+                self.u8(4);
+                self.bytes(s);
+                self.u8(*enc as u8);
+            }
+            TokenKind::EmbedByte(b, file, offset) => {
+                self.u8(5);
+                self.u8(*b);
+                let file = Self::index_of(&mut self.embed_files, file);
+                self.u64(file);
+                self.u64(*offset as u64);
+            }
+            TokenKind::Pack(pack) => {
+                self.u8(6);
+                self.pack_alignment(*pack);
+            }
+            TokenKind::PPNumber => self.u8(10),
+            TokenKind::LF => self.u8(7),
+            TokenKind::EOF => self.u8(8),
+        }
+    }
+
+    // ヘッダと表を本体の前に置いたバイト列を返す
+    fn finish(self) -> Vec<u8> {
+        let body = self.body;
+        let mut writer = PchWriter::new();
+
+        writer.body.extend(MAGIC.iter());
+        writer.u64(VERSION);
+
+        writer.u64(self.sources.len() as u64);
+        for src in self.sources.iter() {
+            writer.opt_str(&src.path);
+            writer.str(&src.code);
+        }
+
+        writer.u64(self.embed_files.len() as u64);
+        for file in self.embed_files.iter() {
+            writer.str(&file.path);
+        }
+
+        writer.u64(self.line_markers.len() as u64);
+        for marker in self.line_markers.iter() {
+            writer.opt_str(&marker.path);
+            writer.u64(marker.row_delta as u64);
+        }
+
+        writer.body.extend(body);
+        writer.body
+    }
+}
+
+struct PchReader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+    sources: Vec<Rc<Source>>,
+    embed_files: Vec<Rc<EmbedFile>>,
+    line_markers: Vec<Rc<LineMarker>>,
+}
+
+impl<'a> PchReader<'a> {
+    // ヘッダと表を読み込み、本体の先頭を指すリーダーを返す
+    fn new(buf: &'a [u8]) -> Option<Self> {
+        let mut reader = Self {
+            buf,
+            pos: 0,
+            sources: Vec::new(),
+            embed_files: Vec::new(),
+            line_markers: Vec::new(),
+        };
+
+        if reader.take(MAGIC.len())? != MAGIC || reader.u64()? != VERSION {
+            return None;
+        }
+
+        for _ in 0..reader.u64()? {
+            let path = reader.opt_str()?;
+            let code = reader.str()?;
+            reader.sources.push(Rc::new(Source { path, code }));
+        }
+
+        for _ in 0..reader.u64()? {
+            let path = reader.str()?;
+            reader.embed_files.push(Rc::new(EmbedFile { path }));
+        }
+
+        for _ in 0..reader.u64()? {
+            let path = reader.opt_str()?;
+            let row_delta = reader.u64()? as isize;
+            reader
+                .line_markers
+                .push(Rc::new(LineMarker { path, row_delta }));
+        }
+
+        Some(reader)
+    }
+
+    fn at_end(&self) -> bool {
+        self.pos == self.buf.len()
+    }
+
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        let end = self.pos.checked_add(len)?;
+        let bytes = self.buf.get(self.pos..end)?;
+        self.pos = end;
+        Some(bytes)
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        Some(self.take(1)?[0])
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(self.take(8)?);
+        Some(u64::from_le_bytes(bytes))
+    }
+
+    fn bytes(&mut self) -> Option<Vec<u8>> {
+        let len = self.u64()? as usize;
+        Some(self.take(len)?.to_vec())
+    }
+
+    fn str(&mut self) -> Option<String> {
+        String::from_utf8(self.bytes()?).ok()
+    }
+
+    fn opt_str(&mut self) -> Option<Option<String>> {
+        match self.u8()? {
+            0 => Some(None),
+            1 => Some(Some(self.str()?)),
+            _ => None,
+        }
+    }
+
+    fn macro_kind(&mut self) -> Option<Option<MacroKind>> {
+        let kind = match self.u8()? {
+            0 => None,
+            1 => {
+                let mut body = Vec::new();
+                for _ in 0..self.u64()? {
+                    body.push(self.token()?);
+                }
+                Some(MacroKind::Object(body))
+            }
+            2 => Some(MacroKind::File),
+            3 => Some(MacroKind::Line),
+            4 => Some(MacroKind::Counter),
+            5 => {
+                let mut params = Vec::new();
+                for _ in 0..self.u64()? {
+                    params.push(self.str()?);
+                }
+                let variadic = self.u8()? != 0;
+                let mut body = Vec::new();
+                for _ in 0..self.u64()? {
+                    body.push(self.token()?);
+                }
+                Some(MacroKind::Function(params, variadic, body))
+            }
+            _ => return None,
+        };
+
+        Some(kind)
+    }
+
+    fn pack_alignment(&mut self) -> Option<Option<usize>> {
+        match self.u64()? as usize {
+            0 => Some(None),
+            n => Some(Some(n - 1)),
+        }
+    }
+
+    fn pack(&mut self) -> Option<(Option<usize>, Vec<Option<usize>>)> {
+        let pack = self.pack_alignment()?;
+        let mut pack_stack = Vec::new();
+        for _ in 0..self.u64()? {
+            pack_stack.push(self.pack_alignment()?);
+        }
+
+        Some((pack, pack_stack))
+    }
+
+    fn token(&mut self) -> Option<Rc<Token>> {
+        let token_str = self.str()?;
+        let src = self.u64()? as usize;
+        let src = Rc::clone(self.sources.get(src)?);
+        let row = self.u64()? as usize;
+        let col = self.u64()? as usize;
+        let line_marker = match self.u64()? as usize {
+            0 => None,
+            i => Some(Rc::clone(self.line_markers.get(i - 1)?)),
+        };
+
+        let kind = match self.u8()? {
+            0 => TokenKind::Punctuator,
+            1 => TokenKind::Ident,
+            2 => TokenKind::Keyword,
+            3 => {
+                let n = self.u64()? as isize;
+                let ctype = match self.u8()? {
+                    0 => IntType::Int,
+                    1 => IntType::UInt,
+                    2 => IntType::Long,
+                    3 => IntType::ULong,
+                    4 => IntType::LongLong,
+                    5 => IntType::ULongLong,
+                    _ => return None,
+                };
+                TokenKind::Num(n, ctype)
+            }
+            4 => {
+                let s = self.bytes()?;
+                let enc = match self.u8()? {
+                    0 => Encoding::Char,
+                    1 => Encoding::Utf8,
+                    2 => Encoding::Utf16,
+                    3 => Encoding::Utf32,
+                    4 => Encoding::Wide,
+                    _ => return None,
+                };
+                TokenKind::Str(s, enc)
+            }
+            5 => {
+                let b = self.u8()?;
+                let file = self.u64()? as usize;
+                let file = Rc::clone(self.embed_files.get(file)?);
+                let offset = self.u64()? as usize;
+                TokenKind::EmbedByte(b, file, offset)
+            }
+            6 => TokenKind::Pack(self.pack_alignment()?),
+            10 => TokenKind::PPNumber,
+            7 => TokenKind::LF,
+            8 => TokenKind::EOF,
+            9 => {
+                let f = f64::from_bits(self.u64()?);
+                let ctype = match self.u8()? {
+                    0 => FloatType::Float,
+                    1 => FloatType::Double,
+                    2 => FloatType::LongDouble,
+                    _ => return None,
+                };
+                TokenKind::Float(f, ctype)
+            }
+            _ => return None,
+        };
+
+        Some(Rc::new(Token {
+            common: TokenCommon {
+                token_str: Rc::from(token_str),
+                src,
+                loc: Loc { row, col },
+                line_marker,
+            },
+            kind,
+        }))
+    }
+}
@@ -1,9 +1,19 @@
+use std::fs;
 use std::path::Path;
 use std::rc::Rc;
 
+use super::ctype::{CType, Floating, Integer};
 use super::get_preprocessed_token;
+use super::parse::const_expr;
+use super::pch::read_pch;
+use super::preprocess_context::{MacroKind, PreprocessContext};
+use super::src::Source;
 use super::token_stream::TokenStream;
-use super::tokenize::Token;
+use super::tokenize::{
+    read_number, reencode_string, tokenize, EmbedFile, Encoding, IntType, LineMarker, Token,
+    TokenCommon, TokenKind,
+};
+use super::util::current_datetime;
 
 fn find_include_file(name: &str, search_dirs: &[String]) -> Result<String, ()> {
     let path = Path::new(name);
@@ -25,79 +35,1576 @@ fn find_include_file(name: &str, search_dirs: &[String]) -> Result<String, ()> {
     Err(())
 }
 
-pub fn preprocess(token: &[Rc<Token>]) -> Vec<Rc<Token>> {
+// __has_builtinで真を返す組み込み関数
+const BUILTINS: [&str; 3] = [
+    "__builtin_bswap16",
+    "__builtin_bswap32",
+    "__builtin_bswap64",
+];
+
+// __has_attributeで真を返す属性。
+// -fgnu-compatで読み捨てても意味が変わらないものに限る。
+// alignedやmodeのように型を変えるものや、gnu_inlineのように
+// 関数を出力するかどうかが変わるものは含めない。
+const ATTRIBUTES: [&str; 22] = [
+    "access",
+    "alloc_size",
+    "always_inline",
+    "artificial",
+    "cold",
+    "const",
+    "deprecated",
+    "fallthrough",
+    "format",
+    "format_arg",
+    "hot",
+    "leaf",
+    "malloc",
+    "noinline",
+    "nonnull",
+    "nonstring",
+    "noreturn",
+    "nothrow",
+    "pure",
+    "returns_nonnull",
+    "sentinel",
+    "unused",
+];
+
+// __has_featureで真を返す機能。いずれも-fgnu-compatで属性を読み捨てることで受け付ける。
+const FEATURES: [&str; 3] = [
+    "attribute_deprecated_with_message",
+    "attribute_unused_on_fields",
+    "enumerator_attributes",
+];
+
+// #ifの条件式で使える、definedで真となる演算子
+const HAS_OPERATORS: [&str; 4] = [
+    "__has_include",
+    "__has_builtin",
+    "__has_attribute",
+    "__has_feature",
+];
+
+// システムヘッダの探索パス。GCC_INCLUDE_DIRの後に探索する。
+const SYSTEM_INCLUDE_DIRS: [&str; 3] = [
+    "/usr/local/include",
+    "/usr/include/x86_64-linux-gnu",
+    "/usr/include",
+];
+
+// GCCの内部ヘッダ(stddef.h, stdarg.hなど)があるディレクトリの親。
+// その下のバージョン名のディレクトリにincludeがある。
+const GCC_LIB_DIR: &str = "/usr/lib/gcc/x86_64-linux-gnu";
+
+// 最も新しいバージョンのGCCの内部ヘッダのディレクトリを返す
+fn gcc_include_dir() -> Option<String> {
+    let version =
+        |name: &str| -> Vec<usize> { name.split('.').map(|n| n.parse().unwrap_or(0)).collect() };
+
+    fs::read_dir(GCC_LIB_DIR)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().join("include").is_dir())
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .max_by_key(|name| version(name))
+        .map(|name| format!("{}/{}/include", GCC_LIB_DIR, name))
+}
+
+// ソースが格納されているディレクトリとカレントディレクトリ、
+// システムヘッダのディレクトリの順にインクルードファイルを探索する。
+fn include_search_dirs(token: &Rc<Token>) -> Vec<String> {
+    let mut search_dirs = Vec::new();
+
+    if let Some(src) = &token.common.src.path {
+        let src = Path::new(src);
+        if let Some(src_dir) = src.parent() {
+            search_dirs.push(src_dir.to_str().unwrap().to_string());
+        }
+    }
+
+    search_dirs.extend(common_include_dirs());
+
+    search_dirs
+}
+
+// #include_nextでは、現在のファイルが見つかった探索ディレクトリより後ろから探す。
+// GCCのstdint.hのように、同名のシステムヘッダをさらに読み込むヘッダで使われる。
+// 現在のファイルがどの探索ディレクトリにも無ければ#includeと同じように探す。
+fn include_next_search_dirs(token: &Rc<Token>) -> Vec<String> {
+    let src = match &token.common.src.path {
+        Some(src) => fs::canonicalize(src).ok(),
+        None => None,
+    };
+    let search_dirs = common_include_dirs();
+
+    // ディレクトリが入れ子になっている場合は最も深いディレクトリで見つかったものとする
+    let found = search_dirs
+        .iter()
+        .enumerate()
+        .filter_map(|(i, dir)| Some((i, fs::canonicalize(dir).ok()?)))
+        .filter(|(_, dir)| matches!(&src, Some(src) if src.starts_with(dir)))
+        .max_by_key(|(_, dir)| dir.components().count());
+
+    match found {
+        Some((i, _)) => search_dirs[i + 1..].to_vec(),
+        None => include_search_dirs(token),
+    }
+}
+
+// ソースのディレクトリ以外の、どのソースでも共通の探索ディレクトリ
+fn common_include_dirs() -> Vec<String> {
+    let mut search_dirs = vec![".".to_string()];
+    search_dirs.extend(gcc_include_dir());
+    search_dirs.extend(SYSTEM_INCLUDE_DIRS.iter().map(|dir| dir.to_string()));
+
+    search_dirs
+}
+
+// PCHの有効性の判定に使う共通の探索ディレクトリ。
+// カレントディレクトリが異なれば別のディレクトリになるので絶対パスにする。
+pub fn pch_include_dirs() -> Vec<String> {
+    common_include_dirs()
+        .into_iter()
+        .map(|dir| match fs::canonicalize(&dir) {
+            Ok(path) => path.to_string_lossy().into_owned(),
+            Err(_) => dir,
+        })
+        .collect()
+}
+
+// 処理系があらかじめ定義するマクロを登録する
+pub fn define_predefined_macros(ctx: &mut PreprocessContext) {
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+
+    let (year, month, day, hour, min, sec) = current_datetime();
+    let char_size = CType::Integer(Integer::Char).size();
+    let short_size = CType::Integer(Integer::Short).size();
+    let int_size = CType::Integer(Integer::Int).size();
+    let long_size = CType::Integer(Integer::Long).size();
+    let long_long_size = CType::Integer(Integer::LongLong).size();
+    let long_double_size = CType::Floating(Floating::LongDouble).size();
+    let ptr_size = CType::Pointer(Box::new(CType::Integer(Integer::Char))).size();
+
+    let mut code = String::new();
+
+    // 規格で定められたマクロ
+    code.push_str("#define __STDC__ 1\n");
+    code.push_str("#define __STDC_VERSION__ 201112L\n");
+    code.push_str("#define __STDC_HOSTED__ 1\n");
+    code.push_str(&format!(
+        "#define __DATE__ \"{} {:>2} {}\"\n",
+        MONTHS[month - 1],
+        day,
+        year
+    ));
+    code.push_str(&format!(
+        "#define __TIME__ \"{:02}:{:02}:{:02}\"\n",
+        hour, min, sec
+    ));
+
+    // ターゲットに関するマクロ
+    for name in [
+        "__x86_64__",
+        "__x86_64",
+        "__amd64__",
+        "__amd64",
+        "__LP64__",
+        "_LP64",
+        "__linux__",
+        "__linux",
+        "__gnu_linux__",
+        "__unix__",
+        "__unix",
+        "__ELF__",
+        "__sumorucc__",
+    ]
+    .iter()
+    {
+        code.push_str(&format!("#define {} 1\n", name));
+    }
+
+    code.push_str(&format!("#define __CHAR_BIT__ {}\n", char_size * 8));
+    code.push_str(&format!("#define __SIZEOF_SHORT__ {}\n", short_size));
+    code.push_str(&format!("#define __SIZEOF_INT__ {}\n", int_size));
+    code.push_str(&format!("#define __SIZEOF_LONG__ {}\n", long_size));
+    code.push_str(&format!(
+        "#define __SIZEOF_LONG_LONG__ {}\n",
+        long_long_size
+    ));
+    code.push_str(&format!(
+        "#define __SIZEOF_LONG_DOUBLE__ {}\n",
+        long_double_size
+    ));
+    code.push_str(&format!("#define __SIZEOF_POINTER__ {}\n", ptr_size));
+    // 符号付き整数型の最大値とビット幅。GCCのlimits.hはこれらからINT_MAXなどを定義する。
+    for (name, size, suffix) in [
+        ("SCHAR", char_size, ""),
+        ("SHRT", short_size, ""),
+        ("INT", int_size, ""),
+        ("LONG", long_size, "L"),
+        ("LONG_LONG", long_long_size, "LL"),
+    ]
+    .iter()
+    {
+        let width = size * 8;
+        code.push_str(&format!(
+            "#define __{}_MAX__ {:#x}{}\n",
+            name,
+            u64::MAX >> (65 - width),
+            suffix
+        ));
+        code.push_str(&format!("#define __{}_WIDTH__ {}\n", name, width));
+    }
+    // アセンブラのシンボル名にはCの識別子をそのまま使う
+    code.push_str("#define __USER_LABEL_PREFIX__\n");
+
+    // -fgnu-compat指定時はシステムヘッダがGNU拡張を使う経路を選ぶようにGCC 12を名乗る
+    if ctx.gnu_compat {
+        code.push_str("#define __GNUC__ 12\n");
+        code.push_str("#define __GNUC_MINOR__ 0\n");
+        code.push_str("#define __GNUC_PATCHLEVEL__ 0\n");
+    }
+
+    let src = Source {
+        path: Some("<built-in>".to_string()),
+        code,
+    };
+
+    preprocess(&tokenize(Rc::new(src)), ctx);
+}
+
+// -dM -E 指定時に定義済みのマクロを出力する
+pub fn print_macros(ctx: &PreprocessContext) {
+    for m in ctx.macros().iter() {
+        // 展開箇所によって値が変わるマクロは出力しない
+        match &m.kind {
+            MacroKind::Object(body) => println!("#define {} {}", m.name, join_tokens(body)),
+            MacroKind::Function(params, variadic, body) => {
+                let mut params = params.clone();
+                if *variadic {
+                    *params.last_mut().unwrap() = "...".to_string();
+                }
+                println!(
+                    "#define {}({}) {}",
+                    m.name,
+                    params.join(", "),
+                    join_tokens(body)
+                );
+            }
+            _ => (),
+        }
+    }
+}
+
+// -fgnu-compatで読み捨てる修飾子などのキーワード
+const GNU_IGNORED_KEYWORDS: [&str; 8] = [
+    "__extension__",
+    "__restrict",
+    "__restrict__",
+    "__inline",
+    "__inline__",
+    "__const",
+    "__volatile",
+    "__volatile__",
+];
+
+// -fgnu-compatで括弧で囲まれた引数ごと読み捨てるキーワード
+const GNU_IGNORED_SPECIFIERS: [&str; 2] = ["__attribute__", "__attribute"];
+
+// -fgnu-compat指定時に、コード生成に影響しないGNU拡張を取り除く
+pub fn strip_gnu_extensions(token: &[Rc<Token>]) -> Vec<Rc<Token>> {
+    let mut stream = TokenStream::new(token);
+    let mut stripped = Vec::new();
+
+    while let Some(token) = stream.next() {
+        if token.kind != TokenKind::Ident {
+            stripped.push(token);
+            continue;
+        }
+
+        let name = token.common.token_str.as_ref();
+        if GNU_IGNORED_KEYWORDS.contains(&name) {
+            continue;
+        }
+
+        // __attribute__((...))
+        if GNU_IGNORED_SPECIFIERS.contains(&name) {
+            let open = stream.expect_punctuator("(");
+            read_balanced_tokens(&mut stream, &open);
+            continue;
+        }
+
+        stripped.push(token);
+    }
+
+    stripped
+}
+
+// 隣接する文字列リテラルを1つの文字列リテラルに連結する(翻訳フェーズ6)
+pub fn join_adjacent_strings(token: &[Rc<Token>]) -> Vec<Rc<Token>> {
+    let mut joined = Vec::new();
+    let mut i = 0;
+
+    while i < token.len() {
+        let len = token[i..]
+            .iter()
+            .take_while(|t| matches!(t.kind, TokenKind::Str(..)))
+            .count();
+
+        if len < 2 {
+            joined.push(Rc::clone(&token[i]));
+            i += 1;
+            continue;
+        }
+
+        let run = &token[i..i + len];
+        i += len;
+
+        // 接頭辞のある文字列リテラルがあれば、その符号化方式に揃える
+        let mut enc = Encoding::Char;
+        for t in run.iter() {
+            if let TokenKind::Str(_, e) = t.kind {
+                if e != Encoding::Char {
+                    if enc != Encoding::Char && enc != e {
+                        error_tok!(t, "接頭辞の異なる文字列リテラルは連結できません");
+                    }
+                    enc = e;
+                }
+            }
+        }
+
+        let mut bytes = Vec::new();
+        for t in run.iter() {
+            let s = match &t.kind {
+                TokenKind::Str(s, e) if *e == enc => s.clone(),
+                _ => reencode_string(&t.common.token_str, enc),
+            };
+            // 終端のnulを取り除いて連結する
+            bytes.extend(&s[..s.len() - enc.unit_size()]);
+        }
+        bytes.extend(vec![0; enc.unit_size()]);
+
+        let token_str: Vec<&str> = run.iter().map(|t| t.common.token_str.as_ref()).collect();
+        joined.push(new_token(
+            &run[0],
+            token_str.join(" "),
+            TokenKind::Str(bytes, enc),
+        ));
+    }
+
+    joined
+}
+
+// -E 指定時にプリプロセス結果を出力する
+pub fn print_preprocessed(token: &[Rc<Token>]) {
+    let mut prev: Option<&Rc<Token>> = None;
+
+    for t in token.iter() {
+        if t.kind == TokenKind::EOF {
+            break;
+        }
+
+        // 元のソースで行が変わっていれば改行する
+        if let Some(prev) = prev {
+            if Rc::ptr_eq(&prev.common.src, &t.common.src)
+                && prev.common.loc.row == t.common.loc.row
+            {
+                print!(" ");
+            } else {
+                println!();
+            }
+        }
+
+        print!("{}", t.common.token_str);
+        prev = Some(t);
+    }
+
+    println!();
+}
+
+pub fn preprocess(token: &[Rc<Token>], ctx: &mut PreprocessContext) -> Vec<Rc<Token>> {
     let mut stream = TokenStream::new(token);
     let mut preprocessed = Vec::new();
 
-    preprocessing_file(&mut stream, &mut preprocessed);
+    preprocessing_file(&mut stream, &mut preprocessed, ctx);
 
     preprocessed
 }
 
+#[derive(PartialEq)]
+enum CondCtx {
+    Then,
+    Elif,
+    Else,
+}
+
+// #if, #ifdef, #ifndefで始まる条件付き取り込みの状態
+struct CondIncl {
+    // 処理中の節
+    ctx: CondCtx,
+    // 条件付き取り込みを開始したディレクティブのトークン
+    token: Rc<Token>,
+    // いずれかの節がすでに取り込まれたか
+    included: bool,
+}
+
 // preprocessing_file := ("#" directive | text_line)*
-fn preprocessing_file(stream: &mut TokenStream, preprocessed: &mut Vec<Rc<Token>>) {
+fn preprocessing_file(
+    stream: &mut TokenStream,
+    preprocessed: &mut Vec<Rc<Token>>,
+    ctx: &mut PreprocessContext,
+) {
+    let mut cond_stack = Vec::new();
+
     while !stream.at_eof() {
         if stream.consume_punctuator("#").is_some() {
-            directive(stream, preprocessed);
+            directive(stream, preprocessed, ctx, &mut cond_stack);
         } else {
-            text_line(stream, preprocessed);
+            text_line(stream, preprocessed, ctx);
         }
     }
 
+    if let Some(cond) = cond_stack.last() {
+        error_tok!(cond.token, "対応する#endifがありません");
+    }
+
     // 末尾にEOFをつける
     preprocessed.push(stream.next().unwrap());
 }
 
-// directive := "include" str LF | LF
-fn directive(stream: &mut TokenStream, preprocessed: &mut Vec<Rc<Token>>) {
-    if let Some((token, directive)) = stream.consume_identifier() {
-        if directive != "include" {
-            error_tok!(token, "無効なディレクティブです");
+// directive := "include" include_path LF
+//            | "include_next" include_path LF
+//            | "define" ident replacement_list
+//            | "undef" ident LF
+//            | "line" num str? LF
+//            | "embed" include_path embed_param* LF
+//            | "pragma" pragma
+//            | "error" [^LF]* LF
+//            | "warning" [^LF]* LF
+//            | "if" const_expr LF
+//            | "ifdef" ident LF
+//            | "ifndef" ident LF
+//            | "elif" const_expr LF
+//            | "else" LF
+//            | "endif" LF
+//            | LF
+fn directive(
+    stream: &mut TokenStream,
+    preprocessed: &mut Vec<Rc<Token>>,
+    ctx: &mut PreprocessContext,
+    cond_stack: &mut Vec<CondIncl>,
+) {
+    if let Some((token, directive)) = directive_name(stream) {
+        match directive.as_str() {
+            "include" => {
+                let search_dirs = include_search_dirs(&token);
+                include(stream, preprocessed, ctx, &token, &search_dirs);
+            }
+            "include_next" => {
+                let search_dirs = include_next_search_dirs(&token);
+                include(stream, preprocessed, ctx, &token, &search_dirs);
+            }
+            "define" => define(stream, ctx),
+            "undef" => undef(stream, ctx),
+            "line" => line(stream, ctx),
+            "embed" => embed(stream, preprocessed, ctx, &token),
+            "pragma" => pragma(stream, preprocessed, ctx, &token),
+            "error" => {
+                let msg = join_tokens(&read_line(stream).0);
+                error_tok!(token, "#error {}", msg);
+            }
+            "warning" => {
+                let msg = join_tokens(&read_line(stream).0);
+                warn_tok!(token, "#warning {}", msg);
+            }
+            "if" => {
+                let included = read_const_expr(stream, ctx) != 0;
+                start_cond_incl(stream, cond_stack, token, included);
+            }
+            "ifdef" | "ifndef" => {
+                let (_, name) = stream.expect_macro_name();
+                stream.expect_lf();
+
+                let defined = is_defined(ctx, &name);
+                let included = if directive == "ifdef" {
+                    defined
+                } else {
+                    !defined
+                };
+                start_cond_incl(stream, cond_stack, token, included);
+            }
+            "elif" => {
+                let cond = current_cond_incl(cond_stack, &token);
+                cond.ctx = CondCtx::Elif;
+
+                // すでに取り込んだ節があれば条件式は評価しない
+                if !cond.included && read_const_expr(stream, ctx) != 0 {
+                    cond.included = true;
+                } else {
+                    skip_cond_incl(stream);
+                }
+            }
+            "else" => {
+                let cond = current_cond_incl(cond_stack, &token);
+                cond.ctx = CondCtx::Else;
+                stream.expect_lf();
+
+                if cond.included {
+                    skip_cond_incl(stream);
+                }
+            }
+            "endif" => {
+                if cond_stack.pop().is_none() {
+                    error_tok!(token, "対応する#ifがありません");
+                }
+                stream.expect_lf();
+            }
+            _ => {
+                error_tok!(token, "無効なディレクティブです");
+            }
         }
+    } else {
+        stream.expect_lf();
+    }
+}
+
+// "if"と"else"はキーワードとしてトークナイズされるので識別子と同様に扱う
+fn directive_name(stream: &mut TokenStream) -> Option<(Rc<Token>, String)> {
+    if let Some(ret) = stream.consume_identifier() {
+        return Some(ret);
+    }
+
+    for keyword in ["if", "else"].iter() {
+        if let Some(token) = stream.consume_keyword(keyword) {
+            return Some((token, keyword.to_string()));
+        }
+    }
+
+    None
+}
+
+fn start_cond_incl(
+    stream: &mut TokenStream,
+    cond_stack: &mut Vec<CondIncl>,
+    token: Rc<Token>,
+    included: bool,
+) {
+    cond_stack.push(CondIncl {
+        ctx: CondCtx::Then,
+        token,
+        included,
+    });
+
+    if !included {
+        skip_cond_incl(stream);
+    }
+}
+
+fn current_cond_incl<'a>(cond_stack: &'a mut [CondIncl], token: &Rc<Token>) -> &'a mut CondIncl {
+    match cond_stack.last_mut() {
+        Some(cond) if cond.ctx == CondCtx::Else => {
+            error_tok!(token, "#elseの後に#{}があります", token.common.token_str);
+        }
+        Some(cond) => cond,
+        None => {
+            error_tok!(token, "対応する#ifがありません");
+        }
+    }
+}
 
-        let (path_token, path) = stream.expect_string();
+// 取り込まない節を読み飛ばす。同じ深さの#elif, #else, #endifの
+// 直前で止まるので、それらのディレクティブは通常通り処理される。
+fn skip_cond_incl(stream: &mut TokenStream) {
+    let mut depth = 0;
+
+    while !stream.at_eof() {
+        let state = stream.save();
+
+        if stream.consume_punctuator("#").is_some() {
+            if let Some((_, name)) = directive_name(stream) {
+                match name.as_str() {
+                    "if" | "ifdef" | "ifndef" => depth += 1,
+                    "elif" | "else" | "endif" if depth == 0 => {
+                        stream.restore(state);
+                        return;
+                    }
+                    "endif" => depth -= 1,
+                    _ => (),
+                }
+            }
+        }
 
+        read_line(stream);
+    }
+}
+
+// #if, #elifの条件式を読み評価する
+fn read_const_expr(stream: &mut TokenStream, ctx: &mut PreprocessContext) -> isize {
+    let (line_token, lf) = read_line(stream);
+    eval_const_expr(line_token, &lf, ctx)
+}
+
+// tokenを条件式として評価する。end_tokenは式の終わりの位置を示すのに使う。
+fn eval_const_expr(
+    line_token: Vec<Rc<Token>>,
+    end_token: &Rc<Token>,
+    ctx: &mut PreprocessContext,
+) -> isize {
+    let lf = end_token;
+
+    // definedの被演算子はマクロ展開しない
+    let expr_token = replace_defined(line_token, lf, ctx);
+    let expr_token = expand_tokens(expr_token, lf, ctx);
+    // マクロ展開で現れたdefinedや__has_attributeなども評価する
+    let expr_token = replace_defined(expr_token, lf, ctx);
+
+    // マクロ展開後に残った識別子は0に置き換える
+    let mut expr_token: Vec<Rc<Token>> = expr_token
+        .into_iter()
+        .map(|t| match t.kind {
+            // C23ではtrueは1となる
+            TokenKind::Keyword if &*t.common.token_str == "true" => new_num_token(&t, 1),
+            TokenKind::Ident | TokenKind::Keyword => new_num_token(&t, 0),
+            _ => t,
+        })
+        .collect();
+    expr_token.push(new_token(lf, String::new(), TokenKind::EOF));
+
+    check_pp_number(&expr_token);
+    const_expr(&expr_token)
+}
+
+// defined演算子と__has_includeなどの演算子をその結果の数値に置き換える
+fn replace_defined(
+    mut token: Vec<Rc<Token>>,
+    end_token: &Rc<Token>,
+    ctx: &PreprocessContext,
+) -> Vec<Rc<Token>> {
+    token.push(new_token(end_token, String::new(), TokenKind::EOF));
+
+    let mut stream = TokenStream::new(&token);
+    let mut replaced = Vec::new();
+
+    while !stream.at_eof() {
+        let token = stream.next().unwrap();
+        let name = token.common.token_str.as_ref();
+
+        if !is_macro_name(&token) {
+            replaced.push(token);
+        } else if name == "defined" {
+            let defined = read_defined(&mut stream, ctx);
+            replaced.push(new_num_token(&token, defined as isize));
+        } else if HAS_OPERATORS.contains(&name) {
+            let has = read_has_operator(&mut stream, &token, ctx);
+            replaced.push(new_num_token(&token, has as isize));
+        } else {
+            replaced.push(token);
+        }
+    }
+
+    replaced
+}
+
+// defined_operator := "defined" ident | "defined" "(" ident ")"
+fn read_defined(stream: &mut TokenStream, ctx: &PreprocessContext) -> bool {
+    let has_paren = stream.consume_punctuator("(").is_some();
+    let (_, name) = stream.expect_macro_name();
+    if has_paren {
+        stream.expect_punctuator(")");
+    }
+
+    is_defined(ctx, &name)
+}
+
+// __has_includeなどの演算子もマクロとして定義されているとみなす
+fn is_defined(ctx: &PreprocessContext, name: &str) -> bool {
+    ctx.find_macro(name).is_some() || HAS_OPERATORS.contains(&name)
+}
+
+// has_operator := "__has_include" "(" (str | "<" [^>]* ">") ")"
+//               | ("__has_builtin" | "__has_attribute" | "__has_feature") "(" ident ")"
+fn read_has_operator(stream: &mut TokenStream, token: &Rc<Token>, ctx: &PreprocessContext) -> bool {
+    stream.expect_punctuator("(");
+
+    let has = match token.common.token_str.as_ref() {
+        "__has_include" => {
+            let (_, path) = read_include_path(stream, token);
+            find_include_file(&path, &include_search_dirs(token)).is_ok()
+        }
+        "__has_builtin" => {
+            let (_, name) = stream.expect_identifier();
+            BUILTINS.contains(&name.as_str())
+        }
+        "__has_attribute" => {
+            let (_, name) = stream.expect_identifier();
+            // __packed__のような前後に__をつけた表記も受け付ける
+            let name = name.trim_start_matches("__").trim_end_matches("__");
+            // 属性は-fgnu-compat指定時のみ受け付ける
+            ctx.gnu_compat && ATTRIBUTES.contains(&name)
+        }
+        "__has_feature" => {
+            let (_, name) = stream.expect_identifier();
+            ctx.gnu_compat && FEATURES.contains(&name.as_str())
+        }
+        _ => unreachable!(),
+    };
+
+    stream.expect_punctuator(")");
+
+    has
+}
+
+// include_path := str | "<" [^>]* ">"
+fn read_include_path(stream: &mut TokenStream, token: &Rc<Token>) -> (Rc<Token>, String) {
+    if let Some((path_token, path)) = stream.consume_string() {
         let mut path = String::from_utf8(path).unwrap();
         // トークナイズで追加したnulを取り除く
         path.pop();
+        return (path_token, path);
+    }
+
+    let path_token = stream.expect_punctuator("<");
+    let mut path = String::new();
+    while stream.consume_punctuator(">").is_none() {
+        if stream.is_lf() || stream.at_eof() {
+            error_tok!(token, ">がありません");
+        }
+        path.push_str(&stream.next().unwrap().common.token_str);
+    }
+
+    (path_token, path)
+}
+
+// embed_param := ident "(" balanced_token* ")"
+// ident: limit, prefix, suffix, if_empty (前後に__をつけた表記も可)
+fn embed(
+    stream: &mut TokenStream,
+    preprocessed: &mut Vec<Rc<Token>>,
+    ctx: &mut PreprocessContext,
+    token: &Rc<Token>,
+) {
+    let token = &apply_line_marker(Rc::clone(token), ctx);
+    let (path_token, path) = read_include_path(stream, token);
+
+    let mut limit = None;
+    let mut prefix = Vec::new();
+    let mut suffix = Vec::new();
+    let mut if_empty = Vec::new();
+
+    while stream.consume_lf().is_none() {
+        let (param_token, param) = stream.expect_identifier();
+        let open = stream.expect_punctuator("(");
+        let (arg, close) = read_balanced_tokens(stream, &open);
+
+        match param.trim_start_matches("__").trim_end_matches("__") {
+            "limit" => {
+                let n = eval_const_expr(arg, &close, ctx);
+                if n < 0 {
+                    error_tok!(param_token, "limitが負の値です");
+                }
+                limit = Some(n as usize);
+            }
+            "prefix" => prefix = arg,
+            "suffix" => suffix = arg,
+            "if_empty" => if_empty = arg,
+            _ => {
+                error_tok!(param_token, "不明な#embedのパラメータです");
+            }
+        }
+    }
 
-        let mut search_dirs = Vec::new();
+    let path = find_include_file(&path, &include_search_dirs(token));
+    if path.is_err() {
+        error_tok!(path_token, "ファイルが見つかりません");
+    }
+    let path = path.unwrap();
+
+    let mut bytes = match fs::read(&path) {
+        Ok(bytes) => bytes,
+        Err(_) => {
+            error_tok!(path_token, "ファイルが読み込めません");
+        }
+    };
+    ctx.add_file(&path);
+    if let Some(limit) = limit {
+        bytes.truncate(limit);
+    }
+
+    let mut embedded = Vec::new();
+
+    if bytes.is_empty() {
+        embedded.extend(if_empty);
+    } else {
+        // .incbinでファイルを参照できるよう絶対パスを持たせる
+        let abs_path = fs::canonicalize(&path).map_or(path, |p| p.to_str().unwrap().to_string());
+        let file = Rc::new(EmbedFile { path: abs_path });
 
-        // ソースが格納されているディレクトリを
-        // インクルードファイルの探索パスに追加。
-        if let Some(src) = &token.common.src.path {
-            let src = Path::new(src);
-            if let Some(src_dir) = src.parent() {
-                search_dirs.push(src_dir.to_str().unwrap().to_string());
+        embedded.extend(prefix);
+        for (i, b) in bytes.iter().enumerate() {
+            if i > 0 {
+                embedded.push(new_token(token, ",".to_string(), TokenKind::Punctuator));
             }
+            let kind = TokenKind::EmbedByte(*b, Rc::clone(&file), i);
+            embedded.push(new_token(token, b.to_string(), kind));
         }
+        embedded.extend(suffix);
+    }
+
+    preprocessed.extend(expand_tokens(embedded, token, ctx));
+}
 
-        search_dirs.push(".".to_string());
+// 対応する")"までのトークンと")"のトークンを返す。
+// 開き括弧"("はすでに読み進めていること。
+fn read_balanced_tokens(stream: &mut TokenStream, open: &Rc<Token>) -> (Vec<Rc<Token>>, Rc<Token>) {
+    let mut token = Vec::new();
+    let mut depth = 0;
 
-        let path = find_include_file(&path, &search_dirs);
-        if path.is_err() {
-            error_tok!(path_token, "ファイルが見つかりません");
+    loop {
+        if stream.is_lf() || stream.at_eof() {
+            error_tok!(open, "対応する)がありません");
         }
-        let path = path.unwrap();
 
-        let mut inc_token = get_preprocessed_token(&path);
-        // 末尾のEOFを取り除く
-        inc_token.pop();
+        if let Some(close) = stream.consume_punctuator(")") {
+            if depth == 0 {
+                return (token, close);
+            }
+            depth -= 1;
+            token.push(close);
+        } else if let Some(open) = stream.consume_punctuator("(") {
+            depth += 1;
+            token.push(open);
+        } else {
+            token.push(stream.next().unwrap());
+        }
+    }
+}
+
+fn include(
+    stream: &mut TokenStream,
+    preprocessed: &mut Vec<Rc<Token>>,
+    ctx: &mut PreprocessContext,
+    token: &Rc<Token>,
+    search_dirs: &[String],
+) {
+    let (path_token, path) = read_include_path(stream, token);
 
-        preprocessed.extend(inc_token);
+    let path = find_include_file(&path, search_dirs);
+    if path.is_err() {
+        error_tok!(path_token, "ファイルが見つかりません");
+    }
+    let path = path.unwrap();
 
+    // #pragma onceが指定されたファイルは2回目以降は読み込まない
+    if ctx.is_once_file(Path::new(&path)) {
         stream.expect_lf();
-    } else {
+        return;
+    }
+
+    // プリコンパイル済みヘッダがあればプリプロセスせずにその状態を使う
+    if let Some(pch) = read_pch(&path, ctx) {
+        pch.apply(ctx);
+        preprocessed.extend(pch.token);
         stream.expect_lf();
+        return;
     }
+
+    // #lineによる位置情報の変更はインクルードしたファイルには引き継がない
+    let line_marker = ctx.line_marker.take();
+    let mut inc_token = get_preprocessed_token(&path, ctx);
+    ctx.line_marker = line_marker;
+    // 末尾のEOFを取り除く
+    inc_token.pop();
+
+    preprocessed.extend(inc_token);
+
+    stream.expect_lf();
+}
+
+// define := ident replacement_list
+//         | ident "(" macro_params replacement_list
+// replacement_list := [^LF]* LF
+fn define(stream: &mut TokenStream, ctx: &mut PreprocessContext) {
+    let (name_token, name) = stream.expect_macro_name();
+
+    // 名前の直後に空白を挟まずに"("があれば関数形式マクロ
+    let is_function = match stream.current() {
+        Some(t) => is_punctuator(&t, "(") && is_adjacent(&name_token, &t),
+        None => false,
+    };
+
+    let kind = if is_function {
+        stream.next();
+        let (params, variadic) = macro_params(stream);
+        MacroKind::Function(params, variadic, read_line(stream).0)
+    } else {
+        MacroKind::Object(read_line(stream).0)
+    };
+
+    ctx.define(&name, kind);
+}
+
+// macro_params := ")" | "..." ")" | ident ("," ident)* ("," "...")? ")"
+fn macro_params(stream: &mut TokenStream) -> (Vec<String>, bool) {
+    let mut params = Vec::new();
+
+    if stream.consume_punctuator(")").is_some() {
+        return (params, false);
+    }
+
+    loop {
+        if stream.consume_punctuator("...").is_some() {
+            stream.expect_punctuator(")");
+            params.push("__VA_ARGS__".to_string());
+            return (params, true);
+        }
+
+        let (_, param) = stream.expect_macro_name();
+        params.push(param);
+
+        if stream.consume_punctuator(")").is_some() {
+            return (params, false);
+        }
+        stream.expect_punctuator(",");
+    }
+}
+
+fn undef(stream: &mut TokenStream, ctx: &mut PreprocessContext) {
+    let (_, name) = stream.expect_macro_name();
+
+    ctx.undef(&name);
+
+    stream.expect_lf();
+}
+
+// 行番号とファイル名はマクロ展開してから読む
+fn line(stream: &mut TokenStream, ctx: &mut PreprocessContext) {
+    let (line_token, lf) = read_line(stream);
+
+    let mut expanded = expand_tokens(line_token, &lf, ctx);
+    expanded.push(Rc::clone(&lf));
+
+    let mut line_stream = TokenStream::new(&expanded);
+
+    let (num_token, n) = line_stream.expect_number();
+    if n <= 0 {
+        error_tok!(num_token, "行番号が不正です");
+    }
+
+    let path = if let Some((_, path)) = line_stream.consume_string() {
+        let mut path = String::from_utf8(path).unwrap();
+        // トークナイズで追加したnulを取り除く
+        path.pop();
+        Some(path)
+    } else {
+        match &ctx.line_marker {
+            Some(marker) => marker.path.clone(),
+            None => lf.common.src.path.clone(),
+        }
+    };
+
+    line_stream.expect_lf();
+
+    // ディレクティブの次の物理行がn行目となるようにする
+    let next_row = lf.common.loc.row as isize + 1;
+    ctx.line_marker = Some(Rc::new(LineMarker {
+        path,
+        row_delta: n - 1 - next_row,
+    }));
+}
+
+// 改行までのトークンと改行トークンを返す
+fn read_line(stream: &mut TokenStream) -> (Vec<Rc<Token>>, Rc<Token>) {
+    let mut line_token = Vec::new();
+
+    loop {
+        if let Some(lf) = stream.consume_lf() {
+            return (line_token, lf);
+        }
+        line_token.push(stream.next().unwrap());
+    }
+}
+
+fn join_tokens(token: &[Rc<Token>]) -> String {
+    let token_str: Vec<&str> = token.iter().map(|t| t.common.token_str.as_ref()).collect();
+    token_str.join(" ")
 }
 
 // text_line := [^LF]* LF
-fn text_line(stream: &mut TokenStream, preprocessed: &mut Vec<Rc<Token>>) {
-    while stream.consume_lf().is_none() {
-        match stream.next() {
-            Some(token) => preprocessed.push(token),
-            None => unreachable!(),
+fn text_line(
+    stream: &mut TokenStream,
+    preprocessed: &mut Vec<Rc<Token>>,
+    ctx: &mut PreprocessContext,
+) {
+    let mut input = MacroInput::new(stream);
+    let line_token: Vec<Rc<Token>> = expand_line(&mut input, ctx)
+        .into_iter()
+        .map(|(t, _)| t)
+        .collect();
+    stream.expect_lf();
+
+    check_pp_number(&line_token);
+
+    // マクロ展開で現れたものも含めて_Pragma演算子を処理する
+    pragma_operator(line_token, preprocessed, ctx);
+}
+
+// 数値として読めないpp-numberが残っていればエラーを報告する
+fn check_pp_number(token: &[Rc<Token>]) {
+    for t in token.iter() {
+        if t.kind == TokenKind::PPNumber {
+            if let Err(msg) = read_number(&t.common.token_str) {
+                error_tok!(t, "{}", msg);
+            }
+        }
+    }
+}
+
+// pragma_operator := "_Pragma" "(" str ")"
+fn pragma_operator(
+    token: Vec<Rc<Token>>,
+    preprocessed: &mut Vec<Rc<Token>>,
+    ctx: &mut PreprocessContext,
+) {
+    let mut token = token.into_iter().peekable();
+
+    while let Some(t) = token.next() {
+        if !(t.kind == TokenKind::Ident && &*t.common.token_str == "_Pragma") {
+            preprocessed.push(t);
+            continue;
+        }
+
+        let mut arg = Vec::new();
+        for expected in ["(", "", ")"].iter() {
+            match token.next() {
+                Some(a) if expected.is_empty() || &*a.common.token_str == *expected => arg.push(a),
+                _ => {
+                    error_tok!(t, "_Pragma(\"...\")の形式ではありません");
+                }
+            }
+        }
+
+        let mut code = match &arg[1].kind {
+            TokenKind::Str(s, _) => String::from_utf8(s[..s.len() - 1].to_vec()).unwrap(),
+            _ => {
+                error_tok!(arg[1], "文字列ではありません");
+            }
+        };
+        code.push('\n');
+
+        // 文字列の中身を#pragmaに続く行としてトークナイズする
+        let src = Source {
+            path: t.common.src.path.clone(),
+            code,
+        };
+        let pragma_token = tokenize(Rc::new(src));
+        let mut pragma_stream = TokenStream::new(&pragma_token);
+        pragma(&mut pragma_stream, preprocessed, ctx, &t);
+    }
+}
+
+// pragma := "once" LF
+//         | "pack" "(" pack_arg? ")" LF
+//         | ("push_macro" | "pop_macro") "(" str ")" LF
+//         | [^LF]* LF
+fn pragma(
+    stream: &mut TokenStream,
+    preprocessed: &mut Vec<Rc<Token>>,
+    ctx: &mut PreprocessContext,
+    token: &Rc<Token>,
+) {
+    let state = stream.save();
+    let name = stream.consume_identifier().map(|(_, name)| name);
+
+    match name.as_deref() {
+        Some("once") => {
+            if let Some(path) = &token.common.src.path {
+                ctx.add_once_file(Path::new(path));
+            }
+            stream.expect_lf();
+        }
+        Some("pack") => {
+            stream.expect_punctuator("(");
+            pragma_pack(stream, ctx);
+            stream.expect_punctuator(")");
+            stream.expect_lf();
+
+            // 構造体のレイアウトは構文解析で決まるのでトークンとして伝える
+            let token_str = match ctx.pack {
+                Some(n) => format!("_Pragma(\"pack({})\")", n),
+                None => "_Pragma(\"pack()\")".to_string(),
+            };
+            let pack = TokenKind::Pack(ctx.pack);
+            preprocessed.push(new_token(token, token_str, pack));
+        }
+        Some(name) if name == "push_macro" || name == "pop_macro" => {
+            stream.expect_punctuator("(");
+            let (_, macro_name) = stream.expect_string();
+            stream.expect_punctuator(")");
+            stream.expect_lf();
+
+            let mut macro_name = String::from_utf8(macro_name).unwrap();
+            // トークナイズで追加したnulを取り除く
+            macro_name.pop();
+
+            if name == "push_macro" {
+                ctx.push_macro(&macro_name);
+            } else {
+                ctx.pop_macro(&macro_name);
+            }
+        }
+        _ => {
+            stream.restore(state);
+            let msg = join_tokens(&read_line(stream).0);
+            warn_tok!(token, "不明なプラグマです: {}", msg);
+        }
+    }
+}
+
+// pack_arg := num | "push" ("," num)? | "pop"
+fn pragma_pack(stream: &mut TokenStream, ctx: &mut PreprocessContext) {
+    if stream.is_punctuator(")") {
+        ctx.pack = None;
+        return;
+    }
+
+    if let Some((token, arg)) = stream.consume_identifier() {
+        match arg.as_str() {
+            "push" => {
+                ctx.pack_stack.push(ctx.pack);
+                if stream.consume_punctuator(",").is_some() {
+                    ctx.pack = Some(pack_alignment(stream));
+                }
+            }
+            "pop" => {
+                if let Some(pack) = ctx.pack_stack.pop() {
+                    ctx.pack = pack;
+                } else {
+                    warn_tok!(token, "{}", "pack(push)されていません");
+                }
+            }
+            _ => {
+                error_tok!(token, "pack()の引数が不正です");
+            }
+        }
+    } else {
+        ctx.pack = Some(pack_alignment(stream));
+    }
+}
+
+fn pack_alignment(stream: &mut TokenStream) -> usize {
+    let (token, n) = stream.expect_number();
+
+    if ![1, 2, 4, 8, 16].contains(&n) {
+        error_tok!(token, "アライメントは1, 2, 4, 8, 16のいずれかです");
+    }
+
+    n as usize
+}
+
+// 展開中のマクロ名の集合。自己参照しているマクロが無限に展開されることを防ぐ。
+type Hideset = Vec<Rc<str>>;
+
+// マクロ展開の入力。展開結果は先頭に戻して続くトークンと共に再走査する。
+struct MacroInput<'a, 'vec> {
+    // 先頭に戻したトークンとそのhideset。末尾から読む。
+    pending: Vec<(Rc<Token>, Hideset)>,
+    stream: &'a mut TokenStream<'vec>,
+}
+
+impl<'a, 'vec> MacroInput<'a, 'vec> {
+    fn new(stream: &'a mut TokenStream<'vec>) -> Self {
+        Self {
+            pending: Vec::new(),
+            stream,
+        }
+    }
+
+    // 行末もしくはEOFに達したらNoneを返す
+    fn next(&mut self, ctx: &PreprocessContext) -> Option<(Rc<Token>, Hideset)> {
+        if let Some(t) = self.pending.pop() {
+            return Some(t);
+        }
+
+        if self.stream.is_lf() || self.stream.at_eof() {
+            return None;
+        }
+        let token = self.stream.next().unwrap();
+        Some((apply_line_marker(token, ctx), Vec::new()))
+    }
+
+    // 関数形式マクロの実引数は改行をまたげるので、改行を読み飛ばして次のトークンを返す
+    fn next_arg_token(&mut self, ctx: &PreprocessContext) -> Option<(Rc<Token>, Hideset)> {
+        if self.pending.is_empty() {
+            while self.stream.consume_lf().is_some() {}
+        }
+        self.next(ctx)
+    }
+
+    // 次のトークンが"("であれば読み進めてtrueを返す。改行は読み飛ばす。
+    fn consume_open_paren(&mut self) -> bool {
+        if let Some((t, _)) = self.pending.last() {
+            if !is_punctuator(t, "(") {
+                return false;
+            }
+            self.pending.pop();
+            return true;
+        }
+
+        let state = self.stream.save();
+        while self.stream.consume_lf().is_some() {}
+        if self.stream.consume_punctuator("(").is_some() {
+            return true;
         }
+        self.stream.restore(state);
+        false
+    }
+}
+
+// inputを行末まで読み、マクロを展開したトークン列を返す
+fn expand_line(input: &mut MacroInput, ctx: &mut PreprocessContext) -> Vec<(Rc<Token>, Hideset)> {
+    let mut expanded = Vec::new();
+
+    while let Some((token, hideset)) = input.next(ctx) {
+        if !expand_macro(&token, &hideset, input, ctx) {
+            expanded.push((token, hideset));
+        }
+    }
+
+    expanded
+}
+
+// トークン列のマクロを展開する。end_tokenは列の終わりの位置を示すのに使う。
+fn expand_tokens(
+    token: Vec<Rc<Token>>,
+    end_token: &Rc<Token>,
+    ctx: &mut PreprocessContext,
+) -> Vec<Rc<Token>> {
+    let token = token.into_iter().map(|t| (t, Vec::new())).collect();
+    expand_arg(token, end_token, ctx)
+        .into_iter()
+        .map(|(t, _)| t)
+        .collect()
+}
+
+// マクロの実引数を、続くトークンとは切り離して展開する
+fn expand_arg(
+    token: Vec<(Rc<Token>, Hideset)>,
+    end_token: &Rc<Token>,
+    ctx: &mut PreprocessContext,
+) -> Vec<(Rc<Token>, Hideset)> {
+    let eof = [new_token(end_token, String::new(), TokenKind::EOF)];
+    let mut stream = TokenStream::new(&eof);
+    let mut input = MacroInput::new(&mut stream);
+    input.pending = token.into_iter().rev().collect();
+
+    expand_line(&mut input, ctx)
+}
+
+// トークンがマクロであれば、展開したトークン列をinputの先頭に戻してtrueを返す
+fn expand_macro(
+    token: &Rc<Token>,
+    hideset: &[Rc<str>],
+    input: &mut MacroInput,
+    ctx: &mut PreprocessContext,
+) -> bool {
+    if !is_macro_name(token) {
+        return false;
+    }
+
+    let name = &token.common.token_str;
+    if hideset.contains(name) {
+        return false;
     }
+
+    let kind = match ctx.find_macro(name) {
+        Some(m) => m.kind.clone(),
+        None => return false,
+    };
+
+    let mut hideset = hideset.to_vec();
+    hideset.push(name.clone());
+
+    let expanded = match kind {
+        MacroKind::Object(body) => subst(token, &body, &[], &[], &hideset, ctx),
+        MacroKind::Function(params, variadic, body) => {
+            // 関数形式マクロの名前だけなら展開しない
+            if !input.consume_open_paren() {
+                return false;
+            }
+            let args = read_macro_args(token, &params, variadic, input, ctx);
+            subst(token, &body, &params, &args, &hideset, ctx)
+        }
+        MacroKind::File => {
+            let path = match token.common.path() {
+                Some(path) => path.to_string(),
+                None => "<stdin>".to_string(),
+            };
+            vec![(new_str_token(token, &path), hideset)]
+        }
+        MacroKind::Line => {
+            let line = token.common.row() + 1;
+            vec![(new_num_token(token, line as isize), hideset)]
+        }
+        MacroKind::Counter => {
+            let counter = ctx.next_counter();
+            vec![(new_num_token(token, counter as isize), hideset)]
+        }
+    };
+
+    input.pending.extend(expanded.into_iter().rev());
+    true
+}
+
+// macro_args := "(" (arg ("," arg)*)? ")"
+// 開き括弧"("はすでに読み進めていること。
+fn read_macro_args(
+    token: &Rc<Token>,
+    params: &[String],
+    variadic: bool,
+    input: &mut MacroInput,
+    ctx: &PreprocessContext,
+) -> Vec<Vec<(Rc<Token>, Hideset)>> {
+    let mut args = vec![Vec::new()];
+    let mut depth = 0;
+
+    loop {
+        let (t, hideset) = match input.next_arg_token(ctx) {
+            Some(t) => t,
+            None => {
+                error_tok!(token, "マクロの引数の)がありません");
+            }
+        };
+
+        if is_punctuator(&t, "(") {
+            depth += 1;
+        } else if is_punctuator(&t, ")") {
+            if depth == 0 {
+                break;
+            }
+            depth -= 1;
+        } else if is_punctuator(&t, ",") && depth == 0 {
+            // 可変長引数に対応する部分はカンマも含めて1つの引数とする
+            if !(variadic && args.len() == params.len()) {
+                args.push(Vec::new());
+                continue;
+            }
+        }
+
+        args.last_mut().unwrap().push((t, hideset));
+    }
+
+    // 仮引数が無ければf()は引数0個の呼び出し
+    if params.is_empty() && args.len() == 1 && args[0].is_empty() {
+        args.pop();
+    }
+    // 可変長引数は省略できる
+    if variadic && args.len() + 1 == params.len() {
+        args.push(Vec::new());
+    }
+
+    if args.len() != params.len() {
+        error_tok!(token, "マクロの引数の数が一致しません");
+    }
+
+    args
+}
+
+// 置換リストの仮引数を実引数に置き換え、#と##を処理する。
+// 展開後のトークンは展開箇所の位置を持たせる。
+fn subst(
+    token: &Rc<Token>,
+    body: &[Rc<Token>],
+    params: &[String],
+    args: &[Vec<(Rc<Token>, Hideset)>],
+    hideset: &[Rc<str>],
+    ctx: &mut PreprocessContext,
+) -> Vec<(Rc<Token>, Hideset)> {
+    let arg_index = |t: &Rc<Token>| -> Option<usize> {
+        if is_macro_name(t) {
+            params.iter().position(|p| *p == *t.common.token_str)
+        } else {
+            None
+        }
+    };
+
+    // ##と#の被演算子となる仮引数は展開せずに置き換える
+    let operand = |t: &Rc<Token>| -> Vec<(Rc<Token>, Hideset)> {
+        match arg_index(t) {
+            Some(i) => args[i].clone(),
+            None => vec![(
+                new_token(token, t.common.token_str.clone(), t.kind.clone()),
+                Vec::new(),
+            )],
+        }
+    };
+
+    let mut expanded: Vec<(Rc<Token>, Hideset)> = Vec::new();
+    let mut i = 0;
+
+    while i < body.len() {
+        let t = &body[i];
+
+        // "#" param
+        if is_punctuator(t, "#") {
+            if let Some(n) = body.get(i + 1).and_then(&arg_index) {
+                expanded.push((stringize(token, &args[n]), Vec::new()));
+                i += 2;
+                continue;
+            }
+        }
+
+        // operand ("##" operand)*
+        if matches!(body.get(i + 1), Some(t) if is_punctuator(t, "##")) {
+            let mut lhs = operand(t);
+            i += 1;
+
+            while i + 1 < body.len() && is_punctuator(&body[i], "##") {
+                let rhs = &body[i + 1];
+                i += 2;
+
+                // GNU拡張: ", ## __VA_ARGS__"は連結せず、可変長引数が空ならカンマを取り除く
+                if let Some(n) = arg_index(rhs) {
+                    let is_comma = matches!(lhs.last(), Some((t, _)) if is_punctuator(t, ","));
+                    if is_comma && params[n] == "__VA_ARGS__" {
+                        if args[n].is_empty() {
+                            lhs.pop();
+                        }
+                        lhs.extend(args[n].iter().cloned());
+                        continue;
+                    }
+                }
+
+                let mut rhs = operand(rhs).into_iter();
+                match (lhs.pop(), rhs.next()) {
+                    (Some((l, _)), Some((r, _))) => lhs.push((paste(token, &l, &r), Vec::new())),
+                    (Some(l), None) => lhs.push(l),
+                    (None, Some(r)) => lhs.push(r),
+                    (None, None) => (),
+                }
+                lhs.extend(rhs);
+            }
+
+            expanded.extend(lhs);
+            continue;
+        }
+
+        match arg_index(t) {
+            Some(n) => expanded.extend(expand_arg(args[n].clone(), token, ctx)),
+            None => expanded.push((
+                new_token(token, t.common.token_str.clone(), t.kind.clone()),
+                Vec::new(),
+            )),
+        }
+        i += 1;
+    }
+
+    // 展開結果にはマクロ呼び出しのhidesetを加える
+    for (_, hs) in expanded.iter_mut() {
+        for name in hideset.iter() {
+            if !hs.contains(name) {
+                hs.push(name.clone());
+            }
+        }
+    }
+
+    expanded
+}
+
+// 実引数を綴りのまま文字列リテラルにする
+fn stringize(token: &Rc<Token>, arg: &[(Rc<Token>, Hideset)]) -> Rc<Token> {
+    let mut s = String::new();
+
+    for (i, (t, _)) in arg.iter().enumerate() {
+        if i > 0 && !is_adjacent(&arg[i - 1].0, t) {
+            s.push(' ');
+        }
+        s.push_str(&t.common.token_str);
+    }
+
+    new_str_token(token, &s)
+}
+
+// 2つのトークンを連結して1つのトークンにする
+fn paste(token: &Rc<Token>, lhs: &Rc<Token>, rhs: &Rc<Token>) -> Rc<Token> {
+    let code = format!("{}{}", lhs.common.token_str, rhs.common.token_str);
+    let src = Source {
+        path: token.common.src.path.clone(),
+        code: code.clone(),
+    };
+
+    // 連結結果は1つのトークンとEOFになる
+    let pasted = tokenize(Rc::new(src));
+    if pasted.len() != 2 {
+        error_tok!(token, "{}は1つのトークンになりません", code);
+    }
+
+    new_token(token, code, pasted[0].kind.clone())
+}
+
+// 2つのトークンが空白を挟まずに並んでいるか
+fn is_adjacent(prev: &Token, next: &Token) -> bool {
+    Rc::ptr_eq(&prev.common.src, &next.common.src)
+        && prev.common.loc.row == next.common.loc.row
+        && prev.common.loc.col + prev.common.token_str.chars().count() == next.common.loc.col
+}
+
+fn is_punctuator(token: &Token, op: &str) -> bool {
+    token.kind == TokenKind::Punctuator && &*token.common.token_str == op
+}
+
+// #lineで変更された位置情報をトークンに反映する
+fn apply_line_marker(token: Rc<Token>, ctx: &PreprocessContext) -> Rc<Token> {
+    match &ctx.line_marker {
+        Some(marker) => Rc::new(Token {
+            common: TokenCommon {
+                token_str: token.common.token_str.clone(),
+                src: Rc::clone(&token.common.src),
+                loc: token.common.loc,
+                line_marker: Some(Rc::clone(marker)),
+            },
+            kind: token.kind.clone(),
+        }),
+        None => token,
+    }
+}
+
+// 位置情報をtokenから引き継いだトークンを作る
+fn new_token(token: &Rc<Token>, token_str: impl Into<Rc<str>>, kind: TokenKind) -> Rc<Token> {
+    Rc::new(Token {
+        common: TokenCommon {
+            token_str: token_str.into(),
+            src: Rc::clone(&token.common.src),
+            loc: token.common.loc,
+            line_marker: token.common.line_marker.clone(),
+        },
+        kind,
+    })
+}
+
+// プリプロセッサはキーワードを識別子として扱う
+fn is_macro_name(token: &Token) -> bool {
+    matches!(token.kind, TokenKind::Ident | TokenKind::Keyword)
+}
+
+fn new_num_token(token: &Rc<Token>, n: isize) -> Rc<Token> {
+    new_token(token, n.to_string(), TokenKind::Num(n, IntType::Int))
+}
+
+fn new_str_token(token: &Rc<Token>, s: &str) -> Rc<Token> {
+    let mut token_str = String::from("\"");
+    for c in s.chars() {
+        if c == '"' || c == '\\' {
+            token_str.push('\\');
+        }
+        token_str.push(c);
+    }
+    token_str.push('"');
+
+    let mut bytes = s.as_bytes().to_vec();
+    bytes.push(b'\0');
+
+    new_token(token, token_str, TokenKind::Str(bytes, Encoding::Char))
 }
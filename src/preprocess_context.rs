@@ -0,0 +1,144 @@
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use super::tokenize::{LineMarker, Token};
+
+#[derive(Clone)]
+pub enum MacroKind {
+    // オブジェクト形式マクロの置換リスト
+    Object(Vec<Rc<Token>>),
+    // 関数形式マクロの仮引数、可変長引数を取るか、置換リスト。
+    // 可変長引数は__VA_ARGS__という名前の最後の仮引数とする。
+    Function(Vec<String>, bool, Vec<Rc<Token>>),
+    // 展開される箇所によって値が変わるマクロ
+    File,
+    Line,
+    Counter,
+}
+
+#[derive(Clone)]
+pub struct Macro {
+    // マクロの名前
+    pub name: String,
+    // マクロの種別
+    pub kind: MacroKind,
+}
+
+pub struct PreprocessContext {
+    // -fgnu-compatが指定されているか
+    pub gnu_compat: bool,
+    // 処理中のファイルで最後に現れた#lineの位置情報
+    pub line_marker: Option<Rc<LineMarker>>,
+    // #pragma packで指定された構造体メンバーの最大アライメント
+    pub pack: Option<usize>,
+    // #pragma pack(push)で退避したアライメント
+    pub pack_stack: Vec<Option<usize>>,
+    macros: Vec<Macro>,
+    // #pragma push_macroで退避したマクロ名と定義
+    pushed_macros: Vec<(String, Option<Macro>)>,
+    // #pragma onceが指定されたファイル
+    once_files: Vec<PathBuf>,
+    // プリプロセスで読み込んだファイル
+    files: Vec<String>,
+    // 次に__COUNTER__を展開したときの値
+    pub counter: usize,
+}
+
+impl PreprocessContext {
+    pub fn new() -> Self {
+        let mut ctx = Self {
+            gnu_compat: false,
+            line_marker: None,
+            pack: None,
+            pack_stack: Vec::new(),
+            macros: Vec::new(),
+            pushed_macros: Vec::new(),
+            once_files: Vec::new(),
+            files: Vec::new(),
+            counter: 0,
+        };
+
+        ctx.define("__FILE__", MacroKind::File);
+        ctx.define("__LINE__", MacroKind::Line);
+        ctx.define("__COUNTER__", MacroKind::Counter);
+
+        ctx
+    }
+
+    // 同名のマクロがすでにあれば置き換える
+    pub fn define(&mut self, name: &str, kind: MacroKind) {
+        let new_macro = Macro {
+            name: name.to_string(),
+            kind,
+        };
+
+        if let Some(m) = self.macros.iter_mut().find(|m| m.name == name) {
+            *m = new_macro;
+        } else {
+            self.macros.push(new_macro);
+        }
+    }
+
+    pub fn undef(&mut self, name: &str) {
+        self.macros.retain(|m| m.name != name);
+    }
+
+    // マクロが定義されていなければそのことを退避する
+    pub fn push_macro(&mut self, name: &str) {
+        let m = self.find_macro(name).cloned();
+        self.pushed_macros.push((name.to_string(), m));
+    }
+
+    // 最後に退避した定義に戻す。退避していなければ何もしない。
+    pub fn pop_macro(&mut self, name: &str) {
+        let pos = self.pushed_macros.iter().rposition(|(n, _)| n == name);
+
+        if let Some(pos) = pos {
+            match self.pushed_macros.remove(pos).1 {
+                Some(m) => self.define(name, m.kind),
+                None => self.undef(name),
+            }
+        }
+    }
+
+    pub fn add_once_file(&mut self, path: &Path) {
+        if let Ok(path) = path.canonicalize() {
+            self.once_files.push(path);
+        }
+    }
+
+    pub fn is_once_file(&self, path: &Path) -> bool {
+        match path.canonicalize() {
+            Ok(path) => self.once_files.contains(&path),
+            Err(_) => false,
+        }
+    }
+
+    pub fn once_files(&self) -> &[PathBuf] {
+        &self.once_files
+    }
+
+    pub fn add_file(&mut self, path: &str) {
+        if !self.files.iter().any(|f| f == path) {
+            self.files.push(path.to_string());
+        }
+    }
+
+    pub fn files(&self) -> &[String] {
+        &self.files
+    }
+
+    pub fn find_macro(&self, name: &str) -> Option<&Macro> {
+        self.macros.iter().find(|m| m.name == name)
+    }
+
+    pub fn macros(&self) -> &[Macro] {
+        &self.macros
+    }
+
+    // __COUNTER__の値を返し、次の値に進める
+    pub fn next_counter(&mut self) -> usize {
+        self.counter += 1;
+        self.counter - 1
+    }
+}
@@ -1,37 +1,71 @@
 use std::rc::Rc;
 
 use super::src::Source;
-use super::tokenize::{Loc, Token, TokenKind};
+use super::tokenize::{FloatType, Loc, Token, TokenKind};
 
 pub struct TokenStream<'vec> {
     token: &'vec [Rc<Token>],
     current: usize,
+    // #pragma packを置き換えたトークンの位置とアライメントの上限
+    packs: Vec<(usize, Option<usize>)>,
 }
 
 impl<'vec> TokenStream<'vec> {
     pub fn new(token: &'vec [Rc<Token>]) -> Self {
-        Self { token, current: 0 }
+        let packs = token
+            .iter()
+            .enumerate()
+            .filter_map(|(i, t)| match t.kind {
+                TokenKind::Pack(pack) => Some((i, pack)),
+                _ => None,
+            })
+            .collect();
+
+        Self {
+            token,
+            current: 0,
+            packs,
+        }
     }
 
     fn get_src(&self) -> Rc<Source> {
         // 終端にEOFがあるので0要素目は必ず存在する
-        Rc::clone(&self.token.get(0).unwrap().common.src)
+        Rc::clone(&self.token.first().unwrap().common.src)
     }
 
-    fn peek(&self) -> Option<Rc<Token>> {
-        if self.current >= self.token.len() {
-            None
-        } else {
-            Some(Rc::clone(&self.token[self.current]))
+    // #pragma packを置き換えたトークンは構文に現れないので読み飛ばした位置を返す
+    fn position(&self) -> usize {
+        let mut pos = self.current;
+        while let Some(Token {
+            kind: TokenKind::Pack(_),
+            ..
+        }) = self.token.get(pos).map(|t| &**t)
+        {
+            pos += 1;
         }
+        pos
+    }
+
+    fn peek(&self) -> Option<Rc<Token>> {
+        self.token.get(self.position()).map(Rc::clone)
     }
 
     pub fn next(&mut self) -> Option<Rc<Token>> {
-        if self.current >= self.token.len() {
+        let pos = self.position();
+        if pos >= self.token.len() {
+            self.current = pos;
             None
         } else {
-            self.current += 1;
-            Some(Rc::clone(&self.token[self.current - 1]))
+            self.current = pos + 1;
+            Some(Rc::clone(&self.token[pos]))
+        }
+    }
+
+    // 読み終えたトークンまでに指定された#pragma packのアライメントの上限を返す
+    pub fn pack(&self) -> Option<usize> {
+        match self.packs.binary_search_by_key(&self.current, |(i, _)| *i) {
+            Ok(0) | Err(0) => None,
+            Ok(i) | Err(i) => self.packs[i - 1].1,
         }
     }
 
@@ -53,7 +87,7 @@ impl<'vec> TokenStream<'vec> {
     }
 
     pub fn current(&self) -> Option<Rc<Token>> {
-        self.token.get(self.current).map(|token| Rc::clone(token))
+        self.peek()
     }
 
     // 次のトークンが期待している記号のときには、trueを返す。
@@ -63,7 +97,7 @@ impl<'vec> TokenStream<'vec> {
             Some(Token {
                 common,
                 kind: TokenKind::Punctuator,
-            }) => common.token_str == op,
+            }) => &*common.token_str == op,
             _ => false,
         }
     }
@@ -71,9 +105,13 @@ impl<'vec> TokenStream<'vec> {
     fn is_number_impl(&self) -> Option<isize> {
         match self.peek().as_deref() {
             Some(Token {
-                kind: TokenKind::Num(n),
+                kind: TokenKind::Num(n, _),
                 ..
             }) => Some(*n),
+            Some(Token {
+                kind: TokenKind::EmbedByte(b, ..),
+                ..
+            }) => Some(*b as isize),
             _ => None,
         }
     }
@@ -88,7 +126,7 @@ impl<'vec> TokenStream<'vec> {
     fn is_string_impl(&self) -> Option<Vec<u8>> {
         match self.peek().as_deref() {
             Some(Token {
-                kind: TokenKind::Str(s),
+                kind: TokenKind::Str(s, _),
                 ..
             }) => Some(s.clone()),
             _ => None,
@@ -107,7 +145,7 @@ impl<'vec> TokenStream<'vec> {
             Some(Token {
                 common,
                 kind: TokenKind::Ident,
-            }) => Some(common.token_str.clone()),
+            }) => Some(common.token_str.to_string()),
             _ => None,
         }
     }
@@ -126,7 +164,7 @@ impl<'vec> TokenStream<'vec> {
             Some(Token {
                 common,
                 kind: TokenKind::Keyword,
-            }) => common.token_str == keyword,
+            }) => &*common.token_str == keyword,
             _ => false,
         }
     }
@@ -157,8 +195,20 @@ impl<'vec> TokenStream<'vec> {
     // 次のトークンが数値の場合、そのトークンと数値をSomeで包んで返し
     // トークンを1つ読み進める。それ以外の場合にはNoneを返す。
     pub fn consume_number(&mut self) -> Option<(Rc<Token>, isize)> {
-        match self.is_number_impl() {
-            Some(n) => Some((self.next().unwrap(), n)),
+        self.is_number_impl().map(|n| (self.next().unwrap(), n))
+    }
+
+    // 次のトークンが浮動小数点数の場合、そのトークンと値、型をSomeで包んで返し
+    // トークンを1つ読み進める。それ以外の場合にはNoneを返す。
+    pub fn consume_float(&mut self) -> Option<(Rc<Token>, f64, FloatType)> {
+        match self.peek().as_deref() {
+            Some(Token {
+                kind: TokenKind::Float(f, ctype),
+                ..
+            }) => {
+                let (f, ctype) = (*f, *ctype);
+                Some((self.next().unwrap(), f, ctype))
+            }
             _ => None,
         }
     }
@@ -166,19 +216,14 @@ impl<'vec> TokenStream<'vec> {
     // 次のトークンが文字列の場合、そのトークンと文字列をSomeで包んで返し
     // トークンを1つ読み進める。それ以外の場合にはNoneを返す。
     pub fn consume_string(&mut self) -> Option<(Rc<Token>, Vec<u8>)> {
-        match self.is_string_impl() {
-            Some(s) => Some((self.next().unwrap(), s)),
-            _ => None,
-        }
+        self.is_string_impl().map(|s| (self.next().unwrap(), s))
     }
 
     // 次のトークンが識別子の場合、そのトークンと識別子をSomeで包んで返し
     // トークンを1つ読み進める。それ以外の場合にはNoneを返す。
     pub fn consume_identifier(&mut self) -> Option<(Rc<Token>, String)> {
-        match self.is_identifier_impl() {
-            Some(ident) => Some((self.next().unwrap(), ident)),
-            _ => None,
-        }
+        self.is_identifier_impl()
+            .map(|ident| (self.next().unwrap(), ident))
     }
 
     // 次のトークンが期待しているキーワードの場合、そのトークンを
@@ -215,7 +260,6 @@ impl<'vec> TokenStream<'vec> {
 
     // 次のトークンが数値の場合、そのトークンと数値を返し、トークンを
     // 1つ読み進める。それ以外の場合にはエラーを報告する。
-    #[allow(dead_code)]
     pub fn expect_number(&mut self) -> (Rc<Token>, isize) {
         let token_num = self.consume_number();
 
@@ -250,6 +294,28 @@ impl<'vec> TokenStream<'vec> {
         token_ident.unwrap()
     }
 
+    // 次のトークンがマクロ名の場合、そのトークンと名前を返し、トークンを
+    // 1つ読み進める。それ以外の場合にはエラーを報告する。
+    // プリプロセッサはキーワードを区別しないので、キーワードもマクロ名にできる。
+    pub fn expect_macro_name(&mut self) -> (Rc<Token>, String) {
+        match self.peek().as_deref() {
+            Some(Token {
+                common,
+                kind: TokenKind::Ident,
+            })
+            | Some(Token {
+                common,
+                kind: TokenKind::Keyword,
+            }) => {
+                let name = common.token_str.to_string();
+                (self.next().unwrap(), name)
+            }
+            _ => {
+                error_at!(self.get_src(), self.loc(), "識別子ではありません");
+            }
+        }
+    }
+
     // 次のトークンが期待しているキーワードの場合、そのトークンを返し
     // トークンを1つ読み進める。それ以外の場合にはエラーを報告する。
     #[allow(dead_code)]
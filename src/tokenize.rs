@@ -1,8 +1,11 @@
-use std::iter::Peekable;
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::hash::{BuildHasherDefault, Hasher};
 use std::rc::Rc;
-use std::str::CharIndices;
 
+use super::f80::{round_bits, F80};
 use super::src::Source;
+use super::unicode::{is_xid_continue, is_xid_start};
 
 #[derive(Clone, Copy, PartialEq)]
 pub struct Loc {
@@ -10,14 +13,84 @@ pub struct Loc {
     pub col: usize,
 }
 
+// #lineディレクティブで変更された位置情報
+#[derive(PartialEq)]
+pub struct LineMarker {
+    // 報告するファイル名。Noneなら標準入力
+    pub path: Option<String>,
+    // 物理的な行に加算する値
+    pub row_delta: isize,
+}
+
+// #embedで読み込んだファイル
+#[derive(PartialEq)]
+pub struct EmbedFile {
+    // ファイルの絶対パス
+    pub path: String,
+}
+
 #[derive(PartialEq)]
 pub struct TokenCommon {
-    pub token_str: String,
+    pub token_str: Rc<str>,
     pub src: Rc<Source>,
     pub loc: Loc,
+    pub line_marker: Option<Rc<LineMarker>>,
 }
 
-#[derive(PartialEq)]
+impl TokenCommon {
+    // #lineを反映したファイル名を返す
+    pub fn path(&self) -> Option<&str> {
+        match &self.line_marker {
+            Some(marker) => marker.path.as_deref(),
+            None => self.src.path.as_deref(),
+        }
+    }
+
+    // #lineを反映した行を返す
+    pub fn row(&self) -> usize {
+        match &self.line_marker {
+            Some(marker) => (self.loc.row as isize + marker.row_delta) as usize,
+            None => self.loc.row,
+        }
+    }
+}
+
+// 整数定数の型
+#[derive(Clone, Copy, PartialEq)]
+pub enum IntType {
+    Int,
+    UInt,
+    Long,
+    ULong,
+    LongLong,
+    ULongLong,
+}
+
+impl IntType {
+    fn is_unsigned(&self) -> bool {
+        matches!(self, Self::UInt | Self::ULong | Self::ULongLong)
+    }
+
+    // 値を表現できる最大値
+    fn max(&self) -> u64 {
+        match self {
+            Self::Int => i32::MAX as u64,
+            Self::UInt => u32::MAX as u64,
+            Self::Long | Self::LongLong => i64::MAX as u64,
+            Self::ULong | Self::ULongLong => u64::MAX,
+        }
+    }
+}
+
+// 浮動小数点定数の型
+#[derive(Clone, Copy, PartialEq)]
+pub enum FloatType {
+    Float,
+    Double,
+    LongDouble,
+}
+
+#[derive(Clone, PartialEq)]
 pub enum TokenKind {
     // 記号
     Punctuator,
@@ -25,10 +98,18 @@ pub enum TokenKind {
     Ident,
     // キーワード
     Keyword,
-    // 整数
-    Num(isize),
-    // 文字列
-    Str(Vec<u8>),
+    // 整数(値, 型)。符号なしで表現できない値は2の補数として格納する
+    Num(isize, IntType),
+    // 浮動小数点数(値, 型)。値は型の精度に丸めてある
+    Float(f64, FloatType),
+    // 文字列(終端のnulを含むリトルエンディアンのバイト列, 符号化方式)
+    Str(Vec<u8>, Encoding),
+    // 整数定数や浮動小数点定数として読めないpp-number
+    PPNumber,
+    // #embedで展開された1バイト(値, ファイル, ファイル先頭からのオフセット)
+    EmbedByte(u8, Rc<EmbedFile>, usize),
+    // #pragma packで変更された構造体メンバーの最大アライメント
+    Pack(Option<usize>),
     // 改行
     #[allow(clippy::upper_case_acronyms)]
     LF,
@@ -43,16 +124,83 @@ pub struct Token {
     pub kind: TokenKind,
 }
 
+// ソースを1文字ずつ位置情報付きで返すイテレータ。
+// 行末のバックスラッシュと改行の組(行連結)は読み飛ばすが、
+// 返す位置情報は元のソース上の物理的な行と桁を指す。
+// ほとんどの文字はASCIIなので、バイト列のまま読み進めて
+// ASCII以外の場合だけUTF-8として復号する。
+#[derive(Clone)]
 struct LocIter<'a> {
-    iter: CharIndices<'a>,
+    code: &'a str,
+    // 次に復号する文字のバイト位置
+    offset: usize,
     loc: Loc,
+    peeked: Option<Option<(Loc, (usize, char))>>,
+    // 最後に読み進めた文字の直後のバイト位置
+    pos: usize,
+    // 最後に読み進めた文字の位置
+    last_loc: Loc,
 }
 
 impl<'a> LocIter<'a> {
-    fn new(iter: CharIndices<'a>) -> Self {
+    fn new(code: &'a str) -> Self {
         LocIter {
-            iter,
+            code,
+            offset: 0,
             loc: Loc { row: 0, col: 0 },
+            peeked: None,
+            pos: 0,
+            last_loc: Loc { row: 0, col: 0 },
+        }
+    }
+
+    fn peek(&mut self) -> Option<&(Loc, (usize, char))> {
+        if self.peeked.is_none() {
+            self.peeked = Some(self.next_spliced());
+        }
+
+        self.peeked.as_ref().unwrap().as_ref()
+    }
+
+    fn pos(&self) -> usize {
+        self.pos
+    }
+
+    fn last_loc(&self) -> Loc {
+        self.last_loc
+    }
+
+    fn next_spliced(&mut self) -> Option<(Loc, (usize, char))> {
+        let bytes = self.code.as_bytes();
+
+        loop {
+            let i = self.offset;
+            let b = *bytes.get(i)?;
+
+            if b == b'\\' && bytes.get(i + 1) == Some(&b'\n') {
+                // 行連結なので改行と共に読み飛ばす
+                self.offset += 2;
+                self.loc.row += 1;
+                self.loc.col = 0;
+                continue;
+            }
+
+            let c = if b.is_ascii() {
+                b as char
+            } else {
+                self.code[i..].chars().next().unwrap()
+            };
+            self.offset += c.len_utf8();
+
+            let ret_loc = self.loc;
+            if c == '\n' {
+                self.loc.row += 1;
+                self.loc.col = 0;
+            } else {
+                self.loc.col += 1;
+            }
+
+            return Some((ret_loc, (i, c)));
         }
     }
 }
@@ -60,31 +208,72 @@ impl<'a> LocIter<'a> {
 impl<'a> Iterator for LocIter<'a> {
     type Item = (Loc, (usize, char));
     fn next(&mut self) -> Option<Self::Item> {
-        match self.iter.next() {
-            Some(elem) => {
-                let ret_loc = self.loc;
+        let elem = match self.peeked.take() {
+            Some(elem) => elem,
+            None => self.next_spliced(),
+        };
 
-                if elem.1 == '\n' {
-                    self.loc.row += 1;
-                    self.loc.col = 0;
-                } else {
-                    self.loc.col += 1;
-                }
+        if let Some((loc, (i, c))) = elem {
+            self.pos = i + c.len_utf8();
+            self.last_loc = loc;
+        }
 
-                Some((ret_loc, elem))
-            }
-            None => None,
+        elem
+    }
+}
+
+// ソース中の範囲から行連結を取り除いた文字列を返す。
+// 行連結を含まない場合は複製しない。
+fn splice(s: &str) -> Cow<'_, str> {
+    if s.contains("\\\n") {
+        Cow::Owned(s.replace("\\\n", ""))
+    } else {
+        Cow::Borrowed(s)
+    }
+}
+
+// 識別子のような短い文字列向けのFNV-1aハッシュ。
+// 標準のSipHashはこの用途では遅い。
+struct FnvHasher(u64);
+
+impl Default for FnvHasher {
+    fn default() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Hasher for FnvHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for b in bytes {
+            self.0 = (self.0 ^ *b as u64).wrapping_mul(0x100_0000_01b3);
         }
     }
 }
 
-trait CharIndicesExt<'a> {
-    fn loc_iter(self) -> LocIter<'a>;
+// 同じ綴りのトークン文字列を1つの文字列で共有する
+struct Interner {
+    strs: HashSet<Rc<str>, BuildHasherDefault<FnvHasher>>,
 }
 
-impl<'a> CharIndicesExt<'a> for CharIndices<'a> {
-    fn loc_iter(self) -> LocIter<'a> {
-        LocIter::new(self)
+impl Interner {
+    fn new() -> Self {
+        Self {
+            strs: HashSet::default(),
+        }
+    }
+
+    fn intern(&mut self, s: &str) -> Rc<str> {
+        if let Some(interned) = self.strs.get(s) {
+            return Rc::clone(interned);
+        }
+
+        let interned: Rc<str> = Rc::from(s);
+        self.strs.insert(Rc::clone(&interned));
+        interned
     }
 }
 
@@ -102,8 +291,14 @@ pub fn debug_print(token: &[Rc<Token>]) {
             TokenKind::Punctuator => format!("PUNCT: {}", t_str),
             TokenKind::Ident => format!("IDENT: {}", t_str),
             TokenKind::Keyword => format!("KEYWD: {}", t_str),
-            TokenKind::Num(n) => format!("NUMBR: {} => {}", t_str, n),
-            TokenKind::Str(_) => format!("STRNG: {}", t_str),
+            TokenKind::Num(n, _) => format!("NUMBR: {} => {}", t_str, n),
+            TokenKind::Float(f, _) => format!("FLOAT: {} => {}", t_str, f),
+            TokenKind::PPNumber => format!("PPNUM: {}", t_str),
+            TokenKind::Str(..) => format!("STRNG: {}", t_str),
+            TokenKind::EmbedByte(b, file, offset) => {
+                format!("EMBED: {} => {}+{}", b, file.path, offset)
+            }
+            TokenKind::Pack(_) => format!("PRAGM: {}", t_str),
             TokenKind::LF => "<LF>".to_string(),
             TokenKind::EOF => "<EOF>".to_string(),
         };
@@ -114,93 +309,486 @@ pub fn debug_print(token: &[Rc<Token>]) {
     }
 }
 
-fn is_punctuator(test_op: &str) -> bool {
-    let symbols = [
-        "==", "!=", "<", "<=", ">", ">=", "+", "-", "*", "/", "(", ")", ";", "{", "}", "&", ",",
-        "[", "]", ".", "->", "#",
-    ];
-
-    for symbol in &symbols {
-        if symbol.starts_with(test_op) {
-            return true;
-        }
-    }
+// sの先頭にあるC17の区切り子を最長一致で探し、読み込む文字数と綴りを返す。
+// ダイグラフは対応する記号の綴りに置き換える。
+fn read_punctuator(s: &[u8]) -> Option<(usize, &'static str)> {
+    let punct = match s {
+        [b'%', b':', b'%', b':', ..] => (4, "##"),
+        [b'.', b'.', b'.', ..] => (3, "..."),
+        [b'<', b'<', b'=', ..] => (3, "<<="),
+        [b'>', b'>', b'=', ..] => (3, ">>="),
+        [b'-', b'>', ..] => (2, "->"),
+        [b'+', b'+', ..] => (2, "++"),
+        [b'-', b'-', ..] => (2, "--"),
+        [b'<', b'<', ..] => (2, "<<"),
+        [b'>', b'>', ..] => (2, ">>"),
+        [b'<', b'=', ..] => (2, "<="),
+        [b'>', b'=', ..] => (2, ">="),
+        [b'=', b'=', ..] => (2, "=="),
+        [b'!', b'=', ..] => (2, "!="),
+        [b'&', b'&', ..] => (2, "&&"),
+        [b'|', b'|', ..] => (2, "||"),
+        [b'*', b'=', ..] => (2, "*="),
+        [b'/', b'=', ..] => (2, "/="),
+        [b'%', b'=', ..] => (2, "%="),
+        [b'+', b'=', ..] => (2, "+="),
+        [b'-', b'=', ..] => (2, "-="),
+        [b'&', b'=', ..] => (2, "&="),
+        [b'^', b'=', ..] => (2, "^="),
+        [b'|', b'=', ..] => (2, "|="),
+        [b'#', b'#', ..] => (2, "##"),
+        [b'<', b':', ..] => (2, "["),
+        [b':', b'>', ..] => (2, "]"),
+        [b'<', b'%', ..] => (2, "{"),
+        [b'%', b'>', ..] => (2, "}"),
+        [b'%', b':', ..] => (2, "#"),
+        [b'[', ..] => (1, "["),
+        [b']', ..] => (1, "]"),
+        [b'(', ..] => (1, "("),
+        [b')', ..] => (1, ")"),
+        [b'{', ..] => (1, "{"),
+        [b'}', ..] => (1, "}"),
+        [b'.', ..] => (1, "."),
+        [b'&', ..] => (1, "&"),
+        [b'*', ..] => (1, "*"),
+        [b'+', ..] => (1, "+"),
+        [b'-', ..] => (1, "-"),
+        [b'~', ..] => (1, "~"),
+        [b'!', ..] => (1, "!"),
+        [b'/', ..] => (1, "/"),
+        [b'%', ..] => (1, "%"),
+        [b'<', ..] => (1, "<"),
+        [b'>', ..] => (1, ">"),
+        [b'^', ..] => (1, "^"),
+        [b'|', ..] => (1, "|"),
+        [b'?', ..] => (1, "?"),
+        [b':', ..] => (1, ":"),
+        [b';', ..] => (1, ";"),
+        [b'=', ..] => (1, "="),
+        [b',', ..] => (1, ","),
+        [b'#', ..] => (1, "#"),
+        _ => return None,
+    };
+
+    Some(punct)
+}
 
-    false
+// 行連結を読み飛ばして、cとそれに続く最大3文字のASCII文字を返す
+fn spliced_head(c: char, src_iter: &LocIter) -> Vec<u8> {
+    let mut head = vec![c as u8];
+    let rest = src_iter.clone().take(3).map(|(_, (_, c))| c);
+    head.extend(rest.take_while(char::is_ascii).map(|c| c as u8));
+    head
 }
 
+// ASCII以外はC23と同じくUnicodeのXID_Start/XID_Continueに従う
 fn is_ident_1(c: char) -> bool {
-    ('a'..='z').contains(&c) || ('A'..='Z').contains(&c) || '_' == c
+    if c.is_ascii() {
+        c.is_ascii_alphabetic() || c == '_'
+    } else {
+        is_xid_start(c)
+    }
 }
 
 fn is_ident_2(c: char) -> bool {
-    is_ident_1(c) || ('0'..='9').contains(&c)
+    if c.is_ascii() {
+        c.is_ascii_alphanumeric() || c == '_'
+    } else {
+        is_xid_continue(c)
+    }
 }
 
-fn is_keyword(s: &str) -> bool {
-    let keywords = [
-        "return", "if", "else", "for", "while", "int", "char", "sizeof", "struct", "union",
+// 次の文字がユニバーサル文字名の開始を示すuもしくはUならtrueを返す。
+// \はすでに読み進めていること。
+fn next_is_ucn(src_iter: &mut LocIter) -> bool {
+    matches!(src_iter.peek(), Some((_, (_, 'u'))) | Some((_, (_, 'U'))))
+}
+
+fn next_is_digit(src_iter: &mut LocIter) -> bool {
+    matches!(src_iter.peek(), Some((_, (_, c))) if c.is_ascii_digit())
+}
+
+// 前処理数(pp-number)を構成する文字のうち、prevに続けられる文字ならtrueを返す。
+// 桁区切りの'は後続の文字も見て判断する。
+fn is_pp_number_char(prev: char, c: char, src_iter: &LocIter) -> bool {
+    match c {
+        _ if is_ident_2(c) || c == '.' => true,
+        // 指数部の符号
+        '+' | '-' => "eEpP".contains(prev),
+        '\'' => {
+            let mut iter = src_iter.clone();
+            iter.next();
+            matches!(iter.peek(), Some((_, (_, c))) if is_ident_2(*c))
+        }
+        _ => false,
+    }
+}
+
+// 整数定数の値と型を返す。
+// 型は接尾辞と基数で決まる候補のうち、値を表現できる最初のものとする。
+fn read_int_literal(s: &str) -> Result<(isize, IntType), &'static str> {
+    const ERROR_INVALID: &str = "不正な整数定数です";
+
+    let (radix, body) = if s.starts_with("0x") || s.starts_with("0X") {
+        (16, &s[2..])
+    } else if s.starts_with("0b") || s.starts_with("0B") {
+        (2, &s[2..])
+    } else if s.starts_with('0') {
+        (8, s)
+    } else {
+        (10, s)
+    };
+
+    let digits_len = body
+        .find(|c: char| !(c.is_digit(radix) || c == '\''))
+        .unwrap_or(body.len());
+    let (digits, suffix) = body.split_at(digits_len);
+
+    // 桁区切りは数字の間にしか置けない
+    if digits.is_empty() || digits.starts_with('\'') || digits.ends_with('\'') {
+        return Err(ERROR_INVALID);
+    }
+    if digits.contains("''") {
+        return Err(ERROR_INVALID);
+    }
+
+    let (unsigned, long) = match suffix {
+        "" => (false, 0),
+        "u" | "U" => (true, 0),
+        "l" | "L" => (false, 1),
+        "ul" | "uL" | "Ul" | "UL" | "lu" | "lU" | "Lu" | "LU" => (true, 1),
+        "ll" | "LL" => (false, 2),
+        "ull" | "uLL" | "Ull" | "ULL" | "llu" | "llU" | "LLu" | "LLU" => (true, 2),
+        _ => return Err(ERROR_INVALID),
+    };
+
+    let value = match u64::from_str_radix(&digits.replace('\'', ""), radix) {
+        Ok(value) => value,
+        Err(_) => return Err("整数定数が大きすぎます"),
+    };
+
+    // 10進数で接尾辞にuがなければ符号付きの型のみが候補となる
+    let candidates = [
+        IntType::Int,
+        IntType::UInt,
+        IntType::Long,
+        IntType::ULong,
+        IntType::LongLong,
+        IntType::ULongLong,
     ];
+    let ctype = candidates.iter().skip(long * 2).find(|t| {
+        (t.is_unsigned() || !unsigned)
+            && (!t.is_unsigned() || unsigned || radix != 10)
+            && value <= t.max()
+    });
+
+    match ctype {
+        Some(ctype) => Ok((value as isize, *ctype)),
+        None => Err("整数定数が大きすぎます"),
+    }
+}
+
+// 16進数以外で小数点か指数部があるか、16進数で指数部があれば浮動小数点定数とみなす
+// pp-numberを整数定数もしくは浮動小数点定数として読む
+pub fn read_number(s: &str) -> Result<TokenKind, &'static str> {
+    if is_float_literal(s) {
+        read_float_literal(s).map(|(f, ctype)| TokenKind::Float(f, ctype))
+    } else {
+        read_int_literal(s).map(|(n, ctype)| TokenKind::Num(n, ctype))
+    }
+}
+
+// 16進数では指数が必須だが、指数の無い0x1.8も不正な浮動小数点定数として扱う
+fn is_float_literal(s: &str) -> bool {
+    if s.starts_with("0x") || s.starts_with("0X") {
+        s.contains(&['.', 'p', 'P'][..])
+    } else {
+        s.contains(&['.', 'e', 'E'][..])
+    }
+}
+
+// 浮動小数点定数の値と型を返す
+fn read_float_literal(s: &str) -> Result<(f64, FloatType), &'static str> {
+    const ERROR_INVALID: &str = "不正な浮動小数点定数です";
 
-    for keyword in &keywords {
-        if s == *keyword {
-            return true;
+    let (body, ctype) = if s.ends_with('f') || s.ends_with('F') {
+        (&s[..s.len() - 1], FloatType::Float)
+    } else if s.ends_with('l') || s.ends_with('L') {
+        (&s[..s.len() - 1], FloatType::LongDouble)
+    } else {
+        (s, FloatType::Double)
+    };
+
+    let hex = body.starts_with("0x") || body.starts_with("0X");
+    let radix = if hex { 16 } else { 10 };
+
+    // 桁区切りは数字の間にしか置けない
+    let chars: Vec<char> = body.chars().collect();
+    for (i, c) in chars.iter().enumerate() {
+        if *c == '\''
+            && !(i > 0
+                && chars[i - 1].is_digit(radix)
+                && i + 1 < chars.len()
+                && chars[i + 1].is_digit(radix))
+        {
+            return Err(ERROR_INVALID);
         }
     }
+    let body = body.replace('\'', "");
+
+    let value = if hex {
+        read_hex_float(&body[2..], ctype)
+    } else if body.starts_with(|c: char| c.is_ascii_digit() || c == '.')
+        && body.chars().all(|c| "0123456789.eE+-".contains(c))
+    {
+        // Rustの変換は正しく丸められるので、floatはf32として変換する
+        match ctype {
+            FloatType::Float => body.parse::<f32>().ok().map(|f| f as f64),
+            FloatType::Double => body.parse::<f64>().ok(),
+            // long doubleは拡張倍精度に丸めた値をdoubleに丸める
+            FloatType::LongDouble => read_long_double(&body).map(|f| f.to_f64()),
+        }
+    } else {
+        None
+    };
 
-    false
+    match value {
+        Some(value) => Ok((value, ctype)),
+        None => Err(ERROR_INVALID),
+    }
 }
 
-fn push_char_as_u8(v: &mut Vec<u8>, c: char) {
-    let mut buf = [0; 4];
-    let u8_s = c.encode_utf8(&mut buf);
-    for b in u8_s.bytes() {
-        v.push(b);
+// 0xを除いた16進浮動小数点定数の仮数と指数を返す。
+// 仮数は124ビットまで保持し、あふれた分は最下位ビット(sticky bit)に集約する。
+fn scan_hex_float(s: &str) -> Option<(u128, i64)> {
+    let (mantissa, exp) = s.split_at(s.find(&['p', 'P'][..])?);
+    let exp = &exp[1..];
+
+    let (int_part, frac_part) = match mantissa.find('.') {
+        Some(pos) => (&mantissa[..pos], &mantissa[pos + 1..]),
+        None => (mantissa, ""),
+    };
+
+    if int_part.is_empty() && frac_part.is_empty() {
+        return None;
+    }
+
+    let exp_digits = exp.strip_prefix(|c| c == '+' || c == '-').unwrap_or(exp);
+    if exp_digits.is_empty() || !exp_digits.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    // 桁数が多すぎる指数は飽和させる
+    let mut e = exp_digits.parse::<i64>().unwrap_or(i64::MAX / 2);
+    if exp.starts_with('-') {
+        e = -e;
+    }
+
+    let mut m: u128 = 0;
+    let mut sticky = false;
+    for (i, c) in int_part.chars().chain(frac_part.chars()).enumerate() {
+        let d = c.to_digit(16)? as u128;
+        if m >> 120 == 0 {
+            m = m << 4 | d;
+            if i >= int_part.len() {
+                e -= 4;
+            }
+        } else {
+            sticky |= d != 0;
+            if i < int_part.len() {
+                e += 4;
+            }
+        }
     }
+    if sticky {
+        m |= 1;
+    }
+
+    Some((m, e))
 }
 
-fn read_oct_escape_sequence(src_iter: &mut Peekable<LocIter>) -> Option<(u8, usize)> {
-    const DIGITS: [char; 8] = ['0', '1', '2', '3', '4', '5', '6', '7'];
+// 0xを除いた16進浮動小数点定数を型の精度に丸めて返す
+fn read_hex_float(s: &str, ctype: FloatType) -> Option<f64> {
+    let (m, e) = scan_hex_float(s)?;
 
-    read_num_escape_sequence(src_iter, 8, &DIGITS, Some(3))
+    if m == 0 {
+        return Some(0.0);
+    }
+
+    // 非正規化数になる場合は表現できる桁数が減るので、その桁数で丸める
+    let (precision, min_lsb_exp) = match ctype {
+        FloatType::Float => (24, -149),
+        FloatType::Double => (53, -1074),
+        // long doubleは拡張倍精度に丸めた値をdoubleに丸める
+        FloatType::LongDouble => return Some(F80::from_parts(false, m, e, false).to_f64()),
+    };
+    // どの型でも0か無限大になる指数は、丸めの計算があふれないように制限する
+    let e = e.clamp(-100000, 100000);
+    let (m, e) = round_bits(m, e, false, precision, min_lsb_exp);
+
+    // 仮数は型の精度に収まっているので、2のべき乗を掛けても丸めは起きない。
+    // 指数が大きすぎる場合は無限大になるので、ループが長くならないよう制限する。
+    let mut value = m as f64;
+    let mut e = e.min(4096);
+    while e > 0 {
+        let step = e.min(1000);
+        value *= 2f64.powi(step as i32);
+        e -= step;
+    }
+    while e < 0 {
+        let step = (-e).min(1000);
+        value /= 2f64.powi(step as i32);
+        e += step;
+    }
+
+    match ctype {
+        FloatType::Float => Some(value as f32 as f64),
+        _ => Some(value),
+    }
 }
 
-fn read_hex_escape_sequence(src_iter: &mut Peekable<LocIter>) -> Option<(u8, usize)> {
-    const DIGITS: [char; 22] = [
-        '0', '1', '2', '3', '4', '5', '6', '7', '8', '9', 'a', 'b', 'c', 'd', 'e', 'f', 'A', 'B',
-        'C', 'D', 'E', 'F',
-    ];
+// long doubleの浮動小数点定数を拡張倍精度に変換する。
+// sには接尾辞と桁区切りを含めない。
+pub fn read_long_double(s: &str) -> Option<F80> {
+    if s.starts_with("0x") || s.starts_with("0X") {
+        let (m, e) = scan_hex_float(&s[2..])?;
+        Some(F80::from_parts(false, m, e, false))
+    } else if s.starts_with(|c: char| c.is_ascii_digit() || c == '.') {
+        F80::from_decimal(s)
+    } else {
+        None
+    }
+}
+
+fn is_keyword(s: &str) -> bool {
+    matches!(
+        s,
+        "return"
+            | "if"
+            | "else"
+            | "for"
+            | "while"
+            | "void"
+            | "_Bool"
+            | "bool"
+            | "true"
+            | "false"
+            | "int"
+            | "char"
+            | "short"
+            | "long"
+            | "signed"
+            | "unsigned"
+            | "float"
+            | "double"
+            | "sizeof"
+            | "struct"
+            | "union"
+            | "enum"
+            | "typedef"
+            | "extern"
+            | "static"
+            | "register"
+            | "inline"
+            | "_Noreturn"
+            | "const"
+            | "volatile"
+            | "restrict"
+    )
+}
 
-    read_num_escape_sequence(src_iter, 16, &DIGITS, None)
+// 文字列リテラルと文字定数の接頭辞で決まる符号化方式
+#[derive(Clone, Copy, PartialEq)]
+pub enum Encoding {
+    // 接頭辞なし
+    Char,
+    // u8
+    Utf8,
+    // u
+    Utf16,
+    // U
+    Utf32,
+    // L
+    Wide,
 }
 
-fn read_num_escape_sequence(
-    src_iter: &mut Peekable<LocIter>,
-    radix: u32,
-    digits: &[char],
-    max_digits: Option<usize>,
-) -> Option<(u8, usize)> {
-    let mut nr_read_bytes = 0;
+impl Encoding {
+    fn from_prefix(prefix: &str) -> Option<Self> {
+        match prefix {
+            "u8" => Some(Self::Utf8),
+            "u" => Some(Self::Utf16),
+            "U" => Some(Self::Utf32),
+            "L" => Some(Self::Wide),
+            _ => None,
+        }
+    }
 
-    let mut s = String::new();
+    // 1要素のバイト数
+    pub fn unit_size(&self) -> usize {
+        match self {
+            Self::Char | Self::Utf8 => 1,
+            Self::Utf16 => 2,
+            Self::Utf32 | Self::Wide => 4,
+        }
+    }
 
-    // 最初にhexadecimal-escape-sequenceを示す'x'があれば読み捨てる
-    if let Some((_, (_, c))) = src_iter.peek() {
-        if *c == 'x' {
-            nr_read_bytes += c.len_utf8();
-            src_iter.next();
+    // 1要素で表現できる最大値
+    fn max_unit(&self) -> u32 {
+        match self {
+            Self::Char | Self::Utf8 => 0xff,
+            Self::Utf16 => 0xffff,
+            Self::Utf32 | Self::Wide => 0xffff_ffff,
+        }
+    }
+
+    // 文字を符号化して要素として追加する
+    fn push_char(&self, units: &mut Vec<u32>, c: char) {
+        match self {
+            Self::Char | Self::Utf8 => {
+                let mut buf = [0; 4];
+                units.extend(c.encode_utf8(&mut buf).bytes().map(|b| b as u32));
+            }
+            Self::Utf16 => {
+                let mut buf = [0; 2];
+                units.extend(c.encode_utf16(&mut buf).iter().map(|u| *u as u32));
+            }
+            Self::Utf32 | Self::Wide => units.push(c as u32),
         }
-    } else {
-        return None;
     }
 
+    // 要素をリトルエンディアンのバイト列にする
+    fn encode(&self, units: &[u32]) -> Vec<u8> {
+        let size = self.unit_size();
+        units
+            .iter()
+            .flat_map(|u| u.to_le_bytes()[..size].to_vec())
+            .collect()
+    }
+}
+
+fn read_oct_escape_sequence(src_iter: &mut LocIter, max: u32) -> Option<u32> {
+    read_num_escape_sequence(src_iter, 8, Some(3), max)
+}
+
+fn read_hex_escape_sequence(src_iter: &mut LocIter, max: u32) -> Option<u32> {
+    // hexadecimal-escape-sequenceを示す'x'を読み捨てる
+    src_iter.next();
+
+    read_num_escape_sequence(src_iter, 16, None, max)
+}
+
+fn read_num_escape_sequence(
+    src_iter: &mut LocIter,
+    radix: u32,
+    max_digits: Option<usize>,
+    max: u32,
+) -> Option<u32> {
+    let mut num: u64 = 0;
     let mut nr_read_char = 0;
 
     loop {
         if let Some((_, (_, c))) = src_iter.peek() {
-            if digits.contains(c) {
-                s.push(*c);
-                nr_read_bytes += c.len_utf8();
+            if let Some(d) = c.to_digit(radix) {
+                num = num.saturating_mul(radix as u64).saturating_add(d as u64);
                 nr_read_char += 1;
                 src_iter.next();
             } else {
@@ -217,24 +805,39 @@ fn read_num_escape_sequence(
         }
     }
 
-    let num = isize::from_str_radix(&s, radix).unwrap();
-
-    // 1バイトで表現できない場合の値は処理系定義。
+    // 1要素で表現できない場合の値は処理系定義。
     // GCC/Clangでは-pedantic-errorsオプションが
     // つけられた時はコンパイルエラーにしている。
     // そちらのほうがいいかもしれないが、エラーを
-    // 返すのが面倒なので、とりあえず0から255で
+    // 返すのが面倒なので、とりあえず要素の最大値で
     // clampして返すことにする。
-    if num < 0 {
-        Some((0, nr_read_bytes))
-    } else if num > 255 {
-        Some((255, nr_read_bytes))
-    } else {
-        Some((num as u8, nr_read_bytes))
+    Some(num.min(max as u64) as u32)
+}
+
+// \uXXXXもしくは\UXXXXXXXXで表される文字を返す
+fn read_universal_character_name(src_iter: &mut LocIter) -> Result<char, &'static str> {
+    const ERROR_INVALID: &str = "不正なユニバーサル文字名です";
+
+    let len = match src_iter.next() {
+        Some((_, (_, 'u'))) => 4,
+        _ => 8,
+    };
+
+    let mut code = 0;
+    for _ in 0..len {
+        match src_iter.peek().and_then(|(_, (_, c))| c.to_digit(16)) {
+            Some(d) => {
+                code = code * 16 + d;
+                src_iter.next();
+            }
+            None => return Err(ERROR_INVALID),
+        }
     }
+
+    std::char::from_u32(code).ok_or(ERROR_INVALID)
 }
 
-fn is_comment(src_iter: &mut Peekable<LocIter>, first: char, second: char) -> bool {
+fn is_comment(src_iter: &mut LocIter, first: char, second: char) -> bool {
     if let Some((_, (_, c))) = src_iter.peek() {
         let b = (first == '/') && (*c == second);
         if b {
@@ -246,253 +849,363 @@ fn is_comment(src_iter: &mut Peekable<LocIter>, first: char, second: char) -> bo
     }
 }
 
-fn read_string(src_iter: &mut Peekable<LocIter>, terminator: char) -> Option<(Vec<u8>, usize)> {
-    const ESCAPE_SEQUENCES: [(char, u8); 12] = [
-        ('\'', b'\''),
-        ('\"', b'"'),
-        ('?', b'?'),
-        ('\\', b'\\'),
+// 終端文字までを読み込み、符号化方式に従った要素の列を返す
+fn read_string(
+    src_iter: &mut LocIter,
+    terminator: char,
+    enc: Encoding,
+) -> Result<Vec<u32>, &'static str> {
+    const ERROR_UNTERMINATED: &str = "終端されていません";
+    const ESCAPE_SEQUENCES: [(char, u32); 12] = [
+        ('\'', b'\'' as u32),
+        ('\"', b'"' as u32),
+        ('?', b'?' as u32),
+        ('\\', b'\\' as u32),
         ('a', 7),
         ('b', 8),
         ('f', 12),
-        ('n', b'\n'),
-        ('r', b'\r'),
-        ('t', b'\t'),
-        ('v', 8),
+        ('n', b'\n' as u32),
+        ('r', b'\r' as u32),
+        ('t', b'\t' as u32),
+        ('v', 11),
         ('e', 27),
     ];
 
-    let mut bytes = Vec::new();
-    // 文字列リテラル開始から終端までに読んだバイト数
-    let mut nr_read_bytes = 0;
-
-    let mut is_terminated = false;
+    let mut units = Vec::new();
 
     while let Some((_, (_, c))) = src_iter.next() {
         match c {
             // 終端文字
-            _ if c == terminator => is_terminated = true,
+            _ if c == terminator => return Ok(units),
             // エスケープシーケンス
             '\\' => {
-                if let Some((_, (_, c))) = src_iter.peek() {
-                    if let Some(e) = ESCAPE_SEQUENCES.iter().find(|e| e.0 == *c) {
-                        // simple-escape-sequence
-                        bytes.push(e.1);
-                        nr_read_bytes += c.len_utf8();
-                        src_iter.next();
-                    } else if ('0'..='9').contains(c) || *c == 'x' {
-                        // octal-escape-sequenceもしくはhexadecimal-escape-sequence
-                        let ret = if *c == 'x' {
-                            read_hex_escape_sequence(src_iter)
-                        } else {
-                            read_oct_escape_sequence(src_iter)
-                        };
+                let c = match src_iter.peek() {
+                    Some((_, (_, c))) => *c,
+                    None => break,
+                };
 
-                        if let Some((c, add_bytes)) = ret {
-                            bytes.push(c);
-                            nr_read_bytes += add_bytes;
-                        } else {
-                            break;
-                        }
+                if let Some(e) = ESCAPE_SEQUENCES.iter().find(|e| e.0 == c) {
+                    // simple-escape-sequence
+                    units.push(e.1);
+                    src_iter.next();
+                } else if c == 'u' || c == 'U' {
+                    // universal-character-name
+                    let c = read_universal_character_name(src_iter)?;
+                    enc.push_char(&mut units, c);
+                } else if ('0'..='7').contains(&c) || c == 'x' {
+                    // octal-escape-sequenceもしくはhexadecimal-escape-sequence
+                    let ret = if c == 'x' {
+                        read_hex_escape_sequence(src_iter, enc.max_unit())
                     } else {
-                        push_char_as_u8(&mut bytes, *c);
-                        nr_read_bytes += c.len_utf8();
-                        src_iter.next();
+                        read_oct_escape_sequence(src_iter, enc.max_unit())
+                    };
+
+                    match ret {
+                        Some(u) => units.push(u),
+                        None => break,
                     }
                 } else {
-                    break;
+                    enc.push_char(&mut units, c);
+                    src_iter.next();
                 }
             }
             // その他の文字
-            _ => push_char_as_u8(&mut bytes, c),
+            _ => enc.push_char(&mut units, c),
         }
+    }
 
-        nr_read_bytes += c.len_utf8();
+    Err(ERROR_UNTERMINATED)
+}
 
-        if is_terminated {
-            break;
-        }
-    }
+// 文字列リテラルのトークン文字列を指定した符号化方式で読み直し、
+// 終端のnulを含むバイト列を返す。
+// 接頭辞の異なる文字列リテラルを連結するときに使う。
+pub fn reencode_string(token_str: &str, enc: Encoding) -> Vec<u8> {
+    let start = token_str.find('"').unwrap() + 1;
+    let mut src_iter = LocIter::new(&token_str[start..]);
 
-    if is_terminated {
-        Some((bytes, nr_read_bytes))
-    } else {
-        None
+    let mut units = read_string(&mut src_iter, '"', enc).unwrap();
+    units.push(0);
+
+    enc.encode(&units)
+}
+
+// 接頭辞(あれば)と開き引用符を読み込んだ後の文字列リテラルを読む
+fn read_string_literal(src_iter: &mut LocIter, enc: Encoding) -> Result<TokenKind, &'static str> {
+    let mut units = read_string(src_iter, '"', enc)?;
+    units.push(0);
+
+    Ok(TokenKind::Str(enc.encode(&units), enc))
+}
+
+// 接頭辞(あれば)と開き引用符を読み込んだ後の文字定数を読む。
+// 複数文字の文字定数は警告を出力し、gccと同じ値とする。
+fn read_char_literal(
+    src_iter: &mut LocIter,
+    enc: Encoding,
+    src: &Source,
+    loc: Loc,
+) -> Result<TokenKind, &'static str> {
+    const WARN_MULTICHAR: &str = "複数文字の文字定数です";
+    const WARN_TOO_LONG: &str = "文字定数が長すぎます";
+
+    let units = read_string(src_iter, '\'', enc)?;
+
+    if units.is_empty() {
+        return Err("空の文字定数です");
     }
+
+    let warn = |msg: &str| {
+        warn_at!(src, loc, "{}", msg);
+    };
+
+    let n = match enc {
+        Encoding::Char => {
+            if units.len() == 1 {
+                // charは符号付き
+                (units[0] as u8 as i8) as isize
+            } else {
+                // 各バイトを上位から並べ、intの幅に切り詰める
+                if units.len() > 4 {
+                    warn(WARN_TOO_LONG);
+                } else {
+                    warn(WARN_MULTICHAR);
+                }
+                let n = units.iter().fold(0u32, |n, u| (n << 8) | u);
+                (n as i32) as isize
+            }
+        }
+        _ => {
+            // 接頭辞付きの場合は最後の要素を値とする。
+            // char16_tとunsigned charは汎整数拡張でintになる。
+            if units.len() > 1 {
+                warn(WARN_TOO_LONG);
+            }
+            let u = *units.last().unwrap();
+            match enc {
+                Encoding::Wide => (u as i32) as isize,
+                _ => u as isize,
+            }
+        }
+    };
+    let ctype = match enc {
+        Encoding::Utf32 => IntType::UInt,
+        _ => IntType::Int,
+    };
+
+    Ok(TokenKind::Num(n, ctype))
 }
 
 pub fn tokenize(src: Rc<Source>) -> Vec<Rc<Token>> {
     let mut token = Vec::new();
-    let mut src_iter = src.code.char_indices().loc_iter().peekable();
+    let mut src_iter = LocIter::new(&src.code);
+    let mut interner = Interner::new();
+
+    let new_token = |token_str, loc, kind| {
+        Rc::new(Token {
+            common: TokenCommon {
+                token_str,
+                src: Rc::clone(&src),
+                loc,
+                line_marker: None,
+            },
+            kind,
+        })
+    };
 
     while let Some((loc, (byte_s, c))) = src_iter.next() {
-        let mut byte_e = byte_s + c.len_utf8();
-
         match c {
+            // 空白文字をスキップ
+            ' ' | '\t' | '\r' | '\x0b' | '\x0c' => (),
+
+            // 改行
+            '\n' => {
+                token.push(new_token(interner.intern("\n"), loc, TokenKind::LF));
+            }
+
             // 数値
-            '0'..='9' => {
+            _ if c.is_ascii_digit() || (c == '.' && next_is_digit(&mut src_iter)) => {
+                let mut prev = c;
                 while let Some((_, (_, c))) = src_iter.peek() {
-                    if ('0'..='9').contains(c) {
-                        byte_e += c.len_utf8();
+                    let c = *c;
+                    if is_pp_number_char(prev, c, &src_iter) {
                         src_iter.next();
+                        prev = c;
                     } else {
                         break;
                     }
                 }
 
-                let token_str = src.code[byte_s..byte_e].to_string();
-                let n = token_str.parse::<isize>().unwrap();
-
-                token.push(Rc::new(Token {
-                    common: TokenCommon {
-                        token_str,
-                        src: Rc::clone(&src),
-                        loc,
-                    },
-                    kind: TokenKind::Num(n),
-                }));
+                let token_str = splice(&src.code[byte_s..src_iter.pos()]);
+                // 数値として読めないpp-numberも、#includeのファイル名や読み飛ばす節に
+                // 現れることがあるので、エラーは構文解析で使われるときに報告する
+                let kind = read_number(&token_str).unwrap_or(TokenKind::PPNumber);
+
+                token.push(new_token(Rc::from(token_str), loc, kind));
             }
 
-            // 文字
-            '\'' => {
-                if let Some((string, nr_read_bytes)) = read_string(&mut src_iter, '\'') {
-                    byte_e += nr_read_bytes;
-                    let token_str = src.code[byte_s..byte_e].to_string();
+            // 文字定数と文字列リテラル
+            '\'' | '"' => {
+                let ret = if c == '"' {
+                    read_string_literal(&mut src_iter, Encoding::Char)
+                } else {
+                    read_char_literal(&mut src_iter, Encoding::Char, &src, loc)
+                };
 
-                    if string.is_empty() {
-                        error_at!(src, loc, "空の文字定数です");
+                let kind = match ret {
+                    Ok(kind) => kind,
+                    Err(msg) => {
+                        error_at!(src, loc, msg);
                     }
+                };
 
-                    // 1バイトで表現できない場合の値は処理系定義。
-                    // はじめの1バイトを返すこととする。
-                    let n = i8::from_ne_bytes([string[0]]) as isize;
-
-                    token.push(Rc::new(Token {
-                        common: TokenCommon {
-                            token_str,
-                            src: Rc::clone(&src),
-                            loc,
-                        },
-                        kind: TokenKind::Num(n),
-                    }));
-                } else {
-                    error_at!(src, loc, "終端されていません");
-                }
+                let token_str = splice(&src.code[byte_s..src_iter.pos()]);
+                token.push(new_token(Rc::from(token_str), loc, kind));
             }
 
-            // 文字列
-            '"' => {
-                if let Some((mut string, nr_read_bytes)) = read_string(&mut src_iter, '"') {
-                    string.push(b'\0');
-
-                    byte_e += nr_read_bytes;
-                    let token_str = src.code[byte_s..byte_e].to_string();
-
-                    token.push(Rc::new(Token {
-                        common: TokenCommon {
-                            token_str,
-                            src: Rc::clone(&src),
-                            loc,
-                        },
-                        kind: TokenKind::Str(string),
-                    }));
-                } else {
-                    error_at!(src, loc, "終端されていません");
+            // 行コメント
+            // 末端の改行は改行トークンとして扱いたいので
+            // next()ではなくpeek()で読み込む。
+            '/' if is_comment(&mut src_iter, c, '/') => {
+                while let Some((_, (_, c))) = src_iter.peek() {
+                    if *c == '\n' {
+                        break;
+                    }
+                    src_iter.next();
                 }
             }
 
-            // "+", "*", ";"といった記号
-            _ if is_punctuator(&src.code[byte_s..byte_e]) => {
-                if is_comment(&mut src_iter, c, '/') {
-                    // 行コメント
-                    // 末端の改行は改行トークンとして扱いたいので
-                    // next()ではなくpeek()で読み込む。
-                    while let Some((_, (_, c))) = src_iter.peek() {
-                        if *c == '\n' {
-                            break;
-                        }
-                        src_iter.next();
+            // ブロックコメント
+            '/' if is_comment(&mut src_iter, c, '*') => {
+                let mut has_terminator = false;
+                let mut prev = ' ';
+                for (_, (_, c)) in src_iter.by_ref() {
+                    if (prev == '*') && (c == '/') {
+                        has_terminator = true;
+                        break;
                     }
-                } else if is_comment(&mut src_iter, c, '*') {
-                    // ブロックコメント
-                    let mut has_terminator = false;
-                    let mut prev = ' ';
-                    while let Some((_, (_, c))) = src_iter.next() {
-                        if (prev == '*') && (c == '/') {
-                            has_terminator = true;
-                            break;
+                    prev = c;
+                }
+
+                if !has_terminator {
+                    error_at!(src, loc, "ブロックコメントの終端が存在しません");
+                }
+            }
+
+            // 識別子とキーワード
+            _ if is_ident_1(c) || (c == '\\' && next_is_ucn(&mut src_iter)) => {
+                // ユニバーサル文字名を含む場合だけ、表す文字に置き換えた識別子を作る。
+                // それ以外はソースをそのまま切り出す。
+                let mut decoded: Option<String> = None;
+                let mut c = c;
+                let mut byte_c = byte_s;
+
+                loop {
+                    if c == '\\' {
+                        let decoded =
+                            decoded.get_or_insert_with(|| splice(&src.code[byte_s..byte_c]).into());
+                        // 基本文字集合の文字は書けない
+                        let is_start = decoded.is_empty();
+                        let valid = |u: char| {
+                            u as u32 >= 0xa0
+                                && if is_start {
+                                    is_ident_1(u)
+                                } else {
+                                    is_ident_2(u)
+                                }
+                        };
+                        match read_universal_character_name(&mut src_iter) {
+                            Ok(u) if valid(u) => decoded.push(u),
+                            _ => {
+                                error_at!(src, loc, "識別子に使えないユニバーサル文字名です");
+                            }
                         }
-                        prev = c;
+                    } else if let Some(decoded) = &mut decoded {
+                        decoded.push(c);
                     }
 
-                    if !has_terminator {
-                        error_at!(src, loc, "ブロックコメントの終端が存在しません");
-                    }
-                } else {
-                    while let Some((_, (_, c))) = src_iter.peek() {
-                        let new_byte_e = byte_e + c.len_utf8();
-                        if is_punctuator(&src.code[byte_s..new_byte_e]) {
-                            byte_e = new_byte_e;
-                            src_iter.next();
-                        } else {
-                            break;
+                    match src_iter.peek() {
+                        Some((_, (i, next))) if is_ident_2(*next) => {
+                            c = *next;
+                            byte_c = *i;
                         }
+                        Some((_, (i, '\\'))) => {
+                            byte_c = *i;
+                            let mut iter = src_iter.clone();
+                            iter.next();
+                            if !next_is_ucn(&mut iter) {
+                                break;
+                            }
+                            c = '\\';
+                        }
+                        _ => break,
                     }
-
-                    let token_str = src.code[byte_s..byte_e].to_string();
-
-                    token.push(Rc::new(Token {
-                        common: TokenCommon {
-                            token_str,
-                            src: Rc::clone(&src),
-                            loc,
-                        },
-                        kind: TokenKind::Punctuator,
-                    }));
+                    src_iter.next();
                 }
-            }
 
-            // 識別子とキーワード
-            _ if is_ident_1(c) => {
-                while let Some((_, (_, c))) = src_iter.peek() {
-                    if is_ident_2(*c) {
-                        byte_e += c.len_utf8();
+                let token_str = match decoded {
+                    Some(decoded) => interner.intern(&decoded),
+                    None => interner.intern(&splice(&src.code[byte_s..src_iter.pos()])),
+                };
+
+                // 接頭辞付きの文字定数と文字列リテラル
+                let quote = src_iter.peek().map(|(_, (_, c))| *c);
+                if let (Some(enc), Some(quote)) = (Encoding::from_prefix(&token_str), quote) {
+                    if quote == '\'' || quote == '"' {
                         src_iter.next();
-                    } else {
-                        break;
+
+                        let ret = if quote == '"' {
+                            read_string_literal(&mut src_iter, enc)
+                        } else {
+                            read_char_literal(&mut src_iter, enc, &src, loc)
+                        };
+
+                        let kind = match ret {
+                            Ok(kind) => kind,
+                            Err(msg) => {
+                                error_at!(src, loc, msg);
+                            }
+                        };
+
+                        let token_str = splice(&src.code[byte_s..src_iter.pos()]);
+                        token.push(new_token(Rc::from(token_str), loc, kind));
+                        continue;
                     }
                 }
 
-                let token_str = src.code[byte_s..byte_e].to_string();
-
                 let kind = if is_keyword(&token_str) {
                     TokenKind::Keyword
                 } else {
                     TokenKind::Ident
                 };
-                let common = TokenCommon {
-                    token_str,
-                    src: Rc::clone(&src),
-                    loc,
-                };
 
-                token.push(Rc::new(Token { common, kind }));
+                token.push(new_token(token_str, loc, kind));
             }
 
-            // 改行
-            _ if c == '\n' => {
-                token.push(Rc::new(Token {
-                    common: TokenCommon {
-                        token_str: c.to_string(),
-                        src: Rc::clone(&src),
-                        loc,
-                    },
-                    kind: TokenKind::LF,
-                }));
-            }
+            // "+", "*", ";"といった記号。
+            // 行連結を読み飛ばした先頭の最大4文字から最長一致で探す。
+            _ if c.is_ascii() => {
+                let raw = &src.code.as_bytes()[byte_s..src.code.len().min(byte_s + 4)];
+                let spliced;
+                let head = if raw.contains(&b'\\') {
+                    spliced = spliced_head(c, &src_iter);
+                    &spliced[..]
+                } else {
+                    raw
+                };
 
-            // 空白文字をスキップ
-            _ if c.is_ascii_whitespace() => (),
+                let (n, op) = match read_punctuator(head) {
+                    Some(punct) => punct,
+                    None => {
+                        error_at!(src, loc, "トークナイズできません");
+                    }
+                };
+                for _ in 1..n {
+                    src_iter.next();
+                }
+
+                token.push(new_token(interner.intern(op), loc, TokenKind::Punctuator));
+            }
 
             _ => {
                 error_at!(src, loc, "トークナイズできません");
@@ -500,16 +1213,9 @@ pub fn tokenize(src: Rc<Source>) -> Vec<Rc<Token>> {
         }
     }
 
-    let loc = LocIter::new(src.code.char_indices()).last().unwrap().0;
-
-    token.push(Rc::new(Token {
-        common: TokenCommon {
-            token_str: String::new(),
-            src: Rc::clone(&src),
-            loc,
-        },
-        kind: TokenKind::EOF,
-    }));
+    // EOFは最後の文字の位置とする
+    let loc = src_iter.last_loc();
+    token.push(new_token(interner.intern(""), loc, TokenKind::EOF));
 
     token
 }
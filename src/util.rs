@@ -1,3 +1,40 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// usize::div_ceilはrustc 1.51には無い
+#[allow(clippy::manual_div_ceil)]
 pub fn align_to(n: usize, align: usize) -> usize {
     (n + align - 1) / align * align
 }
+
+// 現在時刻(UTC)を(年, 月, 日, 時, 分, 秒)で返す。
+// 月と日は1から始まる。
+pub fn current_datetime() -> (i64, usize, usize, usize, usize, usize) {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0);
+
+    let days = secs.div_euclid(86400);
+    let secs_of_day = secs.rem_euclid(86400) as usize;
+
+    // 1970-01-01からの日数をグレゴリオ暦の日付に変換する。
+    // 3月始まりの年として計算すると閏日が年末に来るので扱いやすい。
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as usize;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as usize;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    (
+        year,
+        month,
+        day,
+        secs_of_day / 3600,
+        secs_of_day % 3600 / 60,
+        secs_of_day % 60,
+    )
+}
@@ -20,12 +20,24 @@ run_test() {
 	src="$1"
 	echo [`basename "$src"`]
 
-	if [ "$src" != "test/preprocess.c" ]
-	then
-		gcc -xc "$src" -E -P -C | target/debug/sumorucc - >tmp.s
-	else
+	case "$src" in
+	"test/preprocess.c" | "test/float.c")
+		target/debug/sumorucc "$src" >tmp.s
+		;;
+	"test/pch.c")
+		target/debug/sumorucc -x c-header test/include/pch_src.h -o test/include/pch.h.pch
+		[ $? -ne 0 ] && error_exit 1
+		target/debug/sumorucc -x c-header test/include/pch_macro.h
+		[ $? -ne 0 ] && error_exit 1
 		target/debug/sumorucc "$src" >tmp.s
-	fi
+		;;
+	"test/gnu_compat.c" | "test/system_header.c")
+		target/debug/sumorucc -fgnu-compat "$src" >tmp.s
+		;;
+	*)
+		gcc -xc "$src" -E -P -C | target/debug/sumorucc - >tmp.s
+		;;
+	esac
 	[ $? -ne 0 ] && error_exit 1
 
 	gcc -no-pie -o tmp tmp.s "$TEST_FN_FILE"
@@ -38,6 +50,18 @@ run_test() {
 	echo
 }
 
+# コンパイルエラーになるべきソースを標準入力から与え、
+# パニックせずにエラーとして終了することを確認する
+run_error_test() {
+	echo "[error] $1"
+
+	echo "$1" | target/debug/sumorucc - >/dev/null
+	[ $? -ne 1 ] && error_exit 1
+
+	echo OK
+	echo
+}
+
 cargo build || error_exit 1
 
 
@@ -54,3 +78,79 @@ for src in $srcs
 do
 	run_test "$src"
 done
+
+[ $# -ne 0 ] && exit 0
+
+# 不正な浮動小数点定数
+for literal in 1e 1e+ 0x1p- .5e 0x1.8 0x1p 0x.p1 0x1.gp0 0x1p1.5 1..5 1e5.0 1.0x \
+	1.5ff 1.5fl 1.5lf 1.5d 1_0.5 "1.'5" "1e'5" "1'e5" "0x'1p0" "1.5'f" "1'.5"
+do
+	run_error_test "double d = $literal;"
+done
+
+# プロトタイプと一致しない引数、値で渡す構造体の引数
+run_error_test 'int h(int *p); int main(){ return h(1.5); }'
+run_error_test 'int h(int *p); int main(){ return h("s"); }'
+run_error_test 'int h(int *p); int main(){ return h(5); }'
+run_error_test 'int h(int (*f)(void)); int main(){ int x; return h(&x); }'
+run_error_test 'int f(void); int main(){ return f(1); }'
+run_error_test 'struct S {int a;}; int f(struct S s);'
+run_error_test 'struct S {int a;} s; int f(); int main(){ return f(s); }'
+
+# 空ポインタ定数以外の整数、void*と関数へのポインタの間の暗黙の型変換
+run_error_test 'int main(){ int z = 0; int *p = z; return 0; }'
+run_error_test 'int f(void); int main(){ void *v = f; return 0; }'
+run_error_test 'int main(){ void *v = 0; int (*g)(void) = v; return 0; }'
+
+# intで表せない列挙定数の値
+run_error_test 'enum E { A = 4294967297 };'
+run_error_test 'enum E { A = -2147483649 };'
+run_error_test 'enum E { A = 2147483647, B };'
+
+# ファイルのパスに"や\が含まれていても.incbinと.fileのパスとしてアセンブルできる
+echo '[quoted path]'
+tmp_dir=`mktemp -d`
+trap 'rm -rf "$tmp_dir"' EXIT
+quoted_dir="$tmp_dir/we\"ird\\dir"
+mkdir "$quoted_dir"
+head -c 300 /dev/zero | tr '\0' 'x' >"$quoted_dir/data.bin"
+cat <<EOS >"$quoted_dir/embed.c"
+char data[300] = {
+#embed "data.bin"
+};
+int main(){ return data[299] == 'x' ? 0 : 1; }
+EOS
+target/debug/sumorucc "$quoted_dir/embed.c" >tmp.s || error_exit 1
+gcc -no-pie -o tmp tmp.s || error_exit 1
+./tmp || error_exit 1
+echo OK
+
+# PCHの作成後にインクルードや#embedで読み込んだファイルが更新されたらPCHを使わない
+echo '[stale pch]'
+pch_dir="$tmp_dir/pch"
+mkdir "$pch_dir"
+cat <<EOS >"$pch_dir/outer.h"
+#include "inner.h"
+char pch_data[1] = {
+#embed "data.txt"
+};
+EOS
+echo 'int pch_value = 1;' >"$pch_dir/inner.h"
+printf 2 >"$pch_dir/data.txt"
+cat <<EOS >"$pch_dir/main.c"
+#include "outer.h"
+int main(){ return pch_value * 10 + pch_data[0] - '0'; }
+EOS
+run_pch_main() {
+	target/debug/sumorucc "$pch_dir/main.c" >tmp.s || error_exit 1
+	gcc -no-pie -o tmp tmp.s || error_exit 1
+	./tmp
+	[ $? -ne $1 ] && error_exit 1
+}
+target/debug/sumorucc -x c-header "$pch_dir/outer.h" || error_exit 1
+run_pch_main 12
+echo 'int pch_value = 3;' >"$pch_dir/inner.h"
+run_pch_main 32
+printf 4 >"$pch_dir/data.txt"
+run_pch_main 34
+echo OK
@@ -46,5 +46,37 @@ int main()
 	ASSERT(1, 2 >= 1);
 	ASSERT(0, (1+2*3 < 6-5/4) <= 1 != (10 >= 3) + 10 > 9);
 
+	ASSERT(16, 1 << 4);
+	ASSERT(12, 3 << 2 >> 0);
+	ASSERT(5, 20 >> 2);
+	ASSERT(-3, -5 >> 1);
+	ASSERT(8, 1 + 1 << 2);
+	ASSERT(1, 1 << 2 < 5);
+	ASSERT(2147483647, ({unsigned x=-1; x >> 1;}));
+	ASSERT(-1, ({int x=-1; x >> 1;}));
+	ASSERT(0, ({int x=1; x << 32 >> 32;}));
+	ASSERT(4294967296, 1L << 32);
+
+	ASSERT(4294967295, ({unsigned x=-1; x;}));
+	ASSERT(255, ({unsigned char x=-1; x;}));
+	ASSERT(65535, ({unsigned short x=-1; x;}));
+	ASSERT(-1, ({signed char x=255; x;}));
+	ASSERT(-1, ({signed x=-1; x;}));
+	ASSERT(1, ({unsigned x=-1; x == -1;}));
+	ASSERT(0, ({unsigned x=1; -1 < x;}));
+	ASSERT(1, ({unsigned char x=1; -1 < x;}));
+	ASSERT(1, ({unsigned x=1; -1L < x;}));
+	ASSERT(0, ({unsigned long x=1; -1L < x;}));
+	ASSERT(1, -1 > 0u);
+	ASSERT(1, -1 >= 0ul);
+	ASSERT(0, -1 <= 0u);
+	ASSERT(2147483647, ({unsigned x=-1; x / 2;}));
+	ASSERT(-2, ({int x=-4; x / 2;}));
+	ASSERT(0, ({unsigned x=0; x - 1 + 1;}));
+	ASSERT(4294967295, ({unsigned x=0; x - 1;}));
+	ASSERT(-1, ({unsigned long x=0; x - 1;}));
+	ASSERT(0, ({unsigned x=65536; x * x;}));
+	ASSERT(1, ({unsigned long long x=-1; x / 18446744073709551615ull;}));
+
 	return 0;
 }
@@ -0,0 +1,49 @@
+#include "test.h"
+
+_Bool g0 = 2;
+bool g1 = "abc";
+_Bool g2[3] = {0, -1, 0};
+
+struct {
+	char c;
+	_Bool b;
+	bool a[2];
+} g3;
+
+bool not(bool b) { return b == false; }
+
+int main()
+{
+	ASSERT(1, ({bool x; sizeof x;}));
+	ASSERT(3, sizeof g2);
+	ASSERT(4, sizeof g3);
+
+	ASSERT(0, ({_Bool x=0; x;}));
+	ASSERT(1, ({_Bool x=1; x;}));
+	ASSERT(1, ({_Bool x=2; x;}));
+	ASSERT(1, ({_Bool x=256; x;}));
+	ASSERT(1, ({_Bool x=-1; x;}));
+	ASSERT(1, ({_Bool x=4294967296; x;}));
+	ASSERT(1, ({_Bool x; x=3; x;}));
+	ASSERT(1, ({_Bool x; (x=3) + 0;}));
+	ASSERT(1, ({int y=5; bool x=&y; x;}));
+	ASSERT(1, ({char s[2]; bool x=s; x;}));
+
+	ASSERT(1, g0);
+	ASSERT(1, g1);
+	ASSERT(1, g2[1]);
+	ASSERT(0, g2[2]);
+	ASSERT(1, ({g3.b=7; g3.b;}));
+	ASSERT(0, ({g3.a[0]=0; g3.a[1]=9; g3.a[0];}));
+	ASSERT(1, g3.a[1]);
+
+	ASSERT(1, true);
+	ASSERT(0, false);
+	ASSERT(1, sizeof true);
+	ASSERT(2, true + true);
+	ASSERT(-1, ({_Bool x=1; 0 - x;}));
+	ASSERT(1, not(0));
+	ASSERT(0, not(5));
+
+	return 0;
+}
@@ -0,0 +1,37 @@
+#include "test.h"
+
+char g0 = (char)300;
+long g1 = (int)4294967297;
+int *g2 = (int *)0;
+
+int main()
+{
+	ASSERT(131585, (int)8590066177);
+	ASSERT(513, (short)8590066177);
+	ASSERT(1, (char)8590066177);
+	ASSERT(1, (long)1);
+	ASSERT(0, (long)&*(int *)0);
+	ASSERT(513, ({int x=512; *(char *)&x=1; x;}));
+	ASSERT(5, ({int x=5; long y=(long)&x; *(int *)y;}));
+	ASSERT(-1, (char)255);
+	ASSERT(-1, (signed char)255);
+	ASSERT(255, (unsigned char)255);
+	ASSERT(-1, (short)65535);
+	ASSERT(65535, (unsigned short)65535);
+	ASSERT(-1, (int)0xffffffff);
+	ASSERT(4294967295, (unsigned)0xffffffff);
+	ASSERT(1, (_Bool)256);
+	ASSERT(1, ({int x; (bool)&x;}));
+	ASSERT(3, (int)(char)(long)3);
+	ASSERT(0, (int)(unsigned char)-256);
+	ASSERT(1, -(int)-1);
+
+	ASSERT(44, g0);
+	ASSERT(1, g1);
+	ASSERT(1, g2 == 0);
+
+	ASSERT(7, ({int x=7; (void)x; x;}));
+	ASSERT(3, ({int x=2; (void)(x=3); x;}));
+
+	return 0;
+}
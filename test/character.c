@@ -14,7 +14,7 @@ int main()
 	ASSERT(10, '\n');
 	ASSERT(13, '\r');
 	ASSERT(9, '\t');
-	ASSERT(8, '\v');
+	ASSERT(11, '\v');
 	ASSERT(27, '\e');
 	ASSERT(34, '\"');
 	ASSERT(34, '"');
@@ -32,5 +32,21 @@ int main()
 	ASSERT(7, '\007');
 	ASSERT(87, '\127');
 
+	ASSERT(97, L'a');
+	ASSERT(97, u'a');
+	ASSERT(97, U'a');
+	ASSERT(12354, L'あ');
+	ASSERT(12354, u'\u3042');
+	ASSERT(128512, U'\U0001F600');
+	ASSERT(-1, L'\xffffffff');
+	ASSERT(65535, u'\xffff');
+	ASSERT(4294967295, U'\xffffffff');
+
+	ASSERT(24930, 'ab');
+	ASSERT(50089, 'é');
+	ASSERT(-1, '\xff\xff\xff\xff');
+	ASSERT(1650680933, 'abcde');
+	ASSERT(98, L'ab');
+
 	return 0;
 }
@@ -2,12 +2,18 @@
 
 int g0[(1==1) + (10!=-10) + (2<4) + (1<=5) + (0>-1) + (-2>=-7)];
 int g1[1 + (0==1) + (10!=10) + (4<2) + (5<=1) + (-1>0) + (-7>=-2)];
+int g2[(1<<3) + (64>>4) + (-1u>>31)];
+int g3 = -1u / 2;
+unsigned char g4 = 257;
 
 int main()
 {
-	ASSERT(40, ({int a[1+1][5*(4/(1+2))]; sizeof a[1];}));
-	ASSERT(48, sizeof g0);
-	ASSERT(8, sizeof g1);
+	ASSERT(20, ({int a[1+1][5*(4/(1+2))]; sizeof a[1];}));
+	ASSERT(24, sizeof g0);
+	ASSERT(4, sizeof g1);
+	ASSERT(52, sizeof g2);
+	ASSERT(2147483647, g3);
+	ASSERT(1, g4);
 
 	return 0;
 }
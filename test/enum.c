@@ -0,0 +1,54 @@
+#include "test.h"
+
+enum color { RED, GREEN = 5, BLUE };
+enum { ZERO, ONE, TWO, };
+enum color g0 = BLUE;
+int g1[TWO + 1];
+
+enum state { IDLE, RUNNING, DONE };
+
+enum state next(enum state s)
+{
+	if (s == IDLE)
+		return RUNNING;
+	return DONE;
+}
+
+int main()
+{
+	ASSERT(0, RED);
+	ASSERT(5, GREEN);
+	ASSERT(6, BLUE);
+	ASSERT(0, ZERO);
+	ASSERT(2, TWO);
+	ASSERT(6, g0);
+	ASSERT(12, sizeof g1);
+
+	ASSERT(0, ({enum {A, B, C} x; A;}));
+	ASSERT(2, ({enum {A, B, C} x; C;}));
+	ASSERT(1, ({enum {A = -3, B, C} x; C - B;}));
+	ASSERT(-2, ({enum {A = -3, B, C} x; B;}));
+	ASSERT(8, ({enum {A = 3, B = A + 4, C} x; C;}));
+	ASSERT(4, ({enum t {A, B} x; sizeof x;}));
+	ASSERT(4, ({enum t {A, B}; enum t y; sizeof y;}));
+	ASSERT(1, ({enum t {A, B}; enum t y = B; y;}));
+	ASSERT(20, ({enum {N = 5}; int a[N]; sizeof a;}));
+	ASSERT(3, ({enum {N = 3}; char a[N][N]; sizeof a[0];}));
+	ASSERT(8, ({enum {A = 1 << 3}; A;}));
+	ASSERT(2147483647, ({enum {A = 2147483646, B} x; B;}));
+	ASSERT(-2147483648, ({enum {A = -2147483647 - 1} x; A;}));
+
+	ASSERT(5, ({enum color c = GREEN; c;}));
+	ASSERT(1, ({enum color c = BLUE; c == 6;}));
+	ASSERT(7, ({int RED = 7; RED;}));
+	ASSERT(0, ({int x; { int RED = 7; x = RED; } RED;}));
+	ASSERT(3, ({enum {GREEN = 3}; GREEN;}));
+	ASSERT(5, GREEN);
+	ASSERT(2, ({enum color {X, Y, Z}; enum color c = Z; c;}));
+
+	ASSERT(1, next(IDLE));
+	ASSERT(2, next(RUNNING));
+	ASSERT(2, next(next(IDLE)));
+
+	return 0;
+}
@@ -0,0 +1,136 @@
+#include "test.h"
+
+float g0 = 1.5;
+double g1 = 0.25 + 1;
+int g2 = 3.9;
+unsigned long g3 = 1e19;
+_Bool g4 = 0.1;
+double g5 = 7;
+
+float add_float(float x, float y) { return x + y; }
+double add_double(double x, double y) { return x + y; }
+double mix(int a, double b, long c, float d) { return a * b + c * d; }
+double add10(double a, double b, double c, double d, double e, double f, double g, double h)
+{
+	return a + b + c + d + e + f + g + h;
+}
+int to_int(double x) { return x; }
+float half(int x) { return x / 2.0; }
+
+int main()
+{
+	ASSERT(35, (float)(char)35);
+	ASSERT(35, (double)(char)35);
+	ASSERT(-1, (int)-1.5);
+	ASSERT(3, (long)3.99);
+	ASSERT(3, (unsigned)3.5f);
+	ASSERT(1, (_Bool)0.5);
+	ASSERT(0, (_Bool)0.0);
+	ASSERT(-128, (char)(double)-128);
+	ASSERT(4294967295, (unsigned)(double)4294967295);
+	ASSERT(1, (unsigned long)(double)18446744073709549568UL == 18446744073709549568UL);
+	ASSERT(1, (double)18446744073709551615UL == 18446744073709551616.0);
+	ASSERT(1, (float)(1UL << 63) == 9223372036854775808.0f);
+
+	ASSERT(1, 0.1 + 0.2 > 0.3);
+	ASSERT(0, 0.5 + 0.25 != 0.75);
+	ASSERT(1, 1.5f == 1.5);
+	ASSERT(0, 0.1f == 0.1);
+	ASSERT(1, 2.0 < 2.5);
+	ASSERT(1, 2.0 <= 2.0);
+	ASSERT(0, 3.0 < 2.5);
+	ASSERT(1, 3.0 > 2.5);
+	ASSERT(1, 2.5 >= 2.5);
+	ASSERT(1, -1.0 < 0);
+	ASSERT(0, 1 < 0.5);
+	ASSERT(1, 5 / 2.0 == 2.5);
+	ASSERT(7, 3.5 * 2);
+	ASSERT(-2, 0.5 - 2.5);
+	ASSERT(1, ({float x=1.0f; x/3 == 1.0f/3;}));
+	ASSERT(0, ({double x=1.0; x/3 == 1.0f/3;}));
+
+	ASSERT(1, ({double z=0.0; double nan=z/z; nan != nan;}));
+	ASSERT(0, ({double z=0.0; double nan=z/z; nan == nan;}));
+	ASSERT(0, ({double z=0.0; double nan=z/z; nan < 1.0;}));
+	ASSERT(0, ({double z=0.0; double nan=z/z; nan >= 1.0;}));
+	ASSERT(1, ({double z=0.0; double nan=z/z; (_Bool)nan;}));
+
+	ASSERT(5, ({double x=2.5; int y=x*2; y;}));
+	ASSERT(3, ({float x; x=3.75; (int)x;}));
+	ASSERT(1, ({float f=1.1; double d=f; d != 1.1;}));
+	ASSERT(6, ({double a[3]; a[0]=1; a[1]=2; a[2]=3; a[0]+a[1]+a[2];}));
+	ASSERT(10, ({double x=2.5; double *p=&x; *p=*p*4; x;}));
+	ASSERT(7, ({double x=0; int i; for (i=0; i<7; i=i+1) x=x+1.0; x;}));
+	ASSERT(2, ({double x=0.5; int r=1; if (x) r=2; r;}));
+	ASSERT(1, ({double x=0.0; int r=1; if (x) r=2; r;}));
+	ASSERT(3, ({double x=3; int n=0; while (x) { x=x-1; n=n+1; } n;}));
+
+	ASSERT(1, g0 == 1.5);
+	ASSERT(1, g1 == 1.25);
+	ASSERT(3, g2);
+	ASSERT(1, g3 == 10000000000000000000UL);
+	ASSERT(1, g4);
+	ASSERT(7, g5);
+
+	ASSERT(1, add_float(1.25, 2.5) == 3.75);
+	ASSERT(1, add_double(0.5, 0.25) == 0.75);
+	ASSERT(6, add_double(2, 4));
+	ASSERT(23, mix(3, 2.5, 5, 3.1f));
+	ASSERT(36, add10(1, 2, 3, 4, 5, 6, 7, 8));
+	ASSERT(-2, to_int(-2.9));
+	ASSERT(1, half(3) == 1.5);
+
+	ASSERT(0, ({char buf[32]; sprintf(buf, "%.2f %d %.1f", 2.5, 3, 1.5f); strcmp(buf, "2.50 3 1.5");}));
+
+	ASSERT(4, sizeof 1.5f);
+	ASSERT(4, sizeof 1.5F);
+	ASSERT(8, sizeof 1.5);
+	ASSERT(16, sizeof 1.5l);
+	ASSERT(16, sizeof 1.5L);
+	ASSERT(4, sizeof 0x1p0f);
+	ASSERT(16, sizeof 0x1p0L);
+	ASSERT(8, sizeof 0x1.fp0);
+	ASSERT(1, 1. == 1);
+	ASSERT(1, .5 == 0.5);
+	ASSERT(1, 1e3 == 1000);
+	ASSERT(1, 1E-3 == 0.001);
+	ASSERT(1, 1e+2 == 100);
+	ASSERT(1, 5e0f == 5);
+	ASSERT(1, 08.5 == 8.5);
+	ASSERT(1, 0.1 == 1e-1);
+	ASSERT(1, 0.1f != 0.1);
+	ASSERT(1, 123456789012345678901234567890.0 == 1.2345678901234568e29);
+	ASSERT(1, 1e400 == 1e400 * 2);
+	ASSERT(0, 1e-400 != 0);
+
+	ASSERT(16, 0x1p4);
+	ASSERT(3, 0x1.8p1);
+	ASSERT(1, 0X.8P0 == 0.5);
+	ASSERT(4, 0x1p+2);
+	ASSERT(1, 0x1p-2 == 0.25);
+	ASSERT(10, 0xAp0f);
+	ASSERT(255, 0xff.0p0);
+	ASSERT(1, 0x1.fp0 == 1.9375);
+	ASSERT(1, 0x1.fffffep127f == 3.40282347e38f);
+	ASSERT(1, ({float f=0x1.ffffffp127f; f == f * 2;}));
+	ASSERT(1, 0x1p-1074 > 0);
+	ASSERT(1, 0x1p-1074 == 4.9406564584124654e-324);
+	ASSERT(1, 0x1p-1075 == 0);
+	ASSERT(1, 0x1.8p-1074 == 0x1p-1073);
+	ASSERT(1, 0x1.000001p0f == 1.0f);
+	ASSERT(1, 0x1.000003p0f == 0x1.000004p0f);
+	ASSERT(1, 0x1.0000010000000000000000001p0f == 0x1.000002p0f);
+	ASSERT(1, 0x1.00000000000008p0 == 1);
+	ASSERT(1, 0x1.00000000000018p0 == 0x1.0000000000002p0);
+	ASSERT(1, 0x1.00000000000008000000000000000000001p0 > 1);
+	ASSERT(1, 0x10000000000000000000000000000000p-124 == 1);
+
+	ASSERT(1, 1'000.5 == 1000.5);
+	ASSERT(1, 1'0.2'5f == 10.25f);
+	ASSERT(1, 1e1'0 == 1e10);
+	ASSERT(16, 0x1'0p0);
+	ASSERT(1024, 0x1p1'0);
+	ASSERT(1, 0x1.f'fp0 == 0x1.ffp0);
+
+	return 0;
+}
@@ -8,6 +8,8 @@ int sub(int x, int y){return x-y;}
 
 int fchar0(char a, char b, char c){return a-b-c;}
 int fchar1(char a, char b, int c){return c-a*10-b*10;}
+int fshort(short a, long b, long long c){return a+b+c;}
+long sub_long(long a, long b){return a-b;}
 
 int fst(int *p){return p[0];}
 int snd(char *p){return p[1];}
@@ -15,8 +17,26 @@ int mul_3(int *p){*p=*p*3; return 0;}
 
 struct G0 {char x;};
 
+int g0;
+void set_g0(int x) { g0 = x; }
+void set_g0_if_positive(int x) { if (x < 0) return; g0 = x; }
+int ret_void_param(void) { return 9; }
+short ret_short(int x) { return x; }
+_Bool ret_bool(int x) { return x; }
+_Bool is_nonnull(int *p) { return p; }
+int g_arr[3] = {4, 5, 6};
+int *ret_decay(void) { return g_arr; }
+void *ret_void_ptr(int *p) { return p; }
+char *ret_from_void_ptr(void *p) { return p; }
+int (*ret_fn_ptr(void))(int) { return f2; }
+double ret_double(int x) { return x; }
+struct G1 *ret_null(void) { return 0; }
+
 int arg_struct_fn(struct G0 *p){return 1;}
 
+int add_unproto();
+double half_unproto();
+
 int main()
 {
 	ASSERT(3, ret3());
@@ -33,11 +53,35 @@ int main()
 
 	ASSERT(1, fchar0(7, 3, 3));
 	ASSERT(100, fchar1(10, 20, 400));
+	ASSERT(-6, fshort(-1, -2, -3));
+	ASSERT(1, sub_long(4294967297, 4294967296));
 
 	ASSERT(57, ({int v[2]={-9,9}; fst(v) + snd("ABC");}));
 	ASSERT(21, ({int x=7; mul_3(&x); x;}));
 
 	ASSERT(1, ({struct G0 x; arg_struct_fn(&x);}));
 
+	ASSERT(3, ({set_g0(3); g0;}));
+	ASSERT(5, ({set_g0(5); set_g0_if_positive(-1); g0;}));
+	ASSERT(7, ({set_g0_if_positive(7); g0;}));
+	ASSERT(9, ret_void_param());
+	ASSERT(-1, ret_short(65535));
+	ASSERT(1, ret_bool(256));
+	ASSERT(1, ({int x; is_nonnull(&x);}));
+	ASSERT(5, ret_decay()[1]);
+	ASSERT(1, ({int x; ret_void_ptr(&x) == &x;}));
+	ASSERT(1, ({char c; ret_from_void_ptr(&c) == &c;}));
+	ASSERT(3, ret_fn_ptr()(3));
+	ASSERT(1, ret_double(7) / 2 == 3.5);
+	ASSERT(1, ret_null() == 0);
+	ASSERT(1, ({int *p = 0; p == 0;}));
+	ASSERT(1, ({int *p = &g0; p = 0; !p;}));
+	ASSERT(3, add_unproto(1, 2));
+	ASSERT(1, ({float f=3; half_unproto(f) == 1.5;}));
+	ASSERT(1, ({char c=-1; short s=2; add_unproto(c, s);}));
+
 	return 0;
 }
+
+int add_unproto(int a, int b) { return a + b; }
+double half_unproto(double x) { return x / 2; }
@@ -0,0 +1,90 @@
+#include "test.h"
+
+int add(int x, int y);
+int sub2(int, int);
+int count_args(int n, ...);
+double scale(double x);
+int later(void), g;
+
+int add(int x, int y){return x+y;}
+int sub2(int x, int y){return x-y;}
+int mul2(int x, int y){return x*y;}
+int count_args(int n, ...){return n;}
+double half(double x){return x/2;}
+char *second(char *s){return s+1;}
+long double ld_twice(long double x){return x*2;}
+void set_g(int x){g=x;}
+
+int apply(int (*fn)(int, int), int x, int y){return fn(x, y);}
+int apply_fn(int fn(int, int), int x, int y){return (*fn)(x, y);}
+
+int (*g_op)(int, int) = add;
+int (*g_ops[3])(int, int) = {add, sub2, &mul2};
+
+struct Plugin {
+	char *name;
+	int (*init)(int);
+	int (*run)(int, int);
+};
+
+int plugin_init(int x){return x*10;}
+
+typedef int (*BinOp)(int, int);
+BinOp pick(int i){return g_ops[i];}
+int (*pick2(int i))(int, int){return g_ops[i];}
+static int (*(pick_static)(int i))(int, int){return g_ops[2 - i];}
+int (sum3)(int a, int b, int c){return a+b+c;}
+
+int main()
+{
+	ASSERT(7, add(3, 4));
+	ASSERT(7, scale(3.5));
+	ASSERT(3, count_args(3, 1, 2.5f, "x"));
+	ASSERT(42, later());
+	ASSERT(42, ({int later(void); later();}));
+
+	ASSERT(5, ({int (*fp)(int, int)=add; fp(2, 3);}));
+	ASSERT(5, ({int (*fp)(int, int)=&add; (*fp)(2, 3);}));
+	ASSERT(-1, ({int (*fp)(int, int); fp=sub2; fp(2, 3);}));
+	ASSERT(6, (*add)(2, 4));
+	ASSERT(6, (&add)(2, 4));
+	ASSERT(9, ((int (*)(int, int))add)(4, 5));
+	ASSERT(2, ({int (*v)(int, ...)=count_args; v(2, 1.5, 3);}));
+	ASSERT(4, ({void (*s)(int)=set_g; s(4); g;}));
+
+	ASSERT(8, apply(add, 5, 3));
+	ASSERT(2, apply(sub2, 5, 3));
+	ASSERT(15, apply_fn(mul2, 5, 3));
+
+	ASSERT(9, g_op(4, 5));
+	ASSERT(7, g_ops[0](3, 4));
+	ASSERT(-1, g_ops[1](3, 4));
+	ASSERT(12, g_ops[2](3, 4));
+	ASSERT(12, pick(2)(3, 4));
+	ASSERT(7, pick(1)(10, 3));
+	ASSERT(7, pick2(0)(3, 4));
+	ASSERT(12, pick2(2)(3, 4));
+	ASSERT(12, pick_static(0)(3, 4));
+	ASSERT(-1, ({int (*(*pp)(int))(int, int)=pick2; pp(1)(3, 4);}));
+	ASSERT(6, sum3(1, 2, 3));
+
+	ASSERT(1, ({BinOp op=mul2; op == mul2;}));
+	ASSERT(0, ({BinOp op=mul2; op == add;}));
+	ASSERT(1, ({void *p=(void *)add; (int (*)(int, int))p == add;}));
+
+	ASSERT(70, ({struct Plugin p; p.init=plugin_init; p.init(7);}));
+	ASSERT(30, ({struct Plugin p; struct Plugin *q=&p; q->run=mul2; q->run(5, 6);}));
+
+	ASSERT(3, ({double (*h)(double)=half; h(7) * 2 - 4;}));
+	ASSERT(98, ({char *(*f)(char *)=second; *f("abc");}));
+	ASSERT(10, ({long double (*f)(long double)=ld_twice; f(5);}));
+	ASSERT(5, ({double (*h)(double)=half; int (*fp)(int, int)=add; fp(h(4), h(6));}));
+
+	ASSERT(24, ({int (*a[3])(int); sizeof(a);}));
+	ASSERT(12, ({int (*p)[3]; sizeof(*p);}));
+
+	return 0;
+}
+
+int later(void){return 42;}
+double scale(double x){return x*2;}
@@ -0,0 +1,60 @@
+// -fgnu-compatを指定してコンパイルする
+
+__extension__ int ext;
+int aligned __attribute__((aligned(8), unused));
+int renamed __asm__("renamed_label");
+int __attribute__ ((__nothrow__)) ret7() { return 7; }
+
+__inline int first(int *__restrict p)
+{
+	return p[0];
+}
+
+__inline__ int second(char *__restrict__ p)
+{
+	return p[1];
+}
+
+#if !__has_attribute(unused) || !__has_attribute(__nothrow__) || !__has_attribute(format)
+#error "attributes accepted by -fgnu-compat"
+#endif
+#if __has_attribute(aligned) || __has_attribute(__mode__) || __has_attribute(gnu_inline)
+#error "attributes that change the meaning of the code"
+#endif
+#if !__has_feature(attribute_unused_on_fields) || !__has_feature(enumerator_attributes)
+#error "features accepted by -fgnu-compat"
+#endif
+
+struct {
+	int used;
+	int unused __attribute__((unused));
+} fields;
+enum { OLD __attribute__((deprecated("use NEW"))), NEW };
+int checked(const char *fmt, ...) __attribute__((__format__(__printf__, 1, 2), __nonnull__(1)));
+int checked(const char *fmt, ...) { return fmt[0]; }
+
+int main()
+{
+	__const int x = 3;
+	__volatile__ int y = 4;
+	int a[2];
+	a[0] = x;
+	a[1] = y;
+
+	if (first(a) != 3)
+		return 1;
+	if (second("ab") != 'b')
+		return 2;
+
+	ext = 5;
+	aligned = 6;
+	renamed = 7;
+	if (ext + aligned + renamed + ret7() != 25)
+		return 3;
+
+	fields.unused = 8;
+	if (fields.unused + NEW + checked("a") != 8 + 1 + 'a')
+		return 4;
+
+	return 0;
+}
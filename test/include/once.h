@@ -0,0 +1,5 @@
+#pragma once
+#ifdef ONCE_H
+#error "once.h is included twice"
+#endif
+#define ONCE_H
@@ -0,0 +1,3 @@
+// test.shでinclude/pch_src.hからプリコンパイルしたpch.h.pchが
+// 読み込まれるので、このファイルはプリプロセスされない。
+#error "pch.h.pch is not used"
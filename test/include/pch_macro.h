@@ -0,0 +1,8 @@
+// test.shでこのファイルをPCH_MACROが未定義の状態でプリコンパイルする。
+// test/pch.cはPCH_MACROを定義してからインクルードするので、
+// PCHは使われずにこのファイルがプリプロセスされる。
+#ifdef PCH_MACRO
+int pch_macro = PCH_MACRO;
+#else
+int pch_macro = 1;
+#endif
@@ -0,0 +1,10 @@
+#define PCH_TEN 10
+#define PCH_TWENTY PCH_TEN + PCH_TEN
+#undef __STDC_HOSTED__
+
+int pch_x;
+char pch_str[4] = {
+#embed "include1.h" limit(4)
+};
+int pch_counter = __COUNTER__;
+#pragma pack(push, 1)
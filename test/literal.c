@@ -0,0 +1,26 @@
+#include "test.h"
+
+int main()
+{
+	ASSERT(31, 0x1F);
+	ASSERT(31, 0X1f);
+	ASSERT(493, 0755);
+	ASSERT(0, 0);
+	ASSERT(0, 00);
+	ASSERT(10, 0b1010);
+	ASSERT(5, 0B101);
+
+	ASSERT(10, 10u);
+	ASSERT(1, 1UL);
+	ASSERT(2, 2ll);
+	ASSERT(3, 3LLU);
+	ASSERT(4, 4lu);
+	ASSERT(16, 0x10L);
+	ASSERT(8, 010ull);
+
+	ASSERT(2147483648, 2147483648);
+	ASSERT(4294967296, 0x100000000);
+	ASSERT(-1, 0xffffffffffffffff);
+
+	return 0;
+}
@@ -0,0 +1,132 @@
+#include "test.h"
+
+long double g0 = 0.1L;
+long double g1 = 1.5;
+long double g2 = 3;
+long double g3[3] = {1.25L, 2, 0.1};
+int g4 = 2.75L;
+unsigned long g5 = 1e19L;
+long double g6 = 4.9e-324;
+long double g7 = -2.5;
+long double g8 = -0.1L;
+long double g9 = 0.1L * 3;
+long double g10 = 0x1.0000000000000002p0L;
+long double g11 = 1.0L / 3;
+long double g12 = 0x1.fffffffffffffffep16383L;
+long double g13 = 3.6451995318824746025e-4951L;
+unsigned long g14 = 0x1.fffffffffffffffep63L;
+int g15 = 0.1L * 3 == 0.3L;
+int g16 = 0.1L * 3 != 0.1 * 3;
+
+long double ld_add(long double x, long double y) { return x + y; }
+long double ld_mix(int a, long double b, double c, long double d, float e) { return a * b + c * d - e; }
+long double ld_third(void) { return 1.0L / 3; }
+double ld_to_double(long double x) { return x; }
+int ld_to_int(long double x) { return x; }
+long double ld_from_long(long x) { return x; }
+long double ld_fact(int n)
+{
+	if (n <= 1)
+		return 1;
+	return n * ld_fact(n - 1);
+}
+
+int main()
+{
+	ASSERT(16, ({long double x; sizeof x;}));
+	ASSERT(16, sizeof 1.0L);
+	ASSERT(16, sizeof(1.0L + 1.0));
+	ASSERT(16, sizeof(1 + 1.0L));
+	ASSERT(16, ({long double x[2]; (char *)&x[1] - (char *)&x[0];}));
+
+	ASSERT(1, 0.1L != 0.1);
+	ASSERT(1, 0.1L == 0.1L);
+	ASSERT(1, (double)0.1L == 0.1);
+	ASSERT(1, (float)0.1L == 0.1f);
+	ASSERT(1, 0.5L == 0.5);
+	ASSERT(1, 1.0L / 3 != 1.0 / 3);
+	ASSERT(1, 1.0L / 3 * 3 == 1);
+	ASSERT(1, 2.0L < 2.5L);
+	ASSERT(1, 2.0L <= 2.0L);
+	ASSERT(0, 3.0L < 2.5L);
+	ASSERT(1, 3.0L > 2.5L);
+	ASSERT(1, 2.5L >= 2.5);
+	ASSERT(8, 10.0L - 2);
+	ASSERT(5, 10.0L / 2);
+	ASSERT(21, 3 * 7.0L);
+	ASSERT(-2, 0.5L - 2.5L);
+	ASSERT(1, (1.0L + 1e-18L) != 1.0L);
+	ASSERT(0, (1.0 + 1e-18) != 1.0);
+
+	ASSERT(-1, (int)-1.5L);
+	ASSERT(3, (long)3.99L);
+	ASSERT(3, (unsigned)3.5L);
+	ASSERT(-128, (char)-128.0L);
+	ASSERT(1, (_Bool)0.5L);
+	ASSERT(0, (_Bool)0.0L);
+	ASSERT(1, (unsigned long)1e19L == 10000000000000000000UL);
+	ASSERT(1, (unsigned long)(long double)18446744073709551615UL == 18446744073709551615UL);
+	ASSERT(1, (long double)18446744073709551615UL == 18446744073709551615.0L);
+	ASSERT(1, (long double)9223372036854775807L == 9223372036854775807.0L);
+
+	ASSERT(1, ({long double z=0; long double nan=z/z; nan != nan;}));
+	ASSERT(0, ({long double z=0; long double nan=z/z; nan == nan;}));
+	ASSERT(0, ({long double z=0; long double nan=z/z; nan < 1;}));
+	ASSERT(0, ({long double z=0; long double nan=z/z; nan >= 1;}));
+
+	ASSERT(7, ({long double x=3.5L; x*2;}));
+	ASSERT(5, ({long double x; int y=(x=2.5L)*2; y;}));
+	ASSERT(6, ({long double a[3]; a[0]=1; a[1]=2; a[2]=3; a[0]+a[1]+a[2];}));
+	ASSERT(10, ({long double x=2.5L; long double *p=&x; *p=*p*4; x;}));
+	ASSERT(100, ({long double x=0; int i; for (i=0; i<100; i=i+1) x=x+1; x;}));
+	ASSERT(2, ({long double x=0.5L; int r=1; if (x) r=2; r;}));
+	ASSERT(1, ({long double x=0; int r=1; if (x) r=2; r;}));
+	ASSERT(3, ({long double x=3; int n=0; while (x) { x=x-1; n=n+1; } n;}));
+	ASSERT(3, ({long double x=1; x; x; x; x; x; x; x; x; x; x=x+2; x;}));
+	ASSERT(1, ({float f=1.1f; long double x=f; double d=x; d == f;}));
+	ASSERT(1, ({long double x=0.1L; double d=x; d == 0.1;}));
+
+	ASSERT(1, g0 == 0.1L);
+	ASSERT(1, g1 == 1.5);
+	ASSERT(3, g2);
+	ASSERT(1, g3[0] == 1.25);
+	ASSERT(2, g3[1]);
+	ASSERT(1, g3[2] == 0.1);
+	ASSERT(2, g4);
+	ASSERT(1, g5 == 10000000000000000000UL);
+	ASSERT(1, g6 == 4.9e-324);
+	ASSERT(1, g6 > 0);
+	ASSERT(1, g7 == -2.5);
+	ASSERT(1, ({long double x=0.1L; g8 == -x;}));
+	ASSERT(1, g8 != -0.1);
+	ASSERT(1, ({long double x=0.1L; g9 == x * 3;}));
+	ASSERT(1, g9 != 0.1 * 3);
+	ASSERT(1, g10 - 1 == 0x1p-63L);
+	ASSERT(1, 0x1.0000000000000002p0L != 1);
+	ASSERT(1, ({long double x=1; g11 == x / 3;}));
+	ASSERT(1, ({long double x=0x1.fffffffffffffffep16383L; g12 == x;}));
+	ASSERT(1, g12 > 1e4932L);
+	ASSERT(1, g12 * 2 == g12 * 4);
+	ASSERT(1, g13 > 0);
+	ASSERT(1, g13 / 2 == 0);
+	ASSERT(1, g14 == 0xffffffffffffffffUL);
+	ASSERT(1, g15);
+	ASSERT(1, g16);
+
+	ASSERT(1, ld_add(0.25L, 0.5) == 0.75);
+	ASSERT(1, ld_third() == 1.0L / 3);
+	ASSERT(1, ld_third() != 1.0 / 3);
+	ASSERT(16, ld_mix(2, 2.5L, 3.0, 4, 1.0f));
+	ASSERT(1, ld_to_double(0.1L) == 0.1);
+	ASSERT(-2, ld_to_int(-2.9L));
+	ASSERT(1, ld_from_long(9223372036854775807L) == 9223372036854775807.0L);
+	ASSERT(1, ld_fact(20) == 2432902008176640000);
+	ASSERT(1, ld_add(ld_add(1, 2), ld_add(3, 4)) == 10);
+
+	ASSERT(0, ({char buf[64]; sprintf(buf, "%.20Lf %d %.1f", 1.0L / 3, 5, 2.5); strcmp(buf, "0.33333333333333333334 5 2.5");}));
+	ASSERT(0, ({char buf[64]; sprintf(buf, "%Lg %Lg", 0.1L, 1e300L * 1e300L); strcmp(buf, "0.1 1e+600");}));
+	ASSERT(0, ({char buf[64]; sprintf(buf, "%.20Lf %.20Lf", g8, g9); strcmp(buf, "-0.10000000000000000000 0.30000000000000000001");}));
+	ASSERT(0, ({char buf[64]; sprintf(buf, "%La", g10); strcmp(buf, "0x8.000000000000001p-3");}));
+
+	return 0;
+}
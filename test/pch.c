@@ -0,0 +1,30 @@
+#include "include/pch.h"
+#pragma pack(push)
+struct { char c; int i; } pch_packed;
+#pragma pack(pop)
+#pragma pack(pop)
+struct { char c; int i; } pch_unpacked;
+#define PCH_MACRO 2
+#include "include/pch_macro.h"
+
+int main()
+{
+	pch_x = PCH_TWENTY;
+	if (pch_x != 20)
+		return 1;
+	if (pch_str[0] != 'i')
+		return 2;
+
+#ifdef __STDC_HOSTED__
+	return 3;
+#endif
+
+	if (pch_counter != 0 || __COUNTER__ != 1)
+		return 4;
+	if (sizeof(pch_packed) != 5 || sizeof(pch_unpacked) != 8)
+		return 5;
+	if (pch_macro != 2)
+		return 6;
+
+	return 0;
+}
@@ -78,5 +78,12 @@ int main()
 	ASSERT(13, ({struct {struct X{int i;} *p[2];} a[2]; struct X x,y; x.i=3; y.i=5; (a+1)->p[0]=&x; a[0].p[1]=&y; a[1].p[0]->i=13; x.i;}));
 	ASSERT(17, ({struct {struct X{int i;} *p[2];} a[2]; struct X x,y; x.i=3; y.i=5; a[0].p[0]=&x; a->p[1]=&y; a[0].p[1]->i=17; y.i;}));
 
+	ASSERT(8, ({void *p; sizeof p;}));
+	ASSERT(3, ({int x=3; void *p=&x; int *q=p; *q;}));
+	ASSERT(5, ({int x[2]={3,5}; void *p=x; int *q=p; q[1];}));
+	ASSERT(1, ({int x; void *p=&x; char *q=p; q==p;}));
+	ASSERT(4, ({int x[2]; void *p=&x[0]; void *q=&x[1]; q-p;}));
+	ASSERT(2, ({char s[4]; void *p=s; void *q=p+2; (char *)q-(char *)p;}));
+
 	return 0;
 }
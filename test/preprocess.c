@@ -5,6 +5,99 @@
 /* */ #
 
 #include "include/include0.h"
+#include "include/once.h"
+#include <include/once.h>
+
+#define TEN 10
+#define TWENTY TEN + TEN
+#define SELF SELF
+#define STR_A "a"
+#define UNDEFINED 1
+#undef UNDEFINED
+#define MULTI_LINE \
+	1 + \
+	2
+
+int UNDEFINED;
+
+#if 1
+int if_true = 1;
+#else
+int if_true = 0;
+#endif
+
+#if TEN - 10
+#error "TEN - 10 is not 0"
+#elif UNKNOWN_MACRO
+#error "UNKNOWN_MACRO is not 0"
+#elif defined(TEN) == 1
+int elif_true = 1;
+#if 0
+#error "nested #if 0"
+#else
+int nested_else = 1;
+#endif
+#else
+#if 1
+#error "#if in skipped group"
+#endif
+#endif
+
+#ifdef TEN
+int ifdef_true = 1;
+#endif
+#ifndef TEN
+#error "TEN is defined"
+#endif
+#ifdef __has_include
+#if __has_include("include/include0.h") + __has_include(<include/include1.h>) + __has_include(<stdio.h>) == 3
+int has_include = 1;
+#endif
+#endif
+#if __has_include("no_such_file.h")
+#error "no_such_file.h exists"
+#endif
+#if defined __has_builtin
+#if __has_builtin(__builtin_no_such_function)
+#error "__builtin_no_such_function exists"
+#endif
+#endif
+#if __has_attribute(__no_such_attribute__) + __has_feature(no_such_feature)
+#error "no_such_attribute or no_such_feature exists"
+#endif
+
+#pragma pack(push, 1)
+struct { char c; int i; } packed;
+#pragma pack(pop)
+struct { char c; int i; } unpacked;
+#define PACK_2 _Pragma("pack(2)")
+PACK_2 struct { char c; int i; } packed2;
+_Pragma("pack()")
+int pack_arg(int a, _Pragma("pack(1)") int b) { struct { char c; int i; } s; return a + b + sizeof(s); }
+_Pragma("pack()")
+int pack_init[3] = {1, _Pragma("pack(2)") 2, 3};
+struct { char c; int i; } packed_init;
+_Pragma("pack()")
+#pragma push_macro("TEN")
+#undef TEN
+#define TEN 100
+int pushed_ten = TEN;
+#pragma pop_macro("TEN")
+#pragma no_such_pragma
+_Pragma("no_such_pragma")
+
+char embed_small[7] = {
+#embed "include2.h"
+};
+char embed_self[1024] = {
+#embed "preprocess.c" limit(1024)
+};
+char embed_fix[4] = {
+#embed "include2.h" __limit__(TEN - 8) prefix(TEN,) suffix(, TWENTY)
+};
+int embed_empty =
+#embed "include2.h" limit(0) prefix(1 +) if_empty(42)
+;
 
 int main()
 {
@@ -15,5 +108,202 @@ int main()
 	// include2.h
 	z = 15;
 
+	if (TWENTY != 20)
+		return 1;
+	if (TWENTY * 2 != 30)
+		return 2;
+	int SELF = 3;
+	if (SELF != 3)
+		return 3;
+	UNDEFINED = 4;
+
+	if (__STDC__ != 1)
+		return 10;
+	if (__STDC_HOSTED__ != 1)
+		return 11;
+	if (__LINE__ != 124)
+		return 12;
+	if (__FILE__[5] != 'p')
+		return 13;
+	if (sizeof(__FILE__) != 18)
+		return 14;
+	if (__COUNTER__ != 0)
+		return 15;
+	if (__COUNTER__ != 1)
+		return 16;
+	if (sizeof(__DATE__) != 12)
+		return 17;
+	if (sizeof(__TIME__) != 9)
+		return 18;
+	if (__x86_64__ + __LP64__ + __linux__ != 3)
+		return 19;
+	if (__CHAR_BIT__ != 8)
+		return 20;
+	if (__SIZEOF_INT__ != sizeof(x))
+		return 21;
+	if (__SIZEOF_POINTER__ != sizeof(&x))
+		return 22;
+
+	if (MULTI_LINE != 3)
+		return 30;
+	if (sizeof("abc\
+def") != 7)
+		return 31;
+	int spl\
+iced = 5;
+	if (spliced != 5)
+		return 32;
+	// 行コメントも連結される \
+	return 33;
+	if (__LINE__ != 158)
+		return 34;
+	if (1 =\
+= 0)
+		return 35;
+
+#warning this warning does not stop compilation
+#line 1000 "grammar.y"
+	if (__LINE__ != 1000)
+		return 40;
+	if (__FILE__[0] != 'g')
+		return 41;
+	if (sizeof(__FILE__) != 10)
+		return 42;
+#define LINE_NO 2000
+#line LINE_NO
+	if (__LINE__ != 2000)
+		return 43;
+	if (__FILE__[0] != 'g')
+		return 44;
+
+	if (if_true + elif_true + nested_else + ifdef_true != 4)
+		return 50;
+	if (has_include != 1)
+		return 51;
+
+	if (embed_small[0] != 'i')
+		return 60;
+	if (embed_small[6] != 10)
+		return 61;
+	if (embed_self[0] != '#')
+		return 62;
+	if (embed_self[2] != embed_self[0])
+		return 63;
+	if (embed_fix[0] + embed_fix[1] + embed_fix[2] + embed_fix[3] != 10 + 'i' + 'n' + 20)
+		return 64;
+	if (embed_empty != 42)
+		return 65;
+	char embed_local[3] = {
+#embed "include2.h" limit(3)
+	};
+	if (embed_local[2] != 't')
+		return 66;
+
+	if (sizeof(packed) != 1 + sizeof(x))
+		return 70;
+	if (sizeof(unpacked) != 2 * sizeof(x))
+		return 71;
+	if (sizeof(packed2) != 2 + sizeof(x))
+		return 72;
+	if (pushed_ten != 100)
+		return 73;
+	if (TEN != 10)
+		return 74;
+	if (pack_arg(1, 2) != 3 + 1 + sizeof(x))
+		return 75;
+	if (pack_init[1] != 2 || sizeof(packed_init) != 2 + sizeof(x))
+		return 76;
+	if (({ int y = 1 _Pragma("pack(push, 1)") + 2; struct { char c; int i; } s; _Pragma("pack(pop)") y + sizeof(s); }) != 3 + 1 + sizeof(x))
+		return 77;
+	if (({ struct { char c; int i; } s; sizeof(s); }) != 2 * sizeof(x))
+		return 78;
+
+	if (1'000'000 != 1000000)
+		return 80;
+	if (0x7fff'ffff != 2147483647)
+		return 81;
+	if (0b1'0 + 0'7 != 9)
+		return 82;
+	if (__STDC_VERSION__ != 201112L)
+		return 83;
+	if (sizeof(STR_A "b" STR_A) != 4)
+		return 84;
+	int \u3044\u308d\U00003078 = 12;
+	if (いろへ != 12)
+		return 85;
+%:define DIGRAPH 5
+	int digraph<:2:> = <%DIGRAPH, 6%>;
+	if (digraph<:1:> - digraph<:0:> != 1)
+		return 86;
+
+#define ADD(x, y) ((x) + (y))
+#define ID(x) x
+#define STRINGIZE(x) #x
+#define CONCAT(x, y) x ## y
+#define COUNT_ARGS(...) count_args(2, ## __VA_ARGS__)
+#define FIRST(x, ...) x
+	if (ADD(1, 2) * 2 != 6)
+		return 90;
+	if (ADD(
+		3,
+		4) != 7)
+		return 91;
+	if (ID(ADD)(2, 3) != 5)
+		return 92;
+	if (sizeof(STRINGIZE(a + b)) != 6)
+		return 93;
+	int CONCAT(con, cat) = 94;
+	if (concat != 94)
+		return 94;
+	if (COUNT_ARGS() + COUNT_ARGS(1, 2) != 4)
+		return 95;
+	if (FIRST(96, 97, 98) != 96)
+		return 96;
+	int ADD = 97;
+	if (ADD != 97)
+		return 97;
+#if !defined(ADD) || (0 && UNDEFINED_MACRO) || ADD(1, 1) != 2
+	return 98;
+#endif
+#if (1 ? 2 : 0) != 2 || (0 || 0) || !(1 && 2)
+	return 99;
+#endif
+#if __has_builtin(__builtin_bswap32)
+	if (__builtin_bswap32(0x11223344) != 0x44332211)
+		return 100;
+#else
+	return 100;
+#endif
+
 	return 0;
 }
+
+int count_args(int n, ...)
+{
+	return n;
+}
+
+#if (-1 < 0u) + ((1 << 4) != 16) + ((-1u >> 31) != 1)
+#error "unsigned arithmetic in #if"
+#endif
+#if (true != 1) + (false != 0)
+#error "true or false in #if"
+#endif
+#define bool _Bool
+#ifndef bool
+#error "keyword as a macro name"
+#endif
+#undef bool
+#ifdef bool
+#error "bool is still defined"
+#endif
+#if __SIZEOF_LONG_DOUBLE__ != 16
+#error "__SIZEOF_LONG_DOUBLE__"
+#endif
+#if !__has_builtin(__builtin_bswap16) || !__has_builtin(__builtin_bswap32) || !__has_builtin(__builtin_bswap64)
+#error "__builtin_bswap is missing"
+#endif
+// 属性は-fgnu-compatを指定した場合のみ受け付ける
+#if __has_attribute(unused) + __has_feature(attribute_unused_on_fields)
+#error "attributes without -fgnu-compat"
+#endif
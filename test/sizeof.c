@@ -4,20 +4,67 @@ int g0[2][4];
 
 int main()
 {
-	ASSERT(8, sizeof 1);
+	ASSERT(4, sizeof 1);
 	ASSERT(8, sizeof sizeof 1);
-	ASSERT(8, ({int x; sizeof x;}));
+	ASSERT(4, ({int x; sizeof x;}));
 	ASSERT(8, ({int x; sizeof&x;}));
-	ASSERT(8, ({int x=5; sizeof(5+(x*x)/8);}));
-	ASSERT(40, ({int x[5]; sizeof x;}));
-	ASSERT(48, ({int x[3][2]; sizeof x;}));
-	ASSERT(16, ({int x[4][2]; sizeof x[2];}));
-	ASSERT(65, sizeof g0+1);
+	ASSERT(4, ({int x=5; sizeof(5+(x*x)/8);}));
+	ASSERT(20, ({int x[5]; sizeof x;}));
+	ASSERT(24, ({int x[3][2]; sizeof x;}));
+	ASSERT(8, ({int x[4][2]; sizeof x[2];}));
+	ASSERT(33, sizeof g0+1);
 	ASSERT(2, ({int x=2; sizeof(x=1); x;}));
+	ASSERT(4, sizeof(int));
+	ASSERT(8, sizeof(int *));
+	ASSERT(24, sizeof(int [3][2]));
+	ASSERT(8, sizeof(int (*)[3]));
+	ASSERT(16, sizeof(struct {int *x; char y;}));
+	ASSERT(6, (int) sizeof(int) + 2);
 
 	ASSERT(1, ({char c; sizeof c;}));
 	ASSERT(10, ({char str[10]; sizeof str;}));
 	ASSERT(8, ({char *p; sizeof p;}));
 
+	ASSERT(2, ({short x; sizeof x;}));
+	ASSERT(2, ({short int x; sizeof x;}));
+	ASSERT(2, ({int short x; sizeof x;}));
+	ASSERT(8, ({long x; sizeof x;}));
+	ASSERT(8, ({long int x; sizeof x;}));
+	ASSERT(8, ({int long x; sizeof x;}));
+	ASSERT(8, ({long long x; sizeof x;}));
+	ASSERT(8, ({long long int x; sizeof x;}));
+	ASSERT(8, ({long int long x; sizeof x;}));
+	ASSERT(8, sizeof 1L);
+	ASSERT(8, sizeof 1LL);
+	ASSERT(8, sizeof 2147483648);
+	ASSERT(4, ({char c; sizeof(c+c);}));
+	ASSERT(4, ({short s; sizeof(s*s);}));
+	ASSERT(8, ({int i; long l; sizeof(i+l);}));
+	ASSERT(8, ({int x[2]; sizeof(&x[1]-&x[0]);}));
+
+	ASSERT(1, ({unsigned char x; sizeof x;}));
+	ASSERT(1, ({signed char x; sizeof x;}));
+	ASSERT(2, ({unsigned short int x; sizeof x;}));
+	ASSERT(4, ({unsigned x; sizeof x;}));
+	ASSERT(4, ({signed x; sizeof x;}));
+	ASSERT(4, ({int unsigned x; sizeof x;}));
+	ASSERT(8, ({unsigned long x; sizeof x;}));
+	ASSERT(8, ({long unsigned long int x; sizeof x;}));
+	ASSERT(4, sizeof 1u);
+	ASSERT(8, sizeof 1ul);
+	ASSERT(4, ({unsigned char x; sizeof(x+x);}));
+	ASSERT(4, ({unsigned x; int y; sizeof(x+y);}));
+	ASSERT(8, ({unsigned x; long y; sizeof(x+y);}));
+	ASSERT(4, ({unsigned char x; sizeof(x<<1L);}));
+	ASSERT(1, sizeof 1 - 2 > 0);
+
+	ASSERT(4, ({float x; sizeof x;}));
+	ASSERT(8, ({double x; sizeof x;}));
+	ASSERT(4, sizeof 1.5f);
+	ASSERT(8, sizeof 1.5);
+	ASSERT(8, sizeof(1.5f+1.5));
+	ASSERT(4, sizeof(1.5f*2));
+	ASSERT(8, sizeof(1L+1.0));
+
 	return 0;
 }
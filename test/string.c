@@ -2,6 +2,7 @@
 
 char *g0="GVar";
 char *g1[2]={"Hello", "world!"};
+char *g2="con" "cat";
 
 int main()
 {
@@ -19,7 +20,7 @@ int main()
 	ASSERT(10, "\a\b\f\n\r\t\v\e"[3]);
 	ASSERT(13, "\a\b\f\n\r\t\v\e"[4]);
 	ASSERT(9, "\a\b\f\n\r\t\v\e"[5]);
-	ASSERT(8, "\a\b\f\n\r\t\v\e"[6]);
+	ASSERT(11, "\a\b\f\n\r\t\v\e"[6]);
 	ASSERT(27, "\a\b\f\n\r\t\v\e"[7]);
 	ASSERT(34, "\"\?\\"[0]);
 	ASSERT(63, "\"\?\\"[1]);
@@ -27,6 +28,37 @@ int main()
 	ASSERT(39, "\'"[0]);
 	ASSERT(198, ({char *p="\A\B\C"; p[0] + p[1] + p[2] + p[3];}));
 
+	ASSERT(7, sizeof("abc" "def"));
+	ASSERT(100, ("abc" "def")[3]);
+	ASSERT(0, ("abc" "def")[6]);
+	ASSERT(3, sizeof("\x1" "2"));
+	ASSERT(50, ("\x1" "2")[1]);
+	ASSERT(1, sizeof("" ""));
+	ASSERT(116, g2[5]);
+
+	ASSERT(16, sizeof(L"abc"));
+	ASSERT(8, sizeof(u"abc"));
+	ASSERT(16, sizeof(U"abc"));
+	ASSERT(4, sizeof(u8"abc"));
+	ASSERT(98, L"abc"[1]);
+	ASSERT(0, u"abc"[3]);
+	ASSERT(12354, L"\u3042"[0]);
+	ASSERT(12354, u"あ"[0]);
+	ASSERT(12354, U"\U00003042"[0]);
+	ASSERT(227, u8"あ"[0] + 256);
+	ASSERT(6, sizeof(u"\U0001F600"));
+	ASSERT(55357, u"\U0001F600"[0]);
+	ASSERT(56832, u"\U0001F600"[1]);
+	ASSERT(128512, U"\U0001F600"[0]);
+	ASSERT(305419896, L"\x12345678"[0]);
+	ASSERT(-1, L"\xffffffff"[0]);
+	ASSERT(4294967295, U"\xffffffff"[0]);
+	ASSERT(65535, u"\xffff"[0]);
+	ASSERT(12, sizeof("a" L"b"));
+	ASSERT(98, ("a" L"b")[1]);
+	ASSERT(12, sizeof(U"a" "b"));
+	ASSERT(3, sizeof(u8"a" "b"));
+
 	ASSERT(10, "\xa"[0]);
 	ASSERT(10, "\xA"[0]);
 	ASSERT(-85, "\x0aB"[0]);
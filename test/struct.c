@@ -4,6 +4,21 @@ struct G0 {
 	char x;
 };
 
+struct List;
+struct List *g_list;
+struct List {
+	int val;
+	struct List *next;
+};
+
+typedef struct Tree Tree;
+struct Tree {
+	Tree *left, *right;
+	int val;
+};
+
+int sum_list(struct List *p, int n){int sum=0; for (; n>0; n=n-1) {sum=sum+p->val; p=p->next;} return sum;}
+
 int main()
 {
 	ASSERT(1, ({struct {char x;} x; sizeof(x);}));
@@ -14,6 +29,12 @@ int main()
 	ASSERT(1, ({struct G0 x; sizeof(x);}));
 	ASSERT(8, ({struct G0 {int *x;}; struct G0 x; sizeof(x);}));
 
+	ASSERT(8, ({struct {char x; int y;} x; sizeof(x);}));
+	ASSERT(4, ({struct {char x; short y;} x; sizeof(x);}));
+	ASSERT(16, ({struct {char x; long y;} x; sizeof(x);}));
+	ASSERT(16, ({struct {int x; long long y;} x; sizeof(x);}));
+	ASSERT(6, ({struct {short x; char y; short z;} x; sizeof(x);}));
+
 	ASSERT(3, ({struct {int x; int y;} x; int three=3; x.x=three; x.y=7; x.x;}));
 	ASSERT(7, ({struct {int x; int y;} x; int three=3; x.x=three; x.y=7; x.y;}));
 	ASSERT(11, ({struct {int x; int y;} x; x.x=11; x.y=13; int a=x.x; a;}));
@@ -54,5 +75,12 @@ int main()
 
 	ASSERT(11, ({struct {struct inner {int i;} i;} x; struct inner y; x.i.i=11; y=x.i; y.i;}));
 
+	ASSERT(16, sizeof(struct List));
+	ASSERT(6, ({struct List a, b, c; a.val=1; a.next=&b; b.val=2; b.next=&c; c.val=3; g_list=&a; sum_list(g_list, 3);}));
+	ASSERT(24, sizeof(Tree));
+	ASSERT(7, ({Tree l, t; l.val=7; t.left=&l; t.left->val;}));
+	ASSERT(8, ({struct Fwd; struct Fwd *p; struct Fwd {long x;}; sizeof(struct Fwd);}));
+	ASSERT(4, ({struct Node {int val; struct Node *next;} n; n.next=&n; n.val=4; n.next->next->val;}));
+
 	return 0;
 }
@@ -0,0 +1,64 @@
+// -fgnu-compatを指定し、/usr/includeのシステムヘッダをインクルードしてコンパイルする
+
+#include <stdio.h>
+#include <stdlib.h>
+#include <stdint.h>
+#include <limits.h>
+#include <string.h>
+#include <ctype.h>
+#include "test.h"
+
+int compare_int(const void *a, const void *b)
+{
+	return *(const int *)a - *(const int *)b;
+}
+
+int main()
+{
+	ASSERT(-1, EOF);
+	ASSERT(216, sizeof(FILE));
+	ASSERT(24, sizeof(__gnuc_va_list));
+	ASSERT(0, fflush(stdout));
+
+	// sscanfは__asm__で__isoc99_sscanfに置き換えられている
+	ASSERT(46, ({int a, b; sscanf("12 34", "%d %d", &a, &b) == 2 ? a + b : -1;}));
+	ASSERT('2', ({char buf[8]; snprintf(buf, sizeof buf, "%d", 42); buf[1];}));
+
+	ASSERT(255, strtol("ff", NULL, 16));
+	ASSERT(7, abs(-7));
+	ASSERT(6, ({int *p = malloc(sizeof(int) * 3); p[0] = 1; p[1] = 2; p[2] = 3; int sum = p[0] + p[1] + p[2]; free(p); sum;}));
+	ASSERT(123, ({int a[3]; a[0] = 3; a[1] = 1; a[2] = 2; qsort(a, 3, sizeof(int), compare_int); a[0] * 100 + a[1] * 10 + a[2];}));
+
+	// stdint.hとlimits.hはGCCのヘッダから#include_nextで/usr/includeのヘッダを読み込む
+	ASSERT(8, sizeof(int64_t));
+	ASSERT(1, sizeof(uint8_t));
+	ASSERT(8, sizeof(intptr_t));
+	ASSERT(2147483647, INT32_MAX);
+	ASSERT(255, UINT8_MAX);
+	ASSERT(1, INT64_MIN < 0);
+	ASSERT(8, CHAR_BIT);
+	ASSERT(2147483647, INT_MAX);
+	ASSERT(1, LONG_MIN < INT_MIN);
+	ASSERT(65535, USHRT_MAX);
+
+	ASSERT(5, strlen("hello"));
+	ASSERT(0, strcmp("abc", "abc"));
+	ASSERT(1, strncmp("abd", "abc", 3) > 0);
+	ASSERT('c', ({char buf[4]; memcpy(buf, "abc", 4); buf[2];}));
+	ASSERT(2, ({char *s = "abc"; strchr(s, 'c') - s;}));
+	ASSERT(0, ({char buf[4]; memset(buf, 0, sizeof buf); buf[3];}));
+
+	// isdigitなどのマクロはビット演算子の&を使うので、括弧で囲んで関数として呼び出す
+	ASSERT(1, !!(isdigit)('7'));
+	ASSERT(0, !!(isdigit)('x'));
+	ASSERT(1, !!(isspace)(' '));
+	ASSERT(1, !!(isalpha)('q'));
+	ASSERT('A', toupper('a'));
+	ASSERT('z', tolower('Z'));
+
+	ASSERT(0x3412, __bswap_16(0x1234));
+	ASSERT(0x78563412, __bswap_32(0x12345678));
+	ASSERT(0x0807060504030201, __builtin_bswap64(0x0102030405060708));
+
+	return 0;
+}
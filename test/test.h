@@ -1,11 +1,11 @@
 #define ASSERT(expect, expr) assert(expect, expr, #expr)
 
-int assert(int expect, int actual, char *expr)
+int assert(long expect, long actual, char *expr)
 {
 	if (expect == actual) {
-		printf("%s => %d\n", expr, actual);
+		printf("%s => %ld\n", expr, actual);
 	} else {
-		printf("%s => %d expected, but got %d\n", expr, expect, actual);
+		printf("%s => %ld expected, but got %ld\n", expr, expect, actual);
 		exit(1);
 	}
 }
@@ -0,0 +1,65 @@
+#include "test.h"
+
+typedef int MyInt, MyInt2[4];
+typedef struct { int a; long b; } Pair;
+typedef enum { OFF, ON } Switch;
+typedef char *String;
+typedef unsigned long size_type;
+typedef int MyInt;
+typedef Pair Pair;
+typedef struct Node Node;
+typedef struct Node Node;
+struct Node { int val; Node *next; };
+
+MyInt g0 = 3;
+Pair g1;
+
+MyInt add(MyInt x, MyInt y) { return x + y; }
+size_type length(String s)
+{
+	size_type n = 0;
+	for (; *s; s = s + 1)
+		n = n + 1;
+	return n;
+}
+
+int main()
+{
+	ASSERT(1, ({typedef int t; t x=1; x;}));
+	ASSERT(1, ({typedef struct {int a;} t; t x; x.a=1; x.a;}));
+	ASSERT(2, ({typedef struct {int a;} t; { typedef int t; } t x; x.a=2; x.a;}));
+	ASSERT(3, ({MyInt x=3; x;}));
+	ASSERT(16, ({MyInt2 x; sizeof x;}));
+	ASSERT(48, ({MyInt2 x[3]; sizeof x;}));
+	ASSERT(8, ({MyInt2 *p; sizeof p;}));
+	ASSERT(16, ({Pair p; sizeof p;}));
+	ASSERT(7, ({Pair p; p.a=3; p.b=4; p.a+p.b;}));
+	ASSERT(1, ({Switch s=ON; s;}));
+	ASSERT(8, sizeof((size_type)0));
+	ASSERT(3, g0);
+	ASSERT(16, sizeof g1);
+	ASSERT(5, add(2, 3));
+	ASSERT(5, length("hello"));
+	ASSERT(3, ({typedef long L; L x=7; (int)(L)3;}));
+
+	// 内側のスコープの変数がtypedef名を隠す
+	ASSERT(6, ({typedef int T; int x=2; { int T=3; x=T*x; } x;}));
+	ASSERT(4, ({typedef int T; T x=4; { int T=3; } T y=x; y;}));
+	ASSERT(8, ({typedef int T; { long T; } T x; sizeof x + sizeof((T *)0) - 4;}));
+	ASSERT(12, ({int T=3; { typedef int T; T x=4; T *p=&x; *p; } T*4;}));
+	ASSERT(2, ({typedef int MyInt; MyInt x=2; x;}));
+	ASSERT(4, ({int MyInt=4; MyInt;}));
+	ASSERT(9, ({int x=3; typedef int x2; { int y=3; x=x*y; } x;}));
+
+	// foo * bar;はfooがtypedef名なら宣言、そうでなければ乗算
+	ASSERT(3, ({typedef int foo; foo * bar; int x=3; bar=&x; *bar;}));
+	ASSERT(6, ({int foo=3; int bar=2; foo * bar;}));
+	ASSERT(6, ({typedef int foo; int bar=2; int r; { int foo=3; r=foo * bar; } r;}));
+
+	// 同じ型を表すtypedef名は再定義できる
+	ASSERT(3, ({typedef long T; typedef long T; T x=3; sizeof(T) == 8 ? x : 0;}));
+	ASSERT(16, ({typedef MyInt2 A; typedef int A[4]; sizeof(A);}));
+	ASSERT(5, ({Node n; Node *p=&n; n.val=5; n.next=p; n.next->val;}));
+
+	return 0;
+}
@@ -14,6 +14,19 @@ int g5; int g6;
 
 int g7=-1, g8=2, *g9, g10=3;
 int g11={7};
+int グローバル=9;
+
+extern int g12;
+extern int g12;
+int g12=12;
+static int g13=13;
+static int ret_g13(){return g13;}
+extern int g14[];
+int g14[2]={14, 15};
+const int g15=15;
+char *const g16="abc";
+int * const volatile * restrict g17;
+int 関数(int 引数){return 引数*2;}
 
 int main()
 {
@@ -33,9 +46,33 @@ int main()
 
 	ASSERT(15, ({g9 = &g10; *g9*((g7==-1)*g8+g10);}));
 	ASSERT(7, g11);
+	ASSERT(12, g12);
+	ASSERT(13, ret_g13());
+	ASSERT(15, g14[1]);
+	ASSERT(15, g15);
+	ASSERT('b', g16[1]);
+	ASSERT(8, sizeof g17);
+	ASSERT(4, ({const int x=4; x;}));
+	ASSERT(5, ({register int x=5; x;}));
+	ASSERT(6, ({int const volatile x=6; x;}));
 
 	ASSERT(30, ({char x, y; x=y=29; x+(x==y);}));
 	ASSERT(31, ({char x=31; char *p=&x; *p;}));
+	ASSERT(-1, ({short x=-1; x;}));
+	ASSERT(-3, ({int x=-3; long y=x; y;}));
+	ASSERT(-7, ({long long x=-7; x;}));
+	ASSERT(0, ({int x=4294967296; x;}));
+	ASSERT(-1, ({short x=65535; x;}));
+	ASSERT(-128, ({char x=128; x;}));
+	ASSERT(1, ({long x=4294967296; x/4294967296;}));
+	ASSERT(3, ({union {int i; short s[2];} x; x.s[0]=3; x.s[1]=0; x.i;}));
+
+	ASSERT(3, ({int 変数=3; 変数;}));
+	ASSERT(5, ({int \u3042=5; あ;}));
+	ASSERT(7, ({int café=7; caf\u00e9;}));
+	ASSERT(11, ({int x\U0001D7D9=11; x𝟙;}));
+	ASSERT(9, グローバル);
+	ASSERT(8, 関数(4));
 
 	return 0;
 }